@@ -0,0 +1,298 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Result;
+use serde::Serialize;
+use tokio::time::{sleep, Duration};
+
+use crate::api::client::{ApiClient, Auth, Timeout};
+use crate::api::error::ApiError;
+use crate::api::protocol::{DeviceCredentials, DeviceRegistrationRequest, DeviceRegistrationResponse, TokenRefreshRequest};
+
+// Recovery from a rejected device token. The API layer reports every 401 on a
+// device-authenticated request here. From then on device traffic is held back
+// (callers fall back to the offline queue), the sync engine pauses, and a
+// background task tries to get working credentials without bothering the user.
+static SUSPENDED: AtomicBool = AtomicBool::new(false);
+
+// Tauri event the UI listens to for the re-login prompt
+pub const AUTH_STATE_EVENT: &str = "auth-state-changed";
+
+// Pause between recovery attempts while the server can't be reached
+const RECOVERY_RETRY_SECS: u64 = 30;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum AuthState {
+    /// The device token was rejected and new credentials are being requested
+    Recovering,
+    /// New credentials are in place and syncing has resumed
+    Restored,
+    /// Automatic recovery failed; the user has to log in again
+    ReloginRequired { reason: String },
+}
+
+/// True while the device token is known to be bad
+pub fn is_suspended() -> bool {
+    SUSPENDED.load(Ordering::SeqCst)
+}
+
+fn emit(state: AuthState) {
+    super::emit(AUTH_STATE_EVENT, state);
+}
+
+/// Called by the API layer when the server answers 401 to the device token.
+/// Only the first report starts a recovery; the rest are absorbed.
+pub fn report_unauthorized(url: &str) {
+    if SUSPENDED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    log::warn!("🔒 Device token rejected by {}, pausing sync and renewing credentials", url);
+    emit(AuthState::Recovering);
+    tokio::spawn(recover());
+}
+
+/// Credentials are valid again (recovery or a fresh login): resume normal traffic
+pub fn clear() {
+    if SUSPENDED.swap(false, Ordering::SeqCst) {
+        emit(AuthState::Restored);
+    }
+    crate::sampling::sync_engine::request_sync();
+}
+
+/// Forget any pending recovery, e.g. on logout
+pub fn reset() {
+    SUSPENDED.store(false, Ordering::SeqCst);
+}
+
+async fn recover() {
+    loop {
+        // Logged out in the meantime - nothing left to recover
+        if !is_suspended() || !crate::sampling::is_authenticated().await {
+            return;
+        }
+
+        match renew_credentials().await {
+            Ok(()) => {
+                log::info!("🔓 Device credentials renewed, resuming sync");
+                crate::utils::logging::log_remote_non_blocking(
+                    "auth_recovered",
+                    "info",
+                    "Device credentials renewed after a 401",
+                    None,
+                ).await;
+                clear();
+                return;
+            }
+            Err(e) if is_unreachable(&e) => {
+                log::warn!("Credential renewal could not reach the server, retrying in {}s: {}", RECOVERY_RETRY_SECS, e);
+                sleep(Duration::from_secs(RECOVERY_RETRY_SECS)).await;
+            }
+            Err(e) => {
+                log::error!("Credential renewal failed, user must log in again: {}", e);
+                crate::utils::logging::log_remote_non_blocking(
+                    "auth_relogin_required",
+                    "warn",
+                    "Device credentials could not be renewed",
+                    Some(serde_json::json!({ "error": e.to_string() })),
+                ).await;
+                // Stay suspended: queued data waits for the next successful login
+                emit(AuthState::ReloginRequired { reason: e.to_string() });
+                return;
+            }
+        }
+    }
+}
+
+/// Network trouble (or a struggling server) says nothing about the credentials
+fn is_unreachable(error: &anyhow::Error) -> bool {
+    match error.downcast_ref::<ApiError>() {
+        Some(ApiError::Network { .. }) => true,
+        Some(e) => e.status().is_some_and(|status| status >= 500),
+        None => false,
+    }
+}
+
+/// Try a token refresh first, then a fresh registration for the same employee
+async fn renew_credentials() -> Result<()> {
+    let client = ApiClient::new().await?;
+    let credentials = current_credentials().await;
+    let employee_id = {
+        let state = crate::storage::get_global_app_state()?;
+        let state = state.lock().await;
+        state.employee_id.clone()
+    };
+
+    let (device_id, device_token) = request_credentials(&client, credentials, employee_id).await?;
+    store_credentials(device_id, device_token).await
+}
+
+/// Ask the server for working credentials in exchange for the rejected ones.
+/// Returns (device ID, device token).
+async fn request_credentials(
+    client: &ApiClient,
+    credentials: Result<(String, String), ApiError>,
+    employee_id: Option<String>,
+) -> Result<(String, String)> {
+    let device = match refresh_token(client, credentials).await {
+        Ok(device) => device,
+        Err(e) if e.is_auth() || e.status() == Some(404) => {
+            log::info!("Token refresh refused ({}), re-registering device", e);
+            let employee_id = employee_id.ok_or_else(|| ApiError::Auth("No employee ID to re-register with".to_string()))?;
+            reregister_device(client, employee_id).await?
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let device_token = device
+        .device_token
+        .ok_or_else(|| anyhow::anyhow!("Server did not return a device token"))?;
+    Ok((device.device_id, device_token))
+}
+
+async fn refresh_token(client: &ApiClient, credentials: Result<(String, String), ApiError>) -> Result<DeviceCredentials, ApiError> {
+    let (device_token, device_id) = credentials?;
+    let body = TokenRefreshRequest {
+        device_id: device_id.clone(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+    };
+
+    let response: DeviceRegistrationResponse = client
+        .post("/api/devices/refresh-token", &body)
+        .auth(Auth::Bearer(device_token))
+        .header("X-Device-ID", device_id)
+        .timeout(Timeout::Interactive)
+        .send_json()
+        .await?;
+    Ok(response.device)
+}
+
+async fn reregister_device(client: &ApiClient, employee_id: String) -> Result<DeviceCredentials, ApiError> {
+    let body = DeviceRegistrationRequest {
+        employee_id,
+        device_name: crate::commands::get_device_name(),
+        platform: crate::commands::get_platform_name().to_string(),
+        version: crate::commands::get_os_version(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+    };
+
+    let response: DeviceRegistrationResponse = client
+        .post("/api/devices/employee-register", &body)
+        .auth(Auth::None)
+        .timeout(Timeout::Interactive)
+        .send_json()
+        .await?;
+    Ok(response.device)
+}
+
+async fn current_credentials() -> Result<(String, String), ApiError> {
+    let device_token = crate::storage::get_device_token().await.map_err(|e| ApiError::Auth(e.to_string()))?;
+    let device_id = crate::storage::get_device_id().await.map_err(|e| ApiError::Auth(e.to_string()))?;
+    Ok((device_token, device_id))
+}
+
+/// Swap the new credentials into app state and the stored session
+async fn store_credentials(device_id: String, device_token: String) -> Result<()> {
+    {
+        let state = crate::storage::get_global_app_state()?;
+        let mut state = state.lock().await;
+        state.device_token = Some(device_token.clone());
+        state.device_id = Some(device_id.clone());
+    }
+
+    if let Ok(Some(mut session)) = crate::storage::secure_store::get_session_data().await {
+        session.device_token = device_token;
+        session.device_id = device_id;
+        if let Err(e) = crate::storage::secure_store::store_session_data(&session).await {
+            log::warn!("Failed to persist renewed device credentials: {}", e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::connectivity::{self, Outcome};
+    use crate::api::test_support::mock_server;
+
+    const NEW_DEVICE: &str = "{\"device\":{\"deviceId\":\"device-2\",\"deviceToken\":\"token-2\"}}";
+
+    fn json_response(status: &str, body: &str) -> String {
+        format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body)
+    }
+
+    fn old_credentials() -> Result<(String, String), ApiError> {
+        Ok(("token-1".to_string(), "device-1".to_string()))
+    }
+
+    #[tokio::test]
+    async fn test_refresh_renews_the_rejected_token() {
+        let (client, server) = mock_server(vec![json_response("200 OK", NEW_DEVICE)]).await;
+
+        let renewed = request_credentials(&client, old_credentials(), Some("employee-1".to_string())).await.unwrap();
+        assert_eq!(renewed, ("device-2".to_string(), "token-2".to_string()));
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("post /api/devices/refresh-token"));
+        assert!(requests[0].contains("authorization: bearer token-1"));
+        assert!(requests[0].contains("x-device-id: device-1"));
+    }
+
+    #[tokio::test]
+    async fn test_refused_refresh_falls_back_to_registration() {
+        let (client, server) = mock_server(vec![
+            json_response("401 Unauthorized", "{\"error\":\"token revoked\"}"),
+            json_response("200 OK", NEW_DEVICE),
+        ])
+        .await;
+
+        let renewed = request_credentials(&client, old_credentials(), Some("employee-1".to_string())).await.unwrap();
+        assert_eq!(renewed, ("device-2".to_string(), "token-2".to_string()));
+
+        let requests = server.await.unwrap();
+        assert!(requests[1].starts_with("post /api/devices/employee-register"));
+        assert!(!requests[1].contains("authorization:"));
+    }
+
+    #[tokio::test]
+    async fn test_refused_registration_needs_a_new_login() {
+        let (client, server) = mock_server(vec![
+            json_response("404 Not Found", "{}"),
+            json_response("403 Forbidden", "{\"error\":\"employee disabled\"}"),
+        ])
+        .await;
+
+        let error = request_credentials(&client, old_credentials(), Some("employee-1".to_string())).await.unwrap_err();
+        assert!(!is_unreachable(&error));
+        assert_eq!(server.await.unwrap().len(), 2);
+
+        // Without an employee to register for there is nothing left to try
+        let (client, server) = mock_server(vec![json_response("401 Unauthorized", "{}")]).await;
+        let error = request_credentials(&client, old_credentials(), None).await.unwrap_err();
+        assert!(!is_unreachable(&error));
+        assert_eq!(server.await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_server_keeps_recovery_going() {
+        let (client, server) = mock_server(vec![json_response("503 Service Unavailable", "")]).await;
+        let error = request_credentials(&client, old_credentials(), Some("employee-1".to_string())).await.unwrap_err();
+        assert!(is_unreachable(&error));
+        server.await.unwrap();
+
+        // Nothing listening on the port any more
+        let error = request_credentials(&client, old_credentials(), Some("employee-1".to_string())).await.unwrap_err();
+        assert!(is_unreachable(&error));
+
+        connectivity::record(Outcome::Reached);
+    }
+
+    #[test]
+    fn test_only_network_and_server_errors_are_unreachable() {
+        assert!(is_unreachable(&anyhow::Error::new(ApiError::from_status(502, ""))));
+        assert!(!is_unreachable(&anyhow::Error::new(ApiError::from_status(401, ""))));
+        assert!(!is_unreachable(&anyhow::Error::new(ApiError::Auth("no token".to_string()))));
+        assert!(!is_unreachable(&anyhow::anyhow!("Server did not return a device token")));
+    }
+}
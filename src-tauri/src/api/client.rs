@@ -1,101 +1,292 @@
 use anyhow::Result;
-use reqwest::{Client, Response};
+use reqwest::{Client, Method, Response};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use serde_json::Value;
+use std::sync::OnceLock;
 use std::time::Duration;
 
-use crate::storage::secure_store;
+use super::connectivity::{self, Outcome};
+use super::error::{ApiError, DeliveryError};
 
-use std::env;
+// One connection pool for the whole agent. Every backend call goes through it so
+// headers, timeouts and error handling stay consistent.
+static HTTP_CLIENT: OnceLock<Client> = OnceLock::new();
+
+const CONNECT_TIMEOUT_SECS: u64 = 10;
+
+pub fn user_agent() -> String {
+    format!("TrackEx-Agent/{}", env!("CARGO_PKG_VERSION"))
+}
+
+fn http_client() -> &'static Client {
+    HTTP_CLIENT.get_or_init(|| {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("X-Agent-Version", reqwest::header::HeaderValue::from_static(env!("CARGO_PKG_VERSION")));
+
+        Client::builder()
+            .user_agent(user_agent())
+            .default_headers(headers)
+            .connect_timeout(Duration::from_secs(CONNECT_TIMEOUT_SECS))
+            .build()
+            .unwrap_or_else(|e| {
+                log::error!("Failed to build HTTP client, using defaults: {}", e);
+                Client::new()
+            })
+    })
+}
+
+/// How long a request may take end to end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timeout {
+    /// Fire-and-forget telemetry that must never hold anything up
+    Telemetry,
+    /// Health checks and calls made while the user waits on a click
+    Quick,
+    /// Short reads such as session and config lookups
+    Interactive,
+    /// Event, heartbeat and job traffic
+    Standard,
+    /// Screenshot uploads
+    Upload,
+    /// Long-lived server-push streams. No overall limit; the duration is the longest
+    /// silence allowed between chunks, enforced by the stream reader.
+    Stream,
+}
+
+impl Timeout {
+    pub fn duration(self) -> Duration {
+        match self {
+            Timeout::Telemetry => Duration::from_millis(500),
+            Timeout::Quick => Duration::from_secs(5),
+            Timeout::Interactive => Duration::from_secs(10),
+            Timeout::Standard => Duration::from_secs(30),
+            Timeout::Upload => Duration::from_secs(60),
+            Timeout::Stream => Duration::from_secs(90),
+        }
+    }
+}
+
+/// Credentials attached to a request
+#[derive(Debug, Clone)]
+pub enum Auth {
+    /// Login, health checks, remote logs
+    None,
+    /// A token that isn't (yet) the stored device token, e.g. while validating it
+    Bearer(String),
+    /// This device's stored token and ID (`Authorization` + `X-Device-ID`)
+    Device,
+}
 
 pub struct ApiClient {
-    client: Client,
     base_url: String,
 }
 
 impl ApiClient {
+    /// Client for the configured server
     pub async fn new() -> Result<Self> {
-        
-
         let base_url = crate::storage::get_server_url().await?;
+        Ok(Self::with_base_url(&base_url))
+    }
 
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .user_agent(format!("TrackEx-Agent/{}", env!("CARGO_PKG_VERSION")))
-            .build()?;
+    /// Client for an explicit server, e.g. the one typed on the login screen
+    pub fn with_base_url(base_url: &str) -> Self {
+        Self { base_url: base_url.trim_end_matches('/').to_string() }
+    }
 
-        Ok(Self { client, base_url })
+    pub fn base_url(&self) -> &str {
+        &self.base_url
     }
 
-    pub async fn get_with_auth(&self, endpoint: &str) -> Result<Response> {
-        let device_token = crate::storage::get_device_token().await
-            .map_err(|_| anyhow::anyhow!("No device token available"))?;
-        log::info!("Device token: {}", device_token);
-        let device_id = crate::storage::get_device_id().await
-            .map_err(|_| anyhow::anyhow!("No device ID available"))?;
-        let url = format!("{}{}", self.base_url, endpoint);
+    pub fn request(&self, method: Method, endpoint: &str) -> ApiRequest {
+        ApiRequest {
+            method,
+            url: format!("{}{}", self.base_url, endpoint),
+            auth: Auth::Device,
+            timeout: Timeout::Standard,
+            body: None,
+            headers: Vec::new(),
+        }
+    }
 
-        let response = self.client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", device_token))
-            .header("X-Device-ID", device_id.clone())
-            .header("Content-Type", "application/json")
-            .send()
-            .await?;
+    pub fn get(&self, endpoint: &str) -> ApiRequest {
+        self.request(Method::GET, endpoint)
+    }
 
-        Ok(response)
+    pub fn post<T: Serialize + ?Sized>(&self, endpoint: &str, body: &T) -> ApiRequest {
+        self.request(Method::POST, endpoint).json(body)
     }
+}
 
-    pub async fn post_with_auth(&self, endpoint: &str, body: &Value) -> Result<Response> {
-        let device_token = crate::storage::get_device_token().await
-            .map_err(|_| anyhow::anyhow!("No device token available"))?;
-        let device_id = crate::storage::get_device_id().await
-            .map_err(|_| anyhow::anyhow!("No device ID available"))?;
-        let url = format!("{}{}", self.base_url, endpoint);
+/// A request being built. Defaults to device auth and the standard timeout.
+pub struct ApiRequest {
+    method: Method,
+    url: String,
+    auth: Auth,
+    timeout: Timeout,
+    body: Option<Result<Value, String>>,
+    headers: Vec<(&'static str, String)>,
+}
 
-        let response = self.client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", device_token))
-            .header("X-Device-ID", device_id.clone())
-            .header("Content-Type", "application/json")
-            .json(body)
-            .send()
-            .await?;
+impl ApiRequest {
+    pub fn auth(mut self, auth: Auth) -> Self {
+        self.auth = auth;
+        self
+    }
 
-        Ok(response)
+    pub fn timeout(mut self, timeout: Timeout) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Serialize `body` as the JSON request body, through the privacy filter.
+    /// A body that can't be serialized is reported by `send` rather than sent half-formed.
+    pub fn json<T: Serialize + ?Sized>(mut self, body: &T) -> Self {
+        self.body = Some(
+            serde_json::to_value(body)
+                .map(crate::policy::filter::filter_payload)
+                .map_err(|e| e.to_string()),
+        );
+        self
     }
 
-    #[allow(dead_code)]
-    pub async fn post(&self, endpoint: &str, body: &Value) -> Result<Response> {
-        let url = format!("{}{}", self.base_url, endpoint);
+    pub fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
 
-        let response = self.client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .json(body)
-            .send()
-            .await?;
+    /// Lets the server drop a repeated delivery of the same item
+    pub fn idempotency_key(self, key: &str) -> Self {
+        self.header("Idempotency-Key", key)
+    }
 
-        Ok(response)
+    pub fn url(&self) -> &str {
+        &self.url
     }
 
-    #[allow(dead_code)]
-    pub async fn put_with_auth(&self, endpoint: &str, body: &Value) -> Result<Response> {
-        let device_token = secure_store::get_device_token().await?
-            .ok_or_else(|| anyhow::anyhow!("No device token available"))?;
-        let device_id = crate::storage::get_device_id().await
-            .map_err(|_| anyhow::anyhow!("No device ID available"))?;
-        let url = format!("{}{}", self.base_url, endpoint);
+    /// Send and return the raw response, whatever its status.
+    /// Only transport failures (connect, timeout, ...) and missing credentials are errors.
+    /// A 401 on device auth starts credential recovery (see `api::auth`); until that
+    /// succeeds, device requests fail fast as `Unauthorized` without touching the network.
+    pub async fn send(self) -> Result<Response, ApiError> {
+        let mut builder = http_client()
+            .request(self.method.clone(), &self.url)
+            .header("Content-Type", "application/json");
+        if self.timeout != Timeout::Stream {
+            builder = builder.timeout(self.timeout.duration());
+        }
 
-        let response = self.client
-            .put(&url)
-            .header("Authorization", format!("Bearer {}", device_token))
-            .header("X-Device-ID", device_id.clone())
-            .header("Content-Type", "application/json")
-            .json(body)
-            .send()
-            .await?;
+        match &self.auth {
+            Auth::None => {}
+            Auth::Bearer(token) => {
+                builder = builder.header("Authorization", format!("Bearer {}", token));
+            }
+            Auth::Device => {
+                if super::auth::is_suspended() {
+                    return Err(ApiError::Unauthorized(DeliveryError::from_status(
+                        401,
+                        "device credentials are being renewed",
+                    )));
+                }
+                let device_token = crate::storage::get_device_token().await
+                    .map_err(|_| ApiError::Auth("No device token available".to_string()))?;
+                let device_id = crate::storage::get_device_id().await
+                    .map_err(|_| ApiError::Auth("No device ID available".to_string()))?;
+                builder = builder
+                    .header("Authorization", format!("Bearer {}", device_token))
+                    .header("X-Device-ID", device_id);
+            }
+        }
 
+        for (name, value) in &self.headers {
+            builder = builder.header(*name, value);
+        }
+        match &self.body {
+            Some(Ok(body)) => builder = builder.json(body),
+            Some(Err(reason)) => {
+                return Err(ApiError::Schema { url: self.url.clone(), reason: format!("request body: {}", reason) });
+            }
+            None => {}
+        }
+
+        let result = builder.send().await;
+
+        // Telemetry runs on a hair-trigger timeout, so it says little about the connection
+        if self.timeout != Timeout::Telemetry {
+            connectivity::record(match &result {
+                Ok(response) if response.status().is_server_error() => Outcome::Failed,
+                Ok(_) => Outcome::Reached,
+                Err(e) if e.is_connect() => Outcome::Unreachable,
+                Err(_) => Outcome::Failed,
+            });
+        }
+
+        let response = result.map_err(|e| network_error(e, &self.url, self.timeout))?;
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED && matches!(self.auth, Auth::Device) {
+            super::auth::report_unauthorized(&self.url);
+        }
         Ok(response)
     }
+
+    /// Send and fail on any non-2xx status (`Unauthorized` for 401, `Server` otherwise)
+    pub async fn send_checked(self) -> Result<Response, ApiError> {
+        let url = self.url.clone();
+        let response = self.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let text = response.text().await.unwrap_or_default();
+        log::debug!("{} returned {}: {}", url, status, text);
+        Err(ApiError::from_status(status.as_u16(), &text))
+    }
+
+    /// Send, check the status and decode the JSON body into a protocol type
+    pub async fn send_json<T: DeserializeOwned>(self) -> Result<T, ApiError> {
+        let url = self.url.clone();
+        let response = self.send_checked().await?;
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| network_error(e, &url, Timeout::Standard))?;
+        serde_json::from_slice(&bytes).map_err(|e| ApiError::Schema { url, reason: e.to_string() })
+    }
+}
+
+/// Turn a transport failure into the user-facing message used across the agent.
+/// These are all retryable; `offline_queue::classify_error` treats them as transient.
+fn network_error(source: reqwest::Error, url: &str, timeout: Timeout) -> ApiError {
+    let message = if source.is_connect() {
+        format!("Network error: Cannot connect to server at {}. Please check your network connection and ensure the backend is running.", url)
+    } else if source.is_timeout() {
+        format!("Network error: Request timeout after {:?}. Server may be slow or unresponsive.", timeout.duration())
+    } else {
+        format!("Network error: {}", source)
+    };
+    ApiError::Network { message, source }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_url_is_normalized() {
+        let client = ApiClient::with_base_url("https://example.com/");
+        assert_eq!(client.get("/api/health").url(), "https://example.com/api/health");
+    }
+
+    #[tokio::test]
+    async fn test_request_bodies_pass_the_privacy_filter() {
+        let _policy = crate::policy::toggles::test_policy(Default::default()).await;
+        let client = ApiClient::with_base_url("https://example.com");
+        let body = serde_json::json!({
+            "app_id": "com.google.Chrome",
+            "window_title": "Performance review - Google Chrome",
+        });
+        let request = client.post("/api/ingest/events", &body);
+        let sent = request.body.unwrap().unwrap();
+        assert_eq!(sent["window_title"], crate::policy::filter::REDACTED_TITLE);
+    }
+}
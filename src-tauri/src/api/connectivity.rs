@@ -0,0 +1,254 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::watch;
+use tokio::time::{sleep, Duration};
+
+use crate::api::client::{ApiClient, Auth, Timeout};
+
+// Connectivity as observed from real traffic. The API client reports the outcome
+// of every request here, so senders can check `is_offline()` instead of probing
+// the server themselves. Only while offline does the monitor probe `/api/health`,
+// with backoff, to notice when the server is back.
+
+// Tauri event carrying each transition to the UI
+pub const CONNECTIVITY_EVENT: &str = "connectivity-changed";
+
+// Consecutive failed requests before a degraded connection counts as offline
+const OFFLINE_AFTER_FAILURES: u32 = 3;
+// Probe schedule while offline: doubles from PROBE_BASE_SECS up to PROBE_MAX_SECS
+const PROBE_BASE_SECS: u64 = 5;
+const PROBE_MAX_SECS: u64 = 5 * 60;
+
+static TRACKER: OnceLock<ConnectivityTracker> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Connectivity {
+    /// Requests are getting through
+    Online,
+    /// Some requests are failing (timeouts, 5xx) but the server isn't gone
+    Degraded,
+    /// The server can't be reached; senders queue instead of trying
+    Offline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ConnectivityStatus {
+    pub state: Connectivity,
+    pub since: DateTime<Utc>,
+}
+
+/// How a request ended, as far as reachability is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Any answer from the server other than a 5xx (a 4xx still proves it's reachable)
+    Reached,
+    /// Timeout or 5xx
+    Failed,
+    /// Connection refused, DNS or TLS failure
+    Unreachable,
+}
+
+/// The connectivity state machine: request outcomes in, state transitions out.
+/// The agent uses one process-wide instance through the free functions below.
+pub struct ConnectivityTracker {
+    consecutive_failures: AtomicU32,
+    state: watch::Sender<ConnectivityStatus>,
+}
+
+impl Default for ConnectivityTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConnectivityTracker {
+    /// Assume online until traffic says otherwise
+    pub fn new() -> Self {
+        Self {
+            consecutive_failures: AtomicU32::new(0),
+            state: watch::channel(ConnectivityStatus { state: Connectivity::Online, since: Utc::now() }).0,
+        }
+    }
+
+    pub fn status(&self) -> ConnectivityStatus {
+        *self.state.borrow()
+    }
+
+    pub fn current(&self) -> Connectivity {
+        self.status().state
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<ConnectivityStatus> {
+        self.state.subscribe()
+    }
+
+    /// Feed in the outcome of a request. Returns the (previous, new) state when it changed.
+    pub fn record(&self, outcome: Outcome) -> Option<(Connectivity, Connectivity)> {
+        let next = match outcome {
+            Outcome::Reached => {
+                self.consecutive_failures.store(0, Ordering::SeqCst);
+                Connectivity::Online
+            }
+            Outcome::Unreachable => {
+                self.consecutive_failures.fetch_add(1, Ordering::SeqCst);
+                Connectivity::Offline
+            }
+            Outcome::Failed => {
+                let failures = self.consecutive_failures.fetch_add(1, Ordering::SeqCst) + 1;
+                if failures >= OFFLINE_AFTER_FAILURES {
+                    Connectivity::Offline
+                } else {
+                    Connectivity::Degraded
+                }
+            }
+        };
+
+        let mut previous = next;
+        let changed = self.state.send_if_modified(|status| {
+            if status.state == next {
+                return false;
+            }
+            previous = status.state;
+            *status = ConnectivityStatus { state: next, since: Utc::now() };
+            true
+        });
+        changed.then_some((previous, next))
+    }
+}
+
+fn tracker() -> &'static ConnectivityTracker {
+    TRACKER.get_or_init(ConnectivityTracker::new)
+}
+
+pub fn status() -> ConnectivityStatus {
+    tracker().status()
+}
+
+pub fn current() -> Connectivity {
+    tracker().current()
+}
+
+pub fn is_offline() -> bool {
+    current() == Connectivity::Offline
+}
+
+/// Receive every state transition
+pub fn subscribe() -> watch::Receiver<ConnectivityStatus> {
+    tracker().subscribe()
+}
+
+/// Feed the outcome of a real request into the agent's state machine
+pub fn record(outcome: Outcome) {
+    let Some((previous, next)) = tracker().record(outcome) else {
+        return;
+    };
+
+    match next {
+        Connectivity::Offline => log::warn!("📡 Connectivity: {:?} -> offline, queuing until the server is back", previous),
+        _ => log::info!("📡 Connectivity: {:?} -> {:?}", previous, next),
+    }
+    super::emit(CONNECTIVITY_EVENT, status());
+
+    // Back from offline: deliver what piled up without waiting for the next tick
+    if previous == Connectivity::Offline {
+        crate::sampling::sync_engine::request_sync();
+    }
+}
+
+fn probe_delay(attempt: u32) -> Duration {
+    let secs = PROBE_BASE_SECS.saturating_mul(1 << attempt.min(16)).min(PROBE_MAX_SECS);
+    Duration::from_secs(secs)
+}
+
+/// Probe the server while offline; idle otherwise. The probe goes through the
+/// regular client, so its outcome is recorded like any other request.
+pub async fn start_connectivity_monitor() {
+    let mut changes = subscribe();
+
+    loop {
+        // Wait until we go offline
+        while changes.borrow_and_update().state != Connectivity::Offline {
+            if changes.changed().await.is_err() {
+                return;
+            }
+        }
+
+        let mut attempt = 0;
+        while is_offline() {
+            sleep(probe_delay(attempt)).await;
+            attempt += 1;
+
+            let Ok(client) = ApiClient::new().await else {
+                continue;
+            };
+            let result = client
+                .get("/api/health")
+                .auth(Auth::None)
+                .timeout(Timeout::Quick)
+                .send()
+                .await;
+            log::debug!("Connectivity probe {}: {}", attempt, match &result {
+                Ok(response) => response.status().to_string(),
+                Err(e) => e.to_string(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_backoff_is_capped() {
+        assert_eq!(probe_delay(0), Duration::from_secs(PROBE_BASE_SECS));
+        assert_eq!(probe_delay(1), Duration::from_secs(PROBE_BASE_SECS * 2));
+        assert_eq!(probe_delay(30), Duration::from_secs(PROBE_MAX_SECS));
+    }
+
+    #[test]
+    fn test_state_follows_request_outcomes() {
+        let tracker = ConnectivityTracker::new();
+        assert_eq!(tracker.current(), Connectivity::Online);
+        assert_eq!(tracker.record(Outcome::Reached), None);
+
+        assert_eq!(tracker.record(Outcome::Failed), Some((Connectivity::Online, Connectivity::Degraded)));
+        for _ in 2..OFFLINE_AFTER_FAILURES {
+            assert_eq!(tracker.record(Outcome::Failed), None);
+        }
+        assert_eq!(tracker.record(Outcome::Failed), Some((Connectivity::Degraded, Connectivity::Offline)));
+        assert_eq!(tracker.current(), Connectivity::Offline);
+
+        assert_eq!(tracker.record(Outcome::Reached), Some((Connectivity::Offline, Connectivity::Online)));
+        assert_eq!(tracker.record(Outcome::Unreachable), Some((Connectivity::Online, Connectivity::Offline)));
+    }
+
+    #[test]
+    fn test_success_resets_the_failure_count() {
+        let tracker = ConnectivityTracker::new();
+        for _ in 1..OFFLINE_AFTER_FAILURES {
+            tracker.record(Outcome::Failed);
+        }
+        tracker.record(Outcome::Reached);
+        tracker.record(Outcome::Failed);
+        assert_eq!(tracker.current(), Connectivity::Degraded);
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_see_transitions() {
+        let tracker = ConnectivityTracker::new();
+        let mut changes = tracker.subscribe();
+
+        tracker.record(Outcome::Unreachable);
+        changes.changed().await.unwrap();
+        assert_eq!(changes.borrow_and_update().state, Connectivity::Offline);
+
+        // Repeating the same state isn't a transition
+        tracker.record(Outcome::Unreachable);
+        assert!(!changes.has_changed().unwrap());
+    }
+}
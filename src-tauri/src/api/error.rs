@@ -0,0 +1,138 @@
+/// Whether a failed delivery is worth retrying
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// Network errors, timeouts, 5xx and 429 - retry with backoff
+    Transient,
+    /// The server refused the item itself (4xx validation) - retrying won't help
+    Permanent,
+    /// The server refused the device credentials (401). The item is fine, so it is
+    /// kept as-is until the device is re-authenticated.
+    Unauthorized,
+}
+
+impl FailureKind {
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            401 => FailureKind::Unauthorized,
+            408 | 425 | 429 => FailureKind::Transient,
+            400..=499 => FailureKind::Permanent,
+            _ => FailureKind::Transient,
+        }
+    }
+}
+
+/// Error returned by the upload functions when the server answered with a failure status
+#[derive(Debug, thiserror::Error)]
+#[error("Delivery failed with status {status}: {body}")]
+pub struct DeliveryError {
+    pub kind: FailureKind,
+    pub status: u16,
+    pub body: String,
+}
+
+impl DeliveryError {
+    pub fn from_status(status: u16, body: &str) -> Self {
+        Self {
+            kind: FailureKind::from_status(status),
+            status,
+            body: body.to_string(),
+        }
+    }
+}
+
+/// Why a backend call failed, split by what the caller can do about it
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    /// No stored credentials to send
+    #[error("Not authenticated: {0}")]
+    Auth(String),
+
+    /// The server refused the device credentials (401). Classified as
+    /// `FailureKind::Unauthorized`, so queued items keep their attempts.
+    #[error("Device credentials rejected: {0}")]
+    Unauthorized(#[source] DeliveryError),
+
+    /// The request never got an answer (DNS, connect, TLS, timeout)
+    #[error("{message}")]
+    Network {
+        message: String,
+        #[source]
+        source: reqwest::Error,
+    },
+
+    /// The server answered with a failure status. The `DeliveryError` source says
+    /// whether retrying makes sense (see `offline_queue::classify_error`).
+    #[error("Server error: {0}")]
+    Server(#[source] DeliveryError),
+
+    /// The server answered 2xx, but the body doesn't match the protocol
+    #[error("Unexpected response from {url}: {reason}")]
+    Schema { url: String, reason: String },
+}
+
+impl ApiError {
+    /// Map a non-2xx response to `Unauthorized` or `Server`
+    pub fn from_status(status: u16, body: &str) -> Self {
+        match status {
+            401 => ApiError::Unauthorized(DeliveryError::from_status(status, body)),
+            _ => ApiError::Server(DeliveryError::from_status(status, body)),
+        }
+    }
+
+    /// Re-authenticating (rather than retrying) is what fixes this error
+    pub fn is_auth(&self) -> bool {
+        matches!(self, ApiError::Auth(_) | ApiError::Unauthorized(_))
+    }
+
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ApiError::Server(e) | ApiError::Unauthorized(e) => Some(e.status),
+            ApiError::Network { source, .. } => source.status().map(|s| s.as_u16()),
+            _ => None,
+        }
+    }
+
+    pub fn is_connect(&self) -> bool {
+        matches!(self, ApiError::Network { source, .. } if source.is_connect())
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, ApiError::Network { source, .. } if source.is_timeout())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::offline_queue::classify_error;
+
+    #[test]
+    fn test_failure_kind_from_status() {
+        assert_eq!(FailureKind::from_status(500), FailureKind::Transient);
+        assert_eq!(FailureKind::from_status(429), FailureKind::Transient);
+        assert_eq!(FailureKind::from_status(408), FailureKind::Transient);
+        assert_eq!(FailureKind::from_status(400), FailureKind::Permanent);
+        assert_eq!(FailureKind::from_status(401), FailureKind::Unauthorized);
+
+        let error = DeliveryError::from_status(422, "invalid payload");
+        assert_eq!((error.kind, error.status, error.body.as_str()), (FailureKind::Permanent, 422, "invalid payload"));
+    }
+
+    #[test]
+    fn test_status_mapping() {
+        assert!(matches!(ApiError::from_status(401, ""), ApiError::Unauthorized(_)));
+        assert!(matches!(ApiError::from_status(403, ""), ApiError::Server(_)));
+        assert!(matches!(ApiError::from_status(500, ""), ApiError::Server(_)));
+        assert_eq!(ApiError::from_status(422, "").status(), Some(422));
+    }
+
+    #[test]
+    fn test_classification_sees_through_api_error() {
+        let rejected = anyhow::Error::new(ApiError::from_status(422, "invalid payload"));
+        assert_eq!(classify_error(&rejected), FailureKind::Permanent);
+
+        // Auth failures are fixed by re-authenticating, not by dropping the item
+        let unauthorized = anyhow::Error::new(ApiError::from_status(401, "expired"));
+        assert_eq!(classify_error(&unauthorized), FailureKind::Unauthorized);
+    }
+}
@@ -0,0 +1,84 @@
+use tokio::time::Duration;
+
+use crate::api::jobs::{JobFuture, JobHandler};
+use crate::api::protocol::{Job, ScreenshotTaken};
+use crate::screenshots::screen_capture;
+
+// Built-in job handlers, registered in `jobs::registry`
+
+/// Capture the screen, upload it and queue a `screenshot_taken` event
+pub struct ScreenshotHandler;
+
+impl JobHandler for ScreenshotHandler {
+    fn job_type(&self) -> &'static str {
+        "screenshot"
+    }
+
+    fn timeout(&self) -> Duration {
+        // Capture plus upload of a full-resolution image on a slow link
+        Duration::from_secs(120)
+    }
+
+    fn run<'a>(&'a self, job: &'a Job) -> JobFuture<'a> {
+        Box::pin(async move {
+            // The live policy, not the one in force when the job was queued
+            if !crate::policy::toggles::get_current_policy().should_take_screenshot() {
+                anyhow::bail!("Screenshots are disabled by policy");
+            }
+
+            let screenshot_data = screen_capture::capture_screen().await?;
+            let upload = crate::api::uploads::upload_screenshot(&screenshot_data).await?;
+
+            let taken = ScreenshotTaken::new(&job.id, upload);
+            let result = serde_json::json!({
+                "storageKey": taken.storage_key,
+                "imageUrl": taken.image_url,
+            });
+            crate::storage::offline_queue::queue_event("screenshot_taken", &serde_json::to_value(taken)?).await?;
+
+            Ok(result)
+        })
+    }
+}
+
+/// Send a diagnostics bundle back as the job result
+pub struct DiagnosticsHandler;
+
+impl JobHandler for DiagnosticsHandler {
+    fn job_type(&self) -> &'static str {
+        "diagnostics"
+    }
+
+    fn run<'a>(&'a self, _job: &'a Job) -> JobFuture<'a> {
+        Box::pin(async move { Ok(crate::diagnostics::collect().await) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::jobs::JobHandler;
+    use crate::policy::toggles::{self, PolicyConfig};
+
+    fn screenshot_job() -> Job {
+        Job {
+            id: "j1".to_string(),
+            job_type: "screenshot".to_string(),
+            status: None,
+            payload: serde_json::json!({}),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_screenshot_jobs_fail_when_policy_disables_them() {
+        let disabled = PolicyConfig { screenshot_enabled: false, screenshot_interval_minutes: 30, ..PolicyConfig::default() };
+        let _policy = toggles::test_policy(disabled).await;
+
+        let error = ScreenshotHandler.run(&screenshot_job()).await.unwrap_err();
+        assert!(error.to_string().contains("disabled by policy"));
+
+        toggles::update_policy(PolicyConfig { screenshot_enabled: true, screenshot_interval_minutes: 0, ..PolicyConfig::default() });
+        let error = ScreenshotHandler.run(&screenshot_job()).await.unwrap_err();
+        assert!(error.to_string().contains("disabled by policy"));
+    }
+}
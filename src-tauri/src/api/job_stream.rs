@@ -0,0 +1,275 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+use anyhow::Result;
+use tokio::time::{sleep, timeout, Duration};
+
+use crate::api::client::{ApiClient, Auth, Timeout};
+use crate::api::error::ApiError;
+use crate::api::protocol::Job;
+
+// Server push for jobs over Server-Sent Events. While the stream is up, jobs
+// arrive the moment they are created and `job_polling` stops polling; when it
+// drops, polling takes over until the stream reconnects.
+const STREAM_ENDPOINT: &str = "/api/ingest/jobs/stream";
+
+// Reconnect schedule: doubles from RECONNECT_BASE_SECS up to RECONNECT_MAX_SECS
+const RECONNECT_BASE_SECS: u64 = 1;
+const RECONNECT_MAX_SECS: u64 = 5 * 60;
+// A stream that stayed up this long resets the reconnect backoff
+const STABLE_STREAM_SECS: u64 = 60;
+// How often a quiet stream checks whether services were stopped
+const STOP_CHECK_SECS: u64 = 5;
+
+static CONNECTED: AtomicBool = AtomicBool::new(false);
+
+/// True while the push stream is open and jobs don't need to be polled for
+pub fn is_connected() -> bool {
+    CONNECTED.load(Ordering::SeqCst)
+}
+
+/// One dispatched Server-Sent Event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    pub event: String,
+    pub data: String,
+    pub id: Option<String>,
+}
+
+/// Incremental `text/event-stream` parser. Chunks may split lines (or UTF-8
+/// sequences) anywhere; complete events are returned as they become available.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+    id: Option<String>,
+}
+
+impl SseParser {
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                if let Some(event) = self.dispatch() {
+                    events.push(event);
+                }
+                continue;
+            }
+            // Comment lines are keep-alives
+            if line.starts_with(':') {
+                continue;
+            }
+
+            let (field, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "event" => self.event = Some(value.to_string()),
+                "data" => self.data.push(value.to_string()),
+                "id" => self.id = Some(value.to_string()),
+                _ => {}
+            }
+        }
+
+        events
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        if self.data.is_empty() {
+            return None;
+        }
+        Some(SseEvent {
+            event: event.unwrap_or_else(|| "message".to_string()),
+            data: std::mem::take(&mut self.data).join("\n"),
+            // The last event ID carries over to later events, as in the SSE spec
+            id: self.id.clone(),
+        })
+    }
+}
+
+/// Clears the connected flag however the stream ends
+struct ConnectedGuard;
+
+impl Drop for ConnectedGuard {
+    fn drop(&mut self) {
+        CONNECTED.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Open the job stream and hand every pushed job to `on_job` until the server
+/// closes it, it goes quiet for too long, or services are stopped.
+/// `last_event_id` is sent on reconnect so the server can replay missed jobs.
+pub async fn stream_jobs(
+    client: &ApiClient,
+    auth: Auth,
+    last_event_id: &mut Option<String>,
+    on_job: &mut impl FnMut(Job),
+) -> Result<()> {
+    let mut request = client
+        .get(STREAM_ENDPOINT)
+        .auth(auth)
+        .timeout(Timeout::Stream)
+        .header("Accept", "text/event-stream");
+    if let Some(id) = last_event_id.as_deref() {
+        request = request.header("Last-Event-ID", id);
+    }
+
+    let mut response = request.send_checked().await?;
+    CONNECTED.store(true, Ordering::SeqCst);
+    let _guard = ConnectedGuard;
+    log::info!("📨 Job stream connected, polling paused");
+
+    // Pick up anything created while we weren't listening
+    super::job_polling::request_poll();
+
+    let mut parser = SseParser::default();
+    let mut last_data = Instant::now();
+    loop {
+        let chunk = match timeout(Duration::from_secs(STOP_CHECK_SECS), response.chunk()).await {
+            Ok(chunk) => chunk?,
+            Err(_) => {
+                if !crate::sampling::is_services_running().await {
+                    return Ok(());
+                }
+                if last_data.elapsed() > Timeout::Stream.duration() {
+                    anyhow::bail!("Job stream silent for {:?}", Timeout::Stream.duration());
+                }
+                continue;
+            }
+        };
+        let Some(chunk) = chunk else {
+            log::info!("Job stream closed by server");
+            return Ok(());
+        };
+        last_data = Instant::now();
+
+        for event in parser.feed(&chunk) {
+            if event.id.is_some() {
+                *last_event_id = event.id.clone();
+            }
+            match event.event.as_str() {
+                "job" | "message" => match serde_json::from_str::<Job>(&event.data) {
+                    Ok(job) => on_job(job),
+                    Err(e) => log::warn!("Ignoring malformed pushed job: {}", e),
+                },
+                "ping" => {}
+                other => log::debug!("Ignoring job stream event '{}'", other),
+            }
+        }
+    }
+}
+
+fn reconnect_delay(attempt: u32) -> Duration {
+    let secs = RECONNECT_BASE_SECS.saturating_mul(1 << attempt.min(16)).min(RECONNECT_MAX_SECS);
+    Duration::from_secs(secs)
+}
+
+/// Keep the push channel open for as long as background services run
+pub async fn run_push_channel() {
+    let mut attempt: u32 = 0;
+    let mut last_event_id = None;
+
+    while crate::sampling::is_services_running().await {
+        // Nothing to listen for while clocked out or paused, and no point while
+        // the server is unreachable or our token is being renewed
+        if !crate::sampling::should_services_run().await
+            || crate::api::connectivity::is_offline()
+            || crate::api::auth::is_suspended()
+        {
+            sleep(Duration::from_secs(STOP_CHECK_SECS)).await;
+            continue;
+        }
+
+        let started = Instant::now();
+        let result = match ApiClient::new().await {
+            Ok(client) => {
+                let mut on_job = |job: Job| {
+                    tokio::spawn(super::job_polling::handle_pushed_job(job));
+                };
+                stream_jobs(&client, Auth::Device, &mut last_event_id, &mut on_job).await
+            }
+            Err(e) => Err(e),
+        };
+
+        if started.elapsed() >= Duration::from_secs(STABLE_STREAM_SECS) {
+            attempt = 0;
+        }
+        let delay = match &result {
+            // A server without the endpoint won't grow one soon: poll, and check back rarely
+            Err(e) if matches!(e.downcast_ref::<ApiError>(), Some(api) if api.status() == Some(404)) => {
+                if attempt == 0 {
+                    log::info!("Server has no job stream, relying on polling");
+                }
+                Duration::from_secs(RECONNECT_MAX_SECS)
+            }
+            Err(e) => {
+                log::warn!("Job stream unavailable, falling back to polling: {}", e);
+                reconnect_delay(attempt)
+            }
+            Ok(()) => reconnect_delay(attempt),
+        };
+        attempt = attempt.saturating_add(1);
+        sleep(delay).await;
+    }
+
+    log::info!("Job stream stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_support::mock_server;
+
+    #[test]
+    fn test_parser_handles_split_chunks_and_comments() {
+        let mut parser = SseParser::default();
+        assert!(parser.feed(b": keep-alive\n\nevent: job\nid: 7\nda").is_empty());
+
+        let events = parser.feed(b"ta: {\"id\":\"j1\"}\r\n\r\ndata: a\ndata: b\n\n");
+        assert_eq!(events, vec![
+            SseEvent { event: "job".to_string(), data: "{\"id\":\"j1\"}".to_string(), id: Some("7".to_string()) },
+            SseEvent { event: "message".to_string(), data: "a\nb".to_string(), id: Some("7".to_string()) },
+        ]);
+    }
+
+    #[test]
+    fn test_reconnect_backoff_is_capped() {
+        assert_eq!(reconnect_delay(0), Duration::from_secs(RECONNECT_BASE_SECS));
+        assert_eq!(reconnect_delay(3), Duration::from_secs(8));
+        assert_eq!(reconnect_delay(40), Duration::from_secs(RECONNECT_MAX_SECS));
+    }
+
+    #[tokio::test]
+    async fn test_stream_jobs_against_mock_server() {
+        let body = ": hello\n\n\
+                    event: job\nid: c1\ndata: {\"id\":\"j1\",\"type\":\"screenshot\",\"status\":\"pending\"}\n\n\
+                    event: ping\ndata: {}\n\n\
+                    event: job\nid: c2\ndata: {\"id\":\"j2\",\"type\":\"diagnostics\"}\n\n";
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let (client, server) = mock_server(vec![response]).await;
+
+        let mut last_event_id = Some("c0".to_string());
+        let mut jobs = Vec::new();
+        let mut on_job = |job: Job| jobs.push(job.id);
+        stream_jobs(&client, Auth::None, &mut last_event_id, &mut on_job).await.unwrap();
+
+        assert_eq!(jobs, vec!["j1", "j2"]);
+        assert_eq!(last_event_id.as_deref(), Some("c2"));
+        assert!(!is_connected());
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("get /api/ingest/jobs/stream"));
+        assert!(requests[0].contains("last-event-id: c0"));
+    }
+}
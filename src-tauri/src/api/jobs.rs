@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+
+use anyhow::Result;
+use serde_json::Value;
+use tokio::time::{timeout, Duration};
+
+use crate::api::client::ApiClient;
+use crate::api::protocol::{Job, JobStatusUpdate};
+use crate::storage::job_store::{self, JobStatus, StoredJob, MAX_JOB_ATTEMPTS};
+
+// Server-issued jobs. Every job goes through the same lifecycle, reported back to
+// the server at each step:
+//
+//   received -> in_progress -> succeeded | failed
+//
+// Each step is written to the job journal first (`storage::job_store`), so a job
+// ID is only ever run once, a job cut short by a shutdown is resumed on the next
+// start, and a final status that couldn't be delivered is re-sent later.
+
+const JOB_STATUS_ENDPOINT: &str = "/api/ingest/jobs";
+// Handlers that don't say otherwise get this long before the job is failed
+const DEFAULT_JOB_TIMEOUT_SECS: u64 = 60;
+
+pub type JobFuture<'a> = Pin<Box<dyn Future<Output = Result<Value>> + Send + 'a>>;
+
+/// Runs one type of job. The returned value is reported as the job's result.
+pub trait JobHandler: Send + Sync {
+    /// The `type` of job this handler takes
+    fn job_type(&self) -> &'static str;
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(DEFAULT_JOB_TIMEOUT_SECS)
+    }
+
+    fn run<'a>(&'a self, job: &'a Job) -> JobFuture<'a>;
+}
+
+#[derive(Default)]
+pub struct JobRegistry {
+    handlers: HashMap<&'static str, Arc<dyn JobHandler>>,
+}
+
+impl JobRegistry {
+    pub fn with(mut self, handler: impl JobHandler + 'static) -> Self {
+        self.register(handler);
+        self
+    }
+
+    pub fn register(&mut self, handler: impl JobHandler + 'static) {
+        self.handlers.insert(handler.job_type(), Arc::new(handler));
+    }
+
+    pub fn get(&self, job_type: &str) -> Option<Arc<dyn JobHandler>> {
+        self.handlers.get(job_type).cloned()
+    }
+}
+
+static REGISTRY: OnceLock<JobRegistry> = OnceLock::new();
+
+fn registry() -> &'static JobRegistry {
+    REGISTRY.get_or_init(|| {
+        JobRegistry::default()
+            .with(super::job_handlers::ScreenshotHandler)
+            .with(super::job_handlers::DiagnosticsHandler)
+    })
+}
+
+/// Run a job received from the server, unless this job ID was seen before.
+/// Returns whether the job was new.
+pub async fn dispatch(job: &Job) -> Result<bool> {
+    if !job_store::record_received(&job.id, &job.job_type, &job.payload).await? {
+        log::debug!("Job {} already handled, skipping", job.id);
+        return Ok(false);
+    }
+
+    log::info!("📋 Job {} ({}) received", job.id, job.job_type);
+    report_progress(&job.id, JobStatus::Received).await;
+    run(job).await?;
+    Ok(true)
+}
+
+async fn run(job: &Job) -> Result<()> {
+    let attempt = job_store::record_started(&job.id).await?;
+    report_progress(&job.id, JobStatus::InProgress).await;
+
+    let outcome = match registry().get(&job.job_type) {
+        Some(handler) => match timeout(handler.timeout(), handler.run(job)).await {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(e)) => Err(format!("{:#}", e)),
+            Err(_) => Err(format!("Timed out after {}s", handler.timeout().as_secs())),
+        },
+        None => Err(format!("Unsupported job type: {}", job.job_type)),
+    };
+
+    match &outcome {
+        Ok(_) => log::info!("✅ Job {} ({}) succeeded", job.id, job.job_type),
+        Err(e) => log::error!("❌ Job {} ({}) failed on attempt {}: {}", job.id, job.job_type, attempt, e),
+    }
+
+    finish(&job.id, outcome).await
+}
+
+/// Record the final status, then tell the server. If that fails the report is
+/// retried by `report_outstanding`.
+async fn finish(job_id: &str, outcome: std::result::Result<Value, String>) -> Result<()> {
+    job_store::record_finished(job_id, outcome.clone()).await?;
+
+    let (status, result, error) = match &outcome {
+        Ok(result) => (JobStatus::Succeeded, Some(result), None),
+        Err(error) => (JobStatus::Failed, None, Some(error.as_str())),
+    };
+    match update_job_status(job_id, status, result, error).await {
+        Ok(()) => job_store::record_reported(job_id).await,
+        Err(e) => {
+            log::warn!("Failed to report job {} as {}, will retry: {}", job_id, status.as_str(), e);
+            Ok(())
+        }
+    }
+}
+
+/// Intermediate statuses are informational; losing one doesn't matter
+async fn report_progress(job_id: &str, status: JobStatus) {
+    if let Err(e) = update_job_status(job_id, status, None, None).await {
+        log::warn!("Failed to set job {} to {}: {}", job_id, status.as_str(), e);
+    }
+}
+
+async fn update_job_status(job_id: &str, status: JobStatus, result: Option<&Value>, error: Option<&str>) -> Result<()> {
+    let client = ApiClient::new().await?;
+    let body = JobStatusUpdate { job_id, status: status.as_str(), result, error };
+    client.post(JOB_STATUS_ENDPOINT, &body).send_checked().await?;
+    Ok(())
+}
+
+fn stored_to_job(stored: &StoredJob) -> Job {
+    Job {
+        id: stored.job_id.clone(),
+        job_type: stored.job_type.clone(),
+        status: None,
+        payload: stored.payload.clone(),
+    }
+}
+
+/// Pick up where the last run left off: run jobs that never finished and
+/// re-send final statuses the server hasn't acknowledged
+pub async fn resume_jobs() {
+    let jobs = match job_store::get_outstanding().await {
+        Ok(jobs) => jobs,
+        Err(e) => {
+            log::error!("Failed to load unfinished jobs: {}", e);
+            return;
+        }
+    };
+
+    for stored in jobs.iter().filter(|job| !job.status.is_finished()) {
+        let result = if stored.attempts >= MAX_JOB_ATTEMPTS {
+            // Something about this job keeps taking the agent down with it
+            log::warn!("Job {} was interrupted {} times, giving up", stored.job_id, stored.attempts);
+            finish(&stored.job_id, Err(format!("Interrupted {} times before completing", stored.attempts))).await
+        } else {
+            log::info!("📋 Resuming job {} ({})", stored.job_id, stored.job_type);
+            run(&stored_to_job(stored)).await
+        };
+        if let Err(e) = result {
+            log::error!("Failed to resume job {}: {}", stored.job_id, e);
+        }
+    }
+
+    report_outstanding().await;
+}
+
+/// Re-send final statuses that didn't reach the server
+pub async fn report_outstanding() {
+    if super::connectivity::is_offline() {
+        return;
+    }
+    let Ok(jobs) = job_store::get_outstanding().await else {
+        return;
+    };
+
+    for stored in jobs.iter().filter(|job| job.status.is_finished()) {
+        let result = update_job_status(&stored.job_id, stored.status, stored.result.as_ref(), stored.error.as_deref()).await;
+        match result {
+            Ok(()) => {
+                if let Err(e) = job_store::record_reported(&stored.job_id).await {
+                    log::warn!("Failed to mark job {} as reported: {}", stored.job_id, e);
+                }
+            }
+            // Still can't reach the server; the next round will try again
+            Err(e) => {
+                log::debug!("Job {} status still unreported: {}", stored.job_id, e);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct EchoHandler;
+
+    impl JobHandler for EchoHandler {
+        fn job_type(&self) -> &'static str {
+            "echo"
+        }
+
+        fn timeout(&self) -> Duration {
+            Duration::from_millis(50)
+        }
+
+        fn run<'a>(&'a self, job: &'a Job) -> JobFuture<'a> {
+            Box::pin(async move { Ok(job.payload.clone()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry_looks_up_handlers_by_type() {
+        let registry = JobRegistry::default().with(EchoHandler);
+        let handler = registry.get("echo").unwrap();
+        assert!(registry.get("screenshot").is_none());
+
+        let job = Job {
+            id: "j1".to_string(),
+            job_type: "echo".to_string(),
+            status: None,
+            payload: serde_json::json!({ "hello": "world" }),
+        };
+        assert_eq!(handler.run(&job).await.unwrap(), job.payload);
+        assert_eq!(handler.timeout(), Duration::from_millis(50));
+    }
+}
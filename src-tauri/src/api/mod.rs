@@ -1,6 +1,36 @@
 // API module - simplified for production testing
 
+pub mod auth;
 pub mod client;
+pub mod connectivity;
+pub mod error;
+pub mod job_handlers;
 pub mod job_polling;
+pub mod job_stream;
+pub mod jobs;
+pub mod protocol;
 pub mod uploads;
-pub mod reporting;
\ No newline at end of file
+pub mod reporting;
+#[cfg(test)]
+pub mod test_support;
+
+use std::sync::OnceLock;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+// Used to tell the UI about auth and connectivity changes
+static APP_HANDLE: OnceLock<AppHandle> = OnceLock::new();
+
+pub fn init(app_handle: AppHandle) {
+    let _ = APP_HANDLE.set(app_handle);
+}
+
+/// Broadcast a Tauri event to the UI (no-op before `init`, e.g. in tests)
+pub(crate) fn emit<S: Serialize + Clone>(event: &str, payload: S) {
+    if let Some(app_handle) = APP_HANDLE.get() {
+        if let Err(e) = app_handle.emit(event, payload) {
+            log::warn!("Failed to emit {}: {}", event, e);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,331 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Wire format of the TrackEx backend. Request bodies are built from these types and
+// responses are decoded into them, so field names live in exactly one place.
+// The backend speaks camelCase except for the ingest payloads, which are snake_case.
+
+/// Timestamp format used on the wire, e.g. `2024-01-01T12:00:00.000Z`
+pub fn wire_timestamp(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+// Auth and device registration
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmployeeLoginRequest {
+    pub email: String,
+    pub password: String,
+    pub device_name: String,
+    pub platform: String,
+    /// OS version
+    pub version: String,
+    pub app_version: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmployeeLoginResponse {
+    pub employee: Option<Employee>,
+    /// Present when this machine is already registered for the employee
+    pub device: Option<DeviceCredentials>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Employee {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeviceCredentials {
+    #[serde(alias = "deviceId")]
+    pub device_id: String,
+    #[serde(default, alias = "deviceToken")]
+    pub device_token: Option<String>,
+    /// The device already has a token that the server won't send again
+    #[serde(default, alias = "tokenExists")]
+    pub token_exists: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceRegistrationRequest {
+    pub employee_id: String,
+    pub device_name: String,
+    pub platform: String,
+    /// OS version
+    pub version: String,
+    pub app_version: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeviceRegistrationResponse {
+    pub device: DeviceCredentials,
+}
+
+/// Sent with the rejected token to ask for a replacement
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenRefreshRequest {
+    pub device_id: String,
+    pub app_version: String,
+}
+
+// Work sessions
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CurrentSessionResponse {
+    #[serde(default)]
+    pub is_active: bool,
+    pub session: Option<RemoteSession>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteSession {
+    pub clock_in: Option<String>,
+    pub current_app: Option<String>,
+}
+
+// Ingest: events
+
+/// One event as sent to `/api/ingest/events`
+#[derive(Debug, Clone, Serialize)]
+pub struct EventEnvelope {
+    /// Client-generated outbox ID, stable across retries
+    pub id: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    /// Same as `occurred_at`, for servers that predate it
+    pub timestamp: String,
+    pub occurred_at: String,
+    pub sent_at: String,
+    pub data: Value,
+    /// Which code path sent the event (debugging aid)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<&'static str>,
+}
+
+impl EventEnvelope {
+    pub fn new(id: &str, event_type: &str, data: Value, occurred_at: DateTime<Utc>) -> Self {
+        Self {
+            id: id.to_string(),
+            event_type: event_type.to_string(),
+            timestamp: wire_timestamp(occurred_at),
+            occurred_at: wire_timestamp(occurred_at),
+            sent_at: wire_timestamp(Utc::now()),
+            data,
+            from: None,
+        }
+    }
+
+    pub fn sent_from(mut self, from: &'static str) -> Self {
+        self.from = Some(from);
+        self
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct EventBatch {
+    pub events: Vec<EventEnvelope>,
+}
+
+impl EventBatch {
+    pub fn single(event: EventEnvelope) -> Self {
+        Self { events: vec![event] }
+    }
+}
+
+/// Body of a 2xx answer to an event upload. Older servers send no `results`,
+/// which acknowledges the whole batch.
+#[derive(Debug, Default, Deserialize)]
+pub struct EventBatchResponse {
+    #[serde(default)]
+    pub results: Option<Vec<EventResult>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EventResult {
+    pub id: String,
+    #[serde(default)]
+    pub status: String,
+    pub error: Option<String>,
+}
+
+// Ingest: heartbeats
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Heartbeat {
+    pub timestamp: String,
+    pub status: String,
+    pub idle_time_seconds: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_idle: Option<bool>,
+    #[serde(rename = "currentApp")]
+    pub current_app: Option<HeartbeatApp>,
+    pub session_start_time: String,
+    pub total_session_time_seconds: i64,
+    pub active_time_today_seconds: i64,
+    pub idle_time_today_seconds: i64,
+    pub is_paused: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeartbeatApp {
+    pub name: String,
+    pub app_id: String,
+    pub window_title: Option<String>,
+}
+
+/// A heartbeat as sent to `/api/ingest/heartbeat`. The body is the queued
+/// heartbeat payload, with the delivery fields added alongside.
+#[derive(Debug, Serialize)]
+pub struct HeartbeatEnvelope<'a> {
+    pub id: &'a str,
+    pub occurred_at: String,
+    pub sent_at: String,
+    #[serde(flatten)]
+    pub heartbeat: &'a Value,
+}
+
+// Jobs
+
+#[derive(Debug, Default, Deserialize)]
+pub struct JobsResponse {
+    #[serde(default)]
+    pub jobs: Vec<Job>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Job {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub job_type: String,
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub payload: Value,
+}
+
+impl Job {
+    pub fn is_pending(&self) -> bool {
+        self.status.as_deref() == Some("pending")
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobStatusUpdate<'a> {
+    pub job_id: &'a str,
+    pub status: &'a str,
+    pub result: Option<&'a Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'a str>,
+}
+
+/// `screenshot_taken` event data for an uploaded screenshot
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScreenshotTaken {
+    pub job_id: String,
+    pub storage_key: String,
+    pub image_url: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub bytes: Option<u64>,
+    pub format: Option<String>,
+    pub created_at: Option<String>,
+}
+
+impl ScreenshotTaken {
+    pub fn new(job_id: &str, upload: UploadResponse) -> Self {
+        Self {
+            job_id: job_id.to_string(),
+            storage_key: upload.public_id,
+            image_url: upload.secure_url,
+            width: upload.width,
+            height: upload.height,
+            bytes: upload.bytes,
+            format: upload.format,
+            created_at: upload.created_at,
+        }
+    }
+}
+
+// Uploads
+
+#[derive(Debug, Serialize)]
+pub struct UploadRequest<'a> {
+    /// Base64-encoded image
+    pub image: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadResponse {
+    pub public_id: String,
+    pub secure_url: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub bytes: Option<u64>,
+    pub format: Option<String>,
+    pub created_at: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_login_response_accepts_both_id_spellings() {
+        let snake: EmployeeLoginResponse = serde_json::from_value(json!({
+            "employee": {"id": "emp-1"},
+            "device": {"device_id": "dev-1", "device_token": "tok"}
+        })).unwrap();
+        let camel: EmployeeLoginResponse = serde_json::from_value(json!({
+            "employee": {"id": "emp-1"},
+            "device": {"deviceId": "dev-1", "tokenExists": true}
+        })).unwrap();
+
+        assert_eq!(snake.device.unwrap().device_token.as_deref(), Some("tok"));
+        let device = camel.device.unwrap();
+        assert_eq!(device.device_id, "dev-1");
+        assert!(device.token_exists);
+    }
+
+    #[test]
+    fn test_event_envelope_wire_names() {
+        let event = EventEnvelope::new("evt-1", "clock_in", json!({}), Utc::now()).sent_from("test");
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["type"], "clock_in");
+        assert_eq!(value["timestamp"], value["occurred_at"]);
+        assert_eq!(value["from"], "test");
+    }
+
+    #[test]
+    fn test_heartbeat_envelope_flattens_payload() {
+        let heartbeat = json!({"status": "active"});
+        let envelope = HeartbeatEnvelope {
+            id: "hb-1",
+            occurred_at: "a".to_string(),
+            sent_at: "b".to_string(),
+            heartbeat: &heartbeat,
+        };
+        let value = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(value, json!({"id": "hb-1", "occurred_at": "a", "sent_at": "b", "status": "active"}));
+    }
+
+    #[test]
+    fn test_jobs_without_status_are_not_pending() {
+        let response: JobsResponse = serde_json::from_value(json!({
+            "jobs": [{"id": "j1", "type": "screenshot", "status": "pending"}, {"id": "j2", "type": "screenshot"}],
+            "cursor": "c1"
+        })).unwrap();
+        let pending: Vec<_> = response.jobs.iter().filter(|j| j.is_pending()).map(|j| j.id.as_str()).collect();
+        assert_eq!(pending, vec!["j1"]);
+        assert_eq!(response.cursor.as_deref(), Some("c1"));
+    }
+}
@@ -0,0 +1,24 @@
+// Test helpers shared by the API and policy modules
+
+use crate::api::client::ApiClient;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Answer one connection per response, in order, and hand back the requests
+/// (lowercased, so header names can be matched directly)
+pub async fn mock_server(responses: Vec<String>) -> (ApiClient, tokio::task::JoinHandle<Vec<String>>) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = tokio::spawn(async move {
+        let mut requests = Vec::new();
+        for response in responses {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![0u8; 4096];
+            let n = socket.read(&mut request).await.unwrap();
+            requests.push(String::from_utf8_lossy(&request[..n]).to_lowercase());
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+        requests
+    });
+    (ApiClient::with_base_url(&format!("http://{}", addr)), server)
+}
@@ -4,6 +4,10 @@ use tokio::sync::Mutex;
 use serde::{Deserialize, Serialize};
 
 use crate::storage::{AppState, consent, app_usage};
+use crate::api::protocol::{
+    wire_timestamp, CurrentSessionResponse, DeviceRegistrationRequest, DeviceRegistrationResponse,
+    EmployeeLoginRequest, EmployeeLoginResponse, EventBatch, EventEnvelope, Heartbeat, HeartbeatApp,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoginRequest {
@@ -47,7 +51,7 @@ pub struct TrackingStatus {
 use crate::sampling::app_focus::AppInfo;
 
 // Helper functions for device registration
-fn get_platform_name() -> &'static str {
+pub(crate) fn get_platform_name() -> &'static str {
     match std::env::consts::OS {
         "windows" => "Windows",
         "macos" => "macOS", 
@@ -56,7 +60,7 @@ fn get_platform_name() -> &'static str {
     }
 }
 
-fn get_os_version() -> String {
+pub(crate) fn get_os_version() -> String {
     #[cfg(target_os = "windows")]
     {
         // Method 1: Try PowerShell to get accurate Windows version (most reliable)
@@ -244,7 +248,7 @@ fn get_os_version() -> String {
     }
 }
 
-fn get_device_name() -> String {
+pub(crate) fn get_device_name() -> String {
     #[cfg(target_os = "windows")]
     {
         // Try to get the computer name on Windows with consistent fallbacks
@@ -319,35 +323,85 @@ use crate::permissions::PermissionsStatus;
 
 #[tauri::command]
 pub async fn trigger_sync() -> Result<String, String> {
+    let report = crate::sampling::sync_engine::sync_now().await.map_err(|e| {
+        log::warn!("Manual sync failed: {}", e);
+        e.to_string()
+    })?;
     
-    // Try to sync pending heartbeats
-    let mut synced_heartbeats = 0;
-    if let Ok(heartbeats) = crate::storage::offline_queue::get_pending_heartbeats().await {
-        for heartbeat in heartbeats {
-            if let Ok(_) = crate::sampling::send_heartbeat_to_backend(&heartbeat.heartbeat_data).await {
-                if let Ok(_) = crate::storage::offline_queue::mark_heartbeat_processed(heartbeat.id).await {
-                    synced_heartbeats += 1;
-                }
-            }
-        }
-    }
-    
-    // Try to sync pending events
-    let mut synced_events = 0;
-    if let Ok(events) = crate::storage::offline_queue::get_pending_events().await {
-        for event in events {
-            if let Ok(_) = crate::sampling::send_event_to_backend(&event.event_type, &event.event_data).await {
-                if let Ok(_) = crate::storage::offline_queue::mark_event_processed(event.id).await {
-                    synced_events += 1;
-                }
-            }
-        }
-    }
-    
-    let message = format!("Sync completed: {} heartbeats, {} events synced", synced_heartbeats, synced_events);
+    let message = format!("Sync completed: {} heartbeats, {} events synced", report.heartbeats_sent, report.events_sent);
     Ok(message)
 }
 
+#[tauri::command]
+pub async fn get_dead_letters(limit: Option<usize>) -> Result<Vec<crate::storage::offline_queue::DeadLetter>, String> {
+    crate::storage::offline_queue::get_dead_letters(limit.unwrap_or(100))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn replay_dead_letters(ids: Option<Vec<i64>>) -> Result<usize, String> {
+    let replayed = crate::storage::offline_queue::replay_dead_letters(ids)
+        .await
+        .map_err(|e| e.to_string())?;
+    log::info!("Replayed {} dead-lettered items into the outbox", replayed);
+    Ok(replayed)
+}
+
+#[tauri::command]
+pub async fn get_queue_summary() -> Result<crate::storage::offline_queue::QueueSummary, String> {
+    crate::storage::offline_queue::get_queue_summary()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_queue_items(state: Option<String>, limit: Option<usize>) -> Result<Vec<crate::storage::offline_queue::QueueItem>, String> {
+    crate::storage::offline_queue::get_queue_items(state, limit.unwrap_or(100))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn retry_queue_items(event_ids: Vec<String>) -> Result<usize, String> {
+    let retried = crate::storage::offline_queue::retry_queue_items(event_ids)
+        .await
+        .map_err(|e| e.to_string())?;
+    log::info!("Rescheduled {} queued items for immediate delivery", retried);
+    crate::sampling::sync_engine::request_sync();
+    Ok(retried)
+}
+
+#[tauri::command]
+pub async fn discard_queue_items(event_ids: Vec<String>) -> Result<usize, String> {
+    let requested = event_ids.clone();
+    let discarded = crate::storage::offline_queue::discard_queue_items(event_ids)
+        .await
+        .map_err(|e| e.to_string())?;
+    log::warn!("Discarded {} queued items on user request", discarded);
+    crate::utils::logging::log_remote_non_blocking(
+        "queue_items_discarded",
+        "warn",
+        "Queued items discarded on user request",
+        Some(serde_json::json!({ "event_ids": requested, "discarded": discarded }))
+    ).await;
+    Ok(discarded)
+}
+
+/// Export queued items as pretty-printed JSON (all items when `event_ids` is omitted)
+#[tauri::command]
+pub async fn export_queue_items(event_ids: Option<Vec<String>>) -> Result<String, String> {
+    let items = crate::storage::offline_queue::export_queue_items(event_ids)
+        .await
+        .map_err(|e| e.to_string())?;
+    let export = serde_json::json!({
+        "exported_at": wire_timestamp(chrono::Utc::now()),
+        "app_version": env!("CARGO_PKG_VERSION"),
+        "items": items,
+    });
+    serde_json::to_string_pretty(&export).map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn login(
     request: LoginRequest,
@@ -365,25 +419,7 @@ pub async fn login(
         }))
     ).await;
     
-    // Create HTTP client with timeout
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(30))
-        .connect_timeout(std::time::Duration::from_secs(10))
-        .build()
-        .map_err(|e| {
-            let error_msg = format!("Failed to create HTTP client: {}", e);
-            // Spawn async logging task
-            let error_json = serde_json::json!({"error": e.to_string()});
-            tokio::spawn(async move {
-                crate::utils::logging::log_remote_non_blocking(
-                    "login_client_error",
-                    "error",
-                    "Failed to create HTTP client",
-                    Some(error_json)
-                ).await;
-            });
-            error_msg
-        })?;
+    let client = crate::api::client::ApiClient::with_base_url(&request.server_url);
     
     // Get device information for login
     let device_name = get_device_name();
@@ -391,17 +427,20 @@ pub async fn login(
     let os_version = get_os_version();
     
     // Prepare login request with device information
-    let login_url = format!("{}/api/auth/employee-login", request.server_url.trim_end_matches('/'));
-    let login_data = serde_json::json!({
-        "email": request.email,
-        "password": request.password,
-        "deviceName": device_name,
-        "platform": platform_name,
-        "version": os_version,
-        "appVersion": env!("CARGO_PKG_VERSION")
-    });
+    let login_data = EmployeeLoginRequest {
+        email: request.email.clone(),
+        password: request.password.clone(),
+        device_name: device_name.clone(),
+        platform: platform_name.to_string(),
+        version: os_version.clone(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+    };
 
     // Make login request
+    let login_request = client
+        .post("/api/auth/employee-login", &login_data)
+        .auth(crate::api::client::Auth::None);
+    let login_url = login_request.url().to_string();
     log::debug!("Sending login request to: {}", login_url);
     crate::utils::logging::log_remote_non_blocking(
         "login_request",
@@ -416,25 +455,24 @@ pub async fn login(
         }))
     ).await;
     
-    let response = client
-        .post(&login_url)
-        .header("Content-Type", "application/json")
-        .json(&login_data)
+    let response = login_request
         .send()
         .await
         .map_err(|e| {
-            let error_msg = if e.is_connect() {
+            let is_connect = e.is_connect();
+            let is_timeout = e.is_timeout();
+            let error_msg = if is_connect {
                 "Cannot connect to server. Please check your network connection and try again.".to_string()
-            } else if e.is_timeout() {
+            } else if is_timeout {
                 "Connection timeout. Please check your network connection and try again.".to_string()
             } else {
-                format!("Network error: {}", e)
+                e.to_string()
             };
             
             // Spawn async logging task
             let error_json = serde_json::json!({
-                "error": e.to_string(),
-                "error_type": if e.is_connect() { "connection" } else if e.is_timeout() { "timeout" } else { "other" }
+                "error": format!("{:#}", e),
+                "error_type": if is_connect { "connection" } else if is_timeout { "timeout" } else { "other" }
             });
             tokio::spawn(async move {
                 crate::utils::logging::log_remote_non_blocking(
@@ -459,7 +497,7 @@ pub async fn login(
             }))
         ).await;
         
-        let login_response: serde_json::Value = response
+        let login_response: EmployeeLoginResponse = response
             .json()
             .await
             .map_err(|e| {
@@ -477,20 +515,15 @@ pub async fn login(
                 error_msg
             })?;
 
-        if let Some(employee) = login_response.get("employee") {
-            let employee_id = employee.get("id")
-                .and_then(|v| v.as_str())
-                .ok_or("Missing employee ID")?;
+        if let Some(employee) = &login_response.employee {
+            let employee_id = employee.id.as_str();
 
             // Check if device credentials are already in the login response
-            let (device_id, device_token) = if let Some(device) = login_response.get("device") {
-                // Handle device data from API response
-                let device_id = device.get("device_id")
-                    .and_then(|v| v.as_str())
-                    .ok_or("Missing device_id in device object")?;
+            let (device_id, device_token) = if let Some(device) = &login_response.device {
+                let device_id = device.device_id.as_str();
                 
                 // Check if we have a token or need to handle existing token
-                if let Some(device_token) = device.get("device_token").and_then(|v| v.as_str()) {
+                if let Some(device_token) = device.device_token.as_deref() {
                     // New token provided
                     log::info!("Device registered with new token");
                     crate::utils::logging::log_remote_non_blocking(
@@ -503,7 +536,7 @@ pub async fn login(
                         }))
                     ).await;
                     (device_id.to_string(), device_token.to_string())
-                } else if device.get("token_exists").and_then(|v| v.as_bool()).unwrap_or(false) {
+                } else if device.token_exists {
                     // Token exists but not provided - need to fetch it separately
                     log::info!("Device exists but token not provided, need to fetch token");
                     return Err("Device token exists but not provided. Please contact support.".to_string());
@@ -525,15 +558,18 @@ pub async fn login(
                     }))
                 ).await;
                 
-                let device_data = serde_json::json!({
-                    "employeeId": employee_id,
-                    "deviceName": device_name,
-                    "platform": platform_name,
-                    "version": os_version,
-                    "appVersion": env!("CARGO_PKG_VERSION")
-                });
+                let device_data = DeviceRegistrationRequest {
+                    employee_id: employee_id.to_string(),
+                    device_name: device_name.clone(),
+                    platform: platform_name.to_string(),
+                    version: os_version.clone(),
+                    app_version: env!("CARGO_PKG_VERSION").to_string(),
+                };
 
-                let register_url = format!("{}/api/devices/employee-register", request.server_url.trim_end_matches('/'));
+                let register_request = client
+                    .post("/api/devices/employee-register", &device_data)
+                    .auth(crate::api::client::Auth::None);
+                let register_url = register_request.url().to_string();
                 log::debug!("Sending device registration to: {}", register_url);
                 crate::utils::logging::log_remote_non_blocking(
                     "device_registration_request",
@@ -548,10 +584,7 @@ pub async fn login(
                     }))
                 ).await;
                 
-                let device_response = client
-                    .post(&register_url)
-                    .header("Content-Type", "application/json")
-                    .json(&device_data)
+                let device_response = register_request
                     .send()
                     .await
                     .map_err(|e| {
@@ -580,7 +613,7 @@ pub async fn login(
                         }))
                     ).await;
                     
-                    let device_result: serde_json::Value = device_response
+                    let device_result: DeviceRegistrationResponse = device_response
                         .json()
                         .await
                         .map_err(|e| {
@@ -598,19 +631,10 @@ pub async fn login(
                             error_msg
                         })?;
 
-                    if let Some(device) = device_result.get("device") {
-                        let device_id = device.get("device_id")
-                            .and_then(|v| v.as_str())
-                            .ok_or("Missing device_id in registration response")?;
-
-                        let device_token = device.get("device_token")
-                            .and_then(|v| v.as_str())
-                            .ok_or("Missing device_token in registration response")?;
-                        
-                        (device_id.to_string(), device_token.to_string())
-                    } else {
-                        return Err("Invalid device registration response".to_string());
-                    }
+                    let device = device_result.device;
+                    let device_token = device.device_token
+                        .ok_or("Missing device_token in registration response")?;
+                    (device.device_id, device_token)
                 } else {
                     let error_text = device_response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
                     return Err(format!("Device registration failed: {}", error_text));
@@ -638,6 +662,9 @@ pub async fn login(
                         log::error!("Failed to sync device token to global state1: {}", e);
                     }
 
+                    // Fresh credentials: lift any pause left by a rejected token so queued data goes out
+                    crate::api::auth::clear();
+
                     // Start background services if a work session is already active
                     if crate::storage::work_session::is_session_active().await.unwrap_or(false) {
                         log::info!("Login successful - active work session detected, starting background services");
@@ -735,6 +762,8 @@ pub async fn logout(state: State<'_, Arc<Mutex<AppState>>>) -> Result<(), String
         state.is_paused = false;
     }
 
+    crate::api::auth::reset();
+
     // Stop all background services on logout
     log::info!("Logout: Stopping all background services");
     crate::sampling::stop_services().await;
@@ -747,6 +776,9 @@ pub async fn logout(state: State<'_, Arc<Mutex<AppState>>>) -> Result<(), String
     // Reset idle state to prevent stale idle events
     crate::sampling::reset_idle_state();
 
+    // The next login may belong to another organisation
+    crate::policy::sync::forget_policy().await;
+
     // Clear stored session data
     if let Err(e) = crate::storage::secure_store::delete_session_data().await {
         log::warn!("Failed to clear stored session data: {}", e);
@@ -890,19 +922,10 @@ pub async fn get_auth_status(
 
 // Helper function to validate token with server
 async fn validate_token_with_server(server_url: &str, token: &str) -> Result<bool, String> {
-    // Add timeout to prevent hanging
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(10))
-        .connect_timeout(std::time::Duration::from_secs(5))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-    
-    let url = format!("{}/api/auth/simple-session", server_url.trim_end_matches('/'));
-    
-    match client
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", token))
-        .header("Content-Type", "application/json")
+    match crate::api::client::ApiClient::with_base_url(server_url)
+        .get("/api/auth/simple-session")
+        .auth(crate::api::client::Auth::Bearer(token.to_string()))
+        .timeout(crate::api::client::Timeout::Interactive)
         .send()
         .await
     {
@@ -914,13 +937,7 @@ async fn validate_token_with_server(server_url: &str, token: &str) -> Result<boo
             Ok(is_valid)
         }
         Err(e) => {
-            if e.is_connect() {
-                log::warn!("Cannot connect to server for token validation: {}", e);
-            } else if e.is_timeout() {
-                log::warn!("Token validation timeout: {}", e);
-            } else {
-                log::warn!("Token validation error: {}", e);
-            }
+            log::warn!("Token validation error: {}", e);
             // Return true to allow offline operation - user can still use the app
             // The actual network operations will fail gracefully and queue data
             Ok(true)
@@ -958,28 +975,26 @@ pub async fn start_logging_sync_service() -> Result<(), String> {
 #[tauri::command]
 pub async fn clear_local_database() -> Result<(), String> {
     log::info!("Clearing local database...");
-    let conn = crate::storage::database::get_connection()
-        .map_err(|e| format!("Failed to get database connection: {}", e))?;
-    
-    // Clear all tables
-    conn.execute("DELETE FROM app_usage_sessions", [])
-        .map_err(|e| format!("Failed to clear app_usage_sessions: {}", e))?;
-    
-    conn.execute("DELETE FROM work_sessions", [])
-        .map_err(|e| format!("Failed to clear work_sessions: {}", e))?;
-    
-    conn.execute("DELETE FROM offline_queue", [])
-        .map_err(|e| format!("Failed to clear offline_queue: {}", e))?;
-
-    // Clear event and heartbeat queues to prevent residual sends
-    conn.execute("DELETE FROM event_queue", [])
-        .map_err(|e| format!("Failed to clear event_queue: {}", e))?;
-    conn.execute("DELETE FROM heartbeat_queue", [])
-        .map_err(|e| format!("Failed to clear heartbeat_queue: {}", e))?;
-    
-    // Reset auto-increment counters
-    conn.execute("DELETE FROM sqlite_sequence WHERE name IN ('app_usage_sessions', 'work_sessions', 'offline_queue', 'event_queue', 'heartbeat_queue')", [])
-        .map_err(|e| format!("Failed to reset auto-increment counters: {}", e))?;
+    crate::storage::database::with_connection(|conn| {
+        // Clear all tables (app_usage_segments go with their sessions)
+        conn.execute("DELETE FROM app_usage_sessions", [])
+            .map_err(|e| anyhow::anyhow!("Failed to clear app_usage_sessions: {}", e))?;
+
+        conn.execute("DELETE FROM work_sessions", [])
+            .map_err(|e| anyhow::anyhow!("Failed to clear work_sessions: {}", e))?;
+
+        // Clear the outbox to prevent residual sends
+        conn.execute("DELETE FROM outbox", [])
+            .map_err(|e| anyhow::anyhow!("Failed to clear outbox: {}", e))?;
+        conn.execute("DELETE FROM dead_letter", [])
+            .map_err(|e| anyhow::anyhow!("Failed to clear dead_letter: {}", e))?;
+
+        // Reset auto-increment counters
+        conn.execute("DELETE FROM sqlite_sequence WHERE name IN ('app_usage_sessions', 'app_usage_segments', 'work_sessions', 'outbox', 'dead_letter')", [])
+            .map_err(|e| anyhow::anyhow!("Failed to reset auto-increment counters: {}", e))?;
+
+        Ok(())
+    }).await.map_err(|e| e.to_string())?;
 
     log::info!("Local database cleared successfully - all tables and sequences reset");
     
@@ -987,38 +1002,17 @@ pub async fn clear_local_database() -> Result<(), String> {
 }
 
 #[tauri::command]
-pub async fn get_recent_sessions(state: State<'_, Arc<Mutex<AppState>>>) -> Result<serde_json::Value, String> {
-    let (server_url, device_token, device_id) = {
-        let app_state = state.lock().await;
-        (app_state.server_url.clone(), app_state.device_token.clone(), app_state.device_id.clone())
-    };
-
-    if let (Some(server_url), Some(device_token), Some(device_id)) = (server_url, device_token, device_id) {
-        let client = reqwest::Client::new();
-        
+pub async fn get_recent_sessions() -> Result<serde_json::Value, String> {
+    if crate::sampling::is_authenticated().await {
+        let client = crate::api::client::ApiClient::new().await.map_err(|e| e.to_string())?;
         // Call real API to get recent sessions
-        let url = format!("{}/api/employees/sessions/recent", server_url);
-        
         match client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", device_token))
-            .header("X-Device-ID", device_id)
-            .send()
+            .get("/api/employees/sessions/recent")
+            .send_json::<serde_json::Value>()
             .await
         {
-            Ok(response) => {
-                if response.status().is_success() {
-                    match response.json::<serde_json::Value>().await {
-                        Ok(sessions_data) => {
-                            return Ok(sessions_data);
-                        }
-                        Err(e) => {
-                            log::error!("Failed to parse sessions response: {}", e);
-                        }
-                    }
-                } else {
-                    log::error!("API request failed with status: {}", response.status());
-                }
+            Ok(sessions_data) => {
+                return Ok(sessions_data);
             }
             Err(e) => {
                 log::error!("Failed to fetch sessions from API: {}", e);
@@ -1099,9 +1093,11 @@ pub async fn get_consent_status() -> Result<ConsentStatus, String> {
 }
 
 #[tauri::command]
-pub async fn clock_in(state: State<'_, Arc<Mutex<AppState>>>, app_handle: tauri::AppHandle) -> Result<(), String> {
+pub async fn clock_in(app_handle: tauri::AppHandle) -> Result<(), String> {
     
     log::info!("Clock in: Starting clock in process");
+    // The moment the user clocked in, preserved even if the event is queued and sent hours later
+    let occurred_at = chrono::Utc::now();
     
     // ✅ 1. Save to LOCAL database first (this is the critical part)
     let session_id = crate::storage::work_session::start_session().await
@@ -1120,78 +1116,45 @@ pub async fn clock_in(state: State<'_, Arc<Mutex<AppState>>>, app_handle: tauri:
     });
     
     // ✅ 3. Handle backend communication asynchronously (don't block clock-in)
-    let (server_url, device_token, device_id) = {
-        let app_state = state.lock().await;
-        (app_state.server_url.clone(), app_state.device_token.clone(), app_state.device_id.clone())
-    };
-
-    if let (Some(server_url), Some(device_token), Some(device_id)) = (server_url, device_token, device_id) {
+    if crate::sampling::is_authenticated().await {
+        let client = crate::api::client::ApiClient::new().await.map_err(|e| e.to_string())?;
         // Spawn async task to handle backend communication
         tokio::spawn(async move {
             log::info!("Clock in: Sending clock_in event to backend (async)");
             
-            let client = reqwest::Client::new();
-            let events_url = format!("{}/api/ingest/events", server_url.trim_end_matches('/'));
-            
-            let event_data = serde_json::json!({
-                "events": [{
-                    "type": "clock_in",
-                    "timestamp": chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
-                    "data": {
-                        "session_id": session_id,
-                        "source": "desktop_agent"
-                    }
-                }]
+            let event_id = crate::storage::offline_queue::new_event_id();
+            let clock_in_data = serde_json::json!({
+                "session_id": session_id,
+                "source": "desktop_agent"
             });
+            let event_data = EventBatch::single(EventEnvelope::new(&event_id, "clock_in", clock_in_data.clone(), occurred_at));
 
             // Try to send to backend with timeout
-            match tokio::time::timeout(
-                std::time::Duration::from_secs(5),
-                client
-                    .post(&events_url)
-                    .header("Content-Type", "application/json")
-                    .header("Authorization", format!("Bearer {}", device_token))
-                    .header("X-Device-ID", device_id)
-                    .json(&event_data)
-                    .send()
-            ).await {
-                Ok(Ok(response)) => {
+            match client
+                .post("/api/ingest/events", &event_data)
+                .idempotency_key(&event_id)
+                .timeout(crate::api::client::Timeout::Quick)
+                .send()
+                .await
+            {
+                Ok(response) => {
                     if response.status().is_success() {
                         log::info!("Clock in: Backend event sent successfully");
                     } else {
                         log::warn!("Clock in: Backend returned error ({}), queuing event for later", response.status());
                         // Queue the clock_in event for later retry
-                        if let Err(queue_err) = crate::storage::offline_queue::queue_event("clock_in", &serde_json::json!({
-                            "session_id": session_id,
-                            "source": "desktop_agent"
-                        })).await {
+                        if let Err(queue_err) = crate::storage::offline_queue::queue_event_with_id(&event_id, "clock_in", &clock_in_data, occurred_at).await {
                             log::error!("Failed to queue clock_in event: {}", queue_err);
                         } else {
                             log::info!("Clock in: Event queued for later delivery");
                         }
                     }
                 }
-                Ok(Err(e)) => {
-                    // Network error, queue the event for later
-                    log::warn!("Clock in: Network error, queuing event for later: {}", e);
-                    
-                    if let Err(queue_err) = crate::storage::offline_queue::queue_event("clock_in", &serde_json::json!({
-                        "session_id": session_id,
-                        "source": "desktop_agent"
-                    })).await {
-                        log::error!("Failed to queue clock_in event: {}", queue_err);
-                    } else {
-                        log::info!("Clock in: Event queued for later delivery");
-                    }
-                }
-                Err(_) => {
-                    // Timeout occurred, queue the event for later
-                    log::warn!("Clock in: Backend request timeout, queuing event for later");
+                Err(e) => {
+                    // Network error or timeout, queue the event for later
+                    log::warn!("Clock in: {}, queuing event for later", e);
                     
-                    if let Err(queue_err) = crate::storage::offline_queue::queue_event("clock_in", &serde_json::json!({
-                        "session_id": session_id,
-                        "source": "desktop_agent"
-                    })).await {
+                    if let Err(queue_err) = crate::storage::offline_queue::queue_event_with_id(&event_id, "clock_in", &clock_in_data, occurred_at).await {
                         log::error!("Failed to queue clock_in event: {}", queue_err);
                     } else {
                         log::info!("Clock in: Event queued for later delivery");
@@ -1207,9 +1170,12 @@ pub async fn clock_in(state: State<'_, Arc<Mutex<AppState>>>, app_handle: tauri:
 }
 
 #[tauri::command]
-pub async fn clock_out(state: State<'_, Arc<Mutex<AppState>>>) -> Result<(), String> {
+pub async fn clock_out() -> Result<(), String> {
     
     log::info!("Clock out: Starting clock out process");
+    // Queued items can be flushed for up to 30s before the clock_out event goes out,
+    // so capture the real clock-out time up front
+    let occurred_at = chrono::Utc::now();
     crate::utils::logging::log_remote_non_blocking(
         "clock_out_start",
         "info",
@@ -1242,12 +1208,8 @@ pub async fn clock_out(state: State<'_, Arc<Mutex<AppState>>>) -> Result<(), Str
     }
 
     // ✅ 3. Move heavy processing to background (non-blocking)
-    let (server_url, device_token, device_id) = {
-        let app_state = state.lock().await;
-        (app_state.server_url.clone(), app_state.device_token.clone(), app_state.device_id.clone())
-    };
-
-    if let (Some(server_url), Some(device_token), Some(device_id)) = (server_url, device_token, device_id) {
+    if crate::sampling::is_authenticated().await {
+        let client = crate::api::client::ApiClient::new().await.map_err(|e| e.to_string())?;
         // Spawn background task for heavy processing
         tokio::spawn(async move {
             log::info!("Clock out: Starting background processing");
@@ -1263,10 +1225,11 @@ pub async fn clock_out(state: State<'_, Arc<Mutex<AppState>>>) -> Result<(), Str
                     "app_name": current_app.name,
                     "app_id": current_app.app_id,
                     "window_title": current_app.window_title,
-                    "timestamp": chrono::Utc::now().to_rfc3339()
+                    "timestamp": occurred_at.to_rfc3339()
                 });
 
-                if let Err(e) = crate::sampling::send_event_to_backend("app_focus", &event_data).await {
+                let event_id = crate::storage::offline_queue::new_event_id();
+                if let Err(e) = crate::sampling::send_event_to_backend(&event_id, "app_focus", &event_data, occurred_at).await {
                     log::warn!("Failed to send final app focus event: {}", e);
                 }
             }
@@ -1274,99 +1237,54 @@ pub async fn clock_out(state: State<'_, Arc<Mutex<AppState>>>) -> Result<(), Str
             // Process pending events and heartbeats in background
             log::info!("Clock out: Processing remaining queued events in background");
             
-            // Process pending events with timeout
-            if let Ok(events) = crate::storage::offline_queue::get_pending_events().await {
-                for event in events {
-                    let timeout_result = tokio::time::timeout(
-                        std::time::Duration::from_secs(10),
-                        crate::sampling::send_event_to_backend(&event.event_type, &event.event_data)
-                    ).await;
-                    
-                    match timeout_result {
-                        Ok(Ok(_)) => {
-                            let _ = crate::storage::offline_queue::mark_event_processed(event.id).await;
-                            log::info!("Clock out: Processed queued event {}", event.id);
-                        }
-                        Ok(Err(e)) => {
-                            log::warn!("Clock out: Failed to send queued event {}: {}", event.id, e);
-                            let _ = crate::storage::offline_queue::mark_event_failed(event.id).await;
-                        }
-                        Err(_) => {
-                            log::warn!("Clock out: Timeout sending queued event {}", event.id);
-                            let _ = crate::storage::offline_queue::mark_event_failed(event.id).await;
-                        }
-                    }
-                }
-            }
+            // Flush queued events and heartbeats with timeout
+            let timeout_result = tokio::time::timeout(
+                std::time::Duration::from_secs(30),
+                crate::sampling::sync_engine::sync_now()
+            ).await;
             
-            // Process pending heartbeats with timeout
-            if let Ok(heartbeats) = crate::storage::offline_queue::get_pending_heartbeats().await {
-                for heartbeat in heartbeats {
-                    let timeout_result = tokio::time::timeout(
-                        std::time::Duration::from_secs(10),
-                        crate::sampling::send_heartbeat_to_backend(&heartbeat.heartbeat_data)
-                    ).await;
-                    
-                    match timeout_result {
-                        Ok(Ok(_)) => {
-                            let _ = crate::storage::offline_queue::mark_heartbeat_processed(heartbeat.id).await;
-                            log::info!("Clock out: Processed queued heartbeat {}", heartbeat.id);
-                        }
-                        Ok(Err(e)) => {
-                            log::warn!("Clock out: Failed to send queued heartbeat {}: {}", heartbeat.id, e);
-                            let _ = crate::storage::offline_queue::mark_heartbeat_failed(heartbeat.id).await;
-                        }
-                        Err(_) => {
-                            log::warn!("Clock out: Timeout sending queued heartbeat {}", heartbeat.id);
-                            let _ = crate::storage::offline_queue::mark_heartbeat_failed(heartbeat.id).await;
-                        }
-                    }
+            match timeout_result {
+                Ok(Ok(report)) => {
+                    log::info!("Clock out: Processed {} queued events and {} heartbeats ({} not acknowledged)",
+                        report.events_sent, report.heartbeats_sent, report.failed);
+                }
+                Ok(Err(e)) => {
+                    log::warn!("Clock out: Failed to send queued items: {}", e);
+                }
+                Err(_) => {
+                    // Anything left stays leased briefly, then the sync engine picks it up
+                    log::warn!("Clock out: Timeout sending queued items");
                 }
             }
             
             // Send clock_out event to backend
-            let client = reqwest::Client::builder()
-                .timeout(std::time::Duration::from_secs(10))
-                .connect_timeout(std::time::Duration::from_secs(5))
-                .build();
-                
-            if let Ok(client) = client {
-                let events_url = format!("{}/api/ingest/events", server_url.trim_end_matches('/'));
-                
-                let event_data = serde_json::json!({
-                    "events": [{
-                        "type": "clock_out",
-                        "timestamp": chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
-                        "data": {
-                            "source": "desktop_agent"
-                        }
-                    }]
-                });
+            let event_id = crate::storage::offline_queue::new_event_id();
+            let clock_out_data = serde_json::json!({
+                "source": "desktop_agent"
+            });
+            let event_data = EventBatch::single(EventEnvelope::new(&event_id, "clock_out", clock_out_data.clone(), occurred_at));
 
-                match client
-                    .post(&events_url)
-                    .header("Content-Type", "application/json")
-                    .header("Authorization", format!("Bearer {}", device_token))
-                    .header("X-Device-ID", device_id)
-                    .json(&event_data)
-                    .send()
-                    .await
-                {
-                    Ok(response) => {
-                        if response.status().is_success() {
-                            log::info!("Clock out: Backend event sent successfully");
-                        } else {
-                            log::warn!("Clock out: Backend returned error ({}), queuing event for later", response.status());
-                            let _ = crate::storage::offline_queue::queue_event("clock_out", &serde_json::json!({
-                                "source": "desktop_agent"
-                            })).await;
+            match client
+                .post("/api/ingest/events", &event_data)
+                .idempotency_key(&event_id)
+                .timeout(crate::api::client::Timeout::Interactive)
+                .send()
+                .await
+            {
+                Ok(response) => {
+                    if response.status().is_success() {
+                        log::info!("Clock out: Backend event sent successfully");
+                    } else {
+                        log::warn!("Clock out: Backend returned error ({}), queuing event for later", response.status());
+                        if let Err(queue_err) = crate::storage::offline_queue::queue_event_with_id(&event_id, "clock_out", &clock_out_data, occurred_at).await {
+                            log::error!("Failed to queue clock_out event: {}", queue_err);
                         }
                     }
-                    Err(e) => {
-                        log::warn!("Clock out: Network error, queuing event for later: {}", e);
-                        let _ = crate::storage::offline_queue::queue_event("clock_out", &serde_json::json!({
-                            "source": "desktop_agent"
-                        })).await;
+                }
+                Err(e) => {
+                    log::warn!("Clock out: Network error, queuing event for later: {}", e);
+                    if let Err(queue_err) = crate::storage::offline_queue::queue_event_with_id(&event_id, "clock_out", &clock_out_data, occurred_at).await {
+                        log::error!("Failed to queue clock_out event: {}", queue_err);
                     }
                 }
             }
@@ -1396,88 +1314,70 @@ pub async fn get_work_session(state: State<'_, Arc<Mutex<AppState>>>) -> Result<
         }
     }
     
-    let (server_url, device_token, device_id, employee_id) = {
-        let app_state = state.lock().await;
-        (app_state.server_url.clone(), app_state.device_token.clone(), app_state.device_id.clone(), app_state.employee_id.clone())
-    };
+    let has_employee = state.lock().await.employee_id.is_some();
 
-    if let (Some(server_url), Some(_device_token), Some(device_id), Some(_employee_id)) = (server_url, device_token, device_id, employee_id) {
+    if has_employee && crate::sampling::is_authenticated().await {
+        let client = crate::api::client::ApiClient::new().await.map_err(|e| e.to_string())?;
         // Fetch current work session from backend
-        let client = reqwest::Client::new();
-        let current_session_url = format!("{}/api/devices/current-session", server_url.trim_end_matches('/'));
-       
         match client
-            .get(&current_session_url)
-            .header("Authorization", format!("Bearer {}", _device_token))
-            .header("X-Device-ID", device_id)
-            .send()
+            .get("/api/devices/current-session")
+            .timeout(crate::api::client::Timeout::Interactive)
+            .send_json::<CurrentSessionResponse>()
             .await {
-            Ok(response) if response.status().is_success() => {
-                if let Ok(session_data) = response.json::<serde_json::Value>().await {
-                    let is_active = session_data.get("is_active")
-                        .and_then(|v| v.as_bool())
-                        .unwrap_or(false);
+            Ok(session_data) => {
+                if session_data.is_active {
+                    // Active session found
+                    let session = session_data.session.unwrap_or_default();
+                    let started_at = session.clock_in;
+                    let current_app = session.current_app;
                     
-                    if is_active {
-                        // Active session found
-                        let started_at = session_data.get("session")
-                            .and_then(|s| s.get("clockIn"))
-                            .and_then(|v| v.as_str())
-                            .map(|s| s.to_string());
-                        
-                        let current_app = session_data.get("session")
-                            .and_then(|s| s.get("currentApp"))
-                            .and_then(|v| v.as_str())
-                            .map(|s| s.to_string());
-                        
-                        // If no current app from backend, get it locally
-                        let current_app = if current_app.is_some() {
-                            current_app
-                        } else {
-                            match get_current_app().await {
-                                Ok(Some(app)) => Some(app.name),
-                                _ => None
-                            }
-                        };
-                        
-                        let session_info = WorkSessionInfo {
-                            is_active: true,
-                            started_at,
-                            current_app,
-                            idle_time_seconds: 0,
-                            is_paused: false,
-                        };
-                        
-                        // Cache the result
-                        {
-                            let mut app_state = state.lock().await;
-                            app_state.work_session_cache.update(session_info.clone());
-                        }
-                        
-                        return Ok(session_info);
+                    // If no current app from backend, get it locally
+                    let current_app = if current_app.is_some() {
+                        current_app
                     } else {
-                        // No active session
-                        let session_info = WorkSessionInfo {
-                            is_active: false,
-                            started_at: None,
-                            current_app: Some("TrackEx Agent".to_string()),
-                            idle_time_seconds: 0,
-                            is_paused: false,
-                        };
-                        
-                        // Cache the result
-                        {
-                            let mut app_state = state.lock().await;
-                            app_state.work_session_cache.update(session_info.clone());
+                        match get_current_app().await {
+                            Ok(Some(app)) => Some(app.name),
+                            _ => None
                         }
-                        
-                        return Ok(session_info);
+                    };
+                    
+                    let session_info = WorkSessionInfo {
+                        is_active: true,
+                        started_at,
+                        current_app,
+                        idle_time_seconds: 0,
+                        is_paused: false,
+                    };
+                    
+                    // Cache the result
+                    {
+                        let mut app_state = state.lock().await;
+                        app_state.work_session_cache.update(session_info.clone());
+                    }
+                    
+                    return Ok(session_info);
+                } else {
+                    // No active session
+                    let session_info = WorkSessionInfo {
+                        is_active: false,
+                        started_at: None,
+                        current_app: Some("TrackEx Agent".to_string()),
+                        idle_time_seconds: 0,
+                        is_paused: false,
+                    };
+                    
+                    // Cache the result
+                    {
+                        let mut app_state = state.lock().await;
+                        app_state.work_session_cache.update(session_info.clone());
                     }
+                    
+                    return Ok(session_info);
                 }
             }
-            _ => {
+            Err(e) => {
                 // If we can't fetch from backend, fall back to local state
-                log::warn!("Failed to fetch work session from backend, using local state");
+                log::warn!("Failed to fetch work session from backend, using local state: {}", e);
                 
                 // Check local SQLite database for active session
                 if let Ok(Some(local_session)) = crate::storage::work_session::get_current_session().await {
@@ -1560,332 +1460,18 @@ pub async fn take_screenshot() -> Result<String, String> {
     }
 }
 
-// Helper function to check if an app is the TrackEx Agent itself
-fn is_trackex_agent(app_name: &str, app_id: &str, window_title: Option<&str>) -> bool {
-    let app_name_lower = app_name.to_lowercase();
-    let app_id_lower = app_id.to_lowercase();
-    
-    // IMPORTANT: Be very specific to avoid false positives
-    // (e.g., Cursor with "trackex-desktop-agent" folder open shouldn't match)
-    
-    // Check app name - must be specifically "TrackEx Agent" or similar (not just containing the words)
-    if app_name_lower == "trackex agent" 
-        || app_name_lower == "trackex-agent" 
-        || app_name_lower == "trackex_agent" {
-        return true;
-    }
-    
-    // Check app ID / bundle ID / executable name - must be the exact TrackEx executable
-    if app_id_lower == "trackex-agent.exe" 
-        || app_id_lower == "trackex_agent.exe"
-        || app_id_lower == "trackex-agent"
-        || app_id_lower == "trackex_agent"
-        || app_id_lower.starts_with("com.trackex.agent")
-        || app_id_lower.starts_with("com.nextup.trackex") {
-        return true;
-    }
-    
-    // Check window title ONLY if it's exactly "TrackEx Agent" or "TrackEx"
-    // Do NOT check if it contains these words (to avoid false positives from folder names)
-    if let Some(title) = window_title {
-        let title_lower = title.trim().to_lowercase();
-        if title_lower == "trackex agent" || title_lower == "trackex" {
-            return true;
-        }
-    }
-    
-    false
-}
-
 #[tauri::command]
 pub async fn get_current_app() -> Result<Option<AppInfo>, String> {
-    // Strategy: Return the focused app, but if TrackEx is focused, return the last non-TrackEx app.
-    // This ensures the UI always shows what the user is actually working on, even when viewing TrackEx.
-    
-    #[cfg(target_os = "macos")]
-    {
-        use std::process::Command;
-
-        // Primary: single AppleScript returning name and bundle id separated by ||
-        let script = r#"
-            tell application "System Events"
-                set p to first application process whose frontmost is true
-                set appName to name of p
-                try
-                    set bid to bundle identifier of p
-                on error
-                    set bid to ""
-                end try
-                return appName & "||" & bid
-            end tell
-        "#;
-        if let Ok(out) = Command::new("osascript").arg("-e").arg(script).output() {
-            let raw = String::from_utf8_lossy(&out.stdout).trim().to_string();
-            crate::utils::logging::log_remote_non_blocking(
-                "macos_app_detect_primary",
-                "debug",
-                "Primary AppleScript output",
-                Some(serde_json::json!({"raw": raw}))
-            ).await;
-            if !raw.is_empty() {
-                let parts: Vec<&str> = raw.split("||").collect();
-                let name = parts.get(0).unwrap_or(&"").trim();
-                let bundle_id = parts.get(1).unwrap_or(&"").trim();
-                if !name.is_empty() {
-                    let is_trackex = is_trackex_agent(name, bundle_id, None);
-                    crate::utils::logging::log_remote_non_blocking(
-                        "macos_app_detect_parsed",
-                        "debug",
-                        "Parsed macOS app",
-                        Some(serde_json::json!({"name": name, "bundle_id": bundle_id, "is_trackex": is_trackex}))
-                    ).await;
-                    if is_trackex {
-                        return Ok(crate::sampling::app_focus::get_last_non_trackex_app().await);
-                    }
-                    let app_info = AppInfo { name: name.to_string(), app_id: bundle_id.to_string(), window_title: Some("Active Window".to_string()) };
-                    crate::sampling::app_focus::set_last_non_trackex_app(app_info.clone()).await;
-                    return Ok(Some(app_info));
-                }
-            }
-        }
-
-        // Fallback: separate queries
-        let app_name_result = Command::new("osascript")
-            .arg("-e")
-            .arg("tell application \"System Events\" to get name of first application process whose frontmost is true")
-            .output();
-        let bundle_id_result = Command::new("osascript")
-            .arg("-e")
-            .arg("tell application \"System Events\" to get bundle identifier of first application process whose frontmost is true")
-            .output();
-        if let (Ok(name_output), Ok(bundle_output)) = (app_name_result, bundle_id_result) {
-            let name = String::from_utf8_lossy(&name_output.stdout).trim().to_string();
-            let bundle_id = String::from_utf8_lossy(&bundle_output.stdout).trim().to_string();
-            crate::utils::logging::log_remote_non_blocking(
-                "macos_app_detect_fallback",
-                "debug",
-                "Fallback AppleScript result",
-                Some(serde_json::json!({"name": name, "bundle_id": bundle_id}))
-            ).await;
-            if !name.is_empty() {
-                if is_trackex_agent(&name, &bundle_id, None) {
-                    return Ok(crate::sampling::app_focus::get_last_non_trackex_app().await);
-                }
-                let app_info = AppInfo { name: name.clone(), app_id: bundle_id.clone(), window_title: Some("Active Window".to_string()) };
-                crate::sampling::app_focus::set_last_non_trackex_app(app_info.clone()).await;
-                return Ok(Some(app_info));
-            }
-        }
-
-        // Final fallback
-        crate::utils::logging::log_remote_non_blocking(
-            "macos_app_detect_final_fallback",
-            "warn",
-            "Returning last non-TrackEx app (macOS)",
-            None
-        ).await;
-        return Ok(crate::sampling::app_focus::get_last_non_trackex_app().await);
-    }
-    
-    #[cfg(target_os = "windows")]
-    {
-        use crate::utils::windows_imports::*;
-        use windows::Win32::Foundation::HWND;
-
-        use sysinfo::{System};
-
-        use crate::sampling::app_focus::get_windows_process_name;
-        // Note: Shell API imports may not be available in this version
-        // We'll use a simpler approach without UWP app detection
-
-        unsafe {
-            // Get handle to the foreground window
-            let hwnd: HWND = GetForegroundWindow();
-            if hwnd.0 == std::ptr::null_mut() {
-                return Err("No active window".into());
-            }
-    
-            // Get window title
-            let mut title_buf = [0u16; 512];
-            let len = GetWindowTextW(hwnd, &mut title_buf);
-            let window_title = String::from_utf16_lossy(&title_buf[..len as usize]);
-            let window_title = trim_nulls(&window_title);
-    
-            // Get process ID for identification
-            let mut pid = 0u32;
-            GetWindowThreadProcessId(hwnd, Some(&mut pid));
-
-            if pid == 0 {
-                return Err("Failed to get process ID".into()); // Could not get process ID
-            }
-
-            // First, try to detect if this is a UWP app by checking the window
-            let mut app_name = None;
-            let mut app_id = None;
-
-            if let Some(uwp_package) = crate::sampling::app_focus::get_uwp_app_from_window(hwnd) {
-                app_id = Some(uwp_package.clone());
-                
-                // Map package family name to friendly name
-                app_name = match uwp_package.as_str() {
-                    "Microsoft.WindowsCalculator_8wekyb3d8bbwe" => Some("Calculator".to_string()),
-                    "Microsoft.XboxGamingOverlay_8wekyb3d8bbwe" => Some("Xbox Game Bar".to_string()),
-                    "Microsoft.XboxApp_8wekyb3d8bbwe" => Some("Xbox".to_string()),
-                    "Microsoft.WindowsStore_8wekyb3d8bbwe" => Some("Microsoft Store".to_string()),
-                    "Microsoft.Windows.Settings_8wekyb3d8bbwe" => Some("Settings".to_string()),
-                    "Microsoft.Windows.ShellExperienceHost_cw5n1h2txyewy" => Some("Start Menu".to_string()),
-                    _ => Some(uwp_package), // Use package name as fallback
-                };
-            }
-
-            // If not UWP, use classic Win32 detection
-            if app_name.is_none() {
-                let mut sys = System::new_all();
-                sys.refresh_all(); // Refresh process information
-
-                if let Some(process) = sys.process(sysinfo::Pid::from_u32(pid)) {
-                    let pid = process.pid().as_u32();
-                    
-                    // Try to get friendly name via Windows API first
-                    if let Some(name) = get_windows_process_name(pid) {
-                        app_name = Some(trim_nulls(&name));
-                        log::debug!("Got app name from get_windows_process_name: {}", name);
-                    } else {
-                        // Fallback: use sysinfo to get exe path and apply mapping
-                        log::debug!("get_windows_process_name returned None, using sysinfo fallback");
-                        
-                        // Try to get exe path from sysinfo
-                        if let Some(exe_path) = process.exe() {
-                            let exe_path_str = exe_path.to_string_lossy().to_string();
-                            log::debug!("Process exe path: {}", exe_path_str);
-                            
-                            // Apply the same mapping logic
-                            let exe_lower = exe_path_str.to_lowercase();
-                            
-                            // Check known app mappings (same as in app_focus.rs)
-                            if exe_lower.contains("cursor") {
-                                app_name = Some("Cursor".to_string());
-                            } else if exe_lower.contains("code.exe") || (exe_lower.contains("code") && exe_lower.contains("microsoft")) {
-                                app_name = Some("Visual Studio Code".to_string());
-                            } else if exe_lower.contains("chrome") && !exe_lower.contains("edge") {
-                                app_name = Some("Google Chrome".to_string());
-                            } else if exe_lower.contains("msedge") || (exe_lower.contains("edge") && !exe_lower.contains("edgeupdate")) {
-                                app_name = Some("Microsoft Edge".to_string());
-                            } else if exe_lower.contains("firefox") {
-                                app_name = Some("Mozilla Firefox".to_string());
-                            } else if exe_lower.contains("brave") {
-                                app_name = Some("Brave Browser".to_string());
-                            } else if exe_lower.contains("opera") {
-                                app_name = Some("Opera".to_string());
-                            } else if exe_lower.contains("explorer.exe") || exe_lower.ends_with("\\explorer.exe") {
-                                app_name = Some("File Explorer".to_string());
-                            } else if exe_lower.contains("notepad++") {
-                                app_name = Some("Notepad++".to_string());
-                            } else if exe_lower.contains("notepad.exe") && !exe_lower.contains("++") {
-                                app_name = Some("Notepad".to_string());
-                            } else if exe_lower.contains("devenv") {
-                                app_name = Some("Visual Studio".to_string());
-                            } else if exe_lower.contains("teams") {
-                                app_name = Some("Microsoft Teams".to_string());
-                            } else if exe_lower.contains("slack") {
-                                app_name = Some("Slack".to_string());
-                            } else if exe_lower.contains("discord") {
-                                app_name = Some("Discord".to_string());
-                            } else if exe_lower.contains("zoom") {
-                                app_name = Some("Zoom".to_string());
-                            } else if exe_lower.contains("spotify") {
-                                app_name = Some("Spotify".to_string());
-                            } else if exe_lower.contains("winword") {
-                                app_name = Some("Microsoft Word".to_string());
-                            } else if exe_lower.contains("excel") {
-                                app_name = Some("Microsoft Excel".to_string());
-                            } else if exe_lower.contains("powerpnt") {
-                                app_name = Some("Microsoft PowerPoint".to_string());
-                            } else if exe_lower.contains("outlook") {
-                                app_name = Some("Microsoft Outlook".to_string());
-                            } else {
-                                // Final fallback: clean filename
-                                if let Some(file_name) = exe_path.file_name() {
-                                    let name = file_name.to_string_lossy().to_string();
-                                    // Remove .exe extension
-                                    app_name = Some(if name.to_lowercase().ends_with(".exe") {
-                                        name[..name.len() - 4].to_string()
-                                    } else {
-                                        name
-                                    });
-                                }
-                            }
-                        }
-                        
-                        if app_name.is_none() {
-                            let proc_name = trim_nulls(process.name());
-                            log::debug!("Final fallback to process.name(): {}", proc_name);
-                            // Remove .exe extension if present
-                            app_name = Some(if proc_name.to_lowercase().ends_with(".exe") {
-                                proc_name[..proc_name.len() - 4].to_string()
-                            } else {
-                                proc_name
-                            });
-                        }
-                    }
-                } else {
-                    log::warn!("Could not find process with PID: {}", pid);
-                }
-                
-                // Get app ID using Windows-specific logic
-                app_id = crate::sampling::app_focus::get_windows_app_id(pid);
-            }
-            
-            let final_app_name = app_name.unwrap_or_else(|| {
-                log::warn!("No app name found, using Unknown");
-                "Unknown".to_string()
-            });
-            let final_app_id = app_id.unwrap_or_else(|| format!("pid_{}", pid));
-            
-            let app_info = AppInfo {
-                name: final_app_name.clone(),
-                app_id: final_app_id.clone(),
-                window_title: Some(window_title.clone()),
-            };
-            
-            // Check if this is the TrackEx Agent itself
-            let is_trackex = is_trackex_agent(&final_app_name, &final_app_id, Some(&window_title));
-            
-            log::debug!("App detection: name='{}', id='{}', title='{}', is_trackex={}", 
-                final_app_name, final_app_id, window_title, is_trackex);
-            
-            if is_trackex {
-                // Return the last non-TrackEx app instead
-                log::debug!("TrackEx detected as foreground, returning last non-TrackEx app");
-                return Ok(crate::sampling::app_focus::get_last_non_trackex_app().await);
-            }
-            
-            // Save this as the last non-TrackEx app
-            crate::sampling::app_focus::set_last_non_trackex_app(app_info.clone()).await;
-            Ok(Some(app_info))
-        }
-    }
-    
-    
-    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
-    {
-        // Fallback for other systems
-        return Ok(Some(AppInfo {
-            app_name: "Unknown Application".to_string(),
-            app_id: "unknown".to_string(),
-            window_title: Some("Unknown Window".to_string()),
-        }));
-    }
-}
-
-fn trim_nulls(s: &str) -> String {
-    s.trim_end_matches('\u{0}').to_string()
+    crate::sampling::app_focus::current_app().await.map_err(|e| e.to_string())
 }
 
-
+/// Save a diagnostics bundle locally and return the path of the zip
 #[tauri::command]
-pub async fn send_diagnostics() -> Result<(), String> {
-    Ok(())
+pub async fn send_diagnostics() -> Result<String, String> {
+    crate::diagnostics::save_bundle()
+        .await
+        .map(|path| path.display().to_string())
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -1911,40 +1497,24 @@ pub async fn get_app_info() -> Result<serde_json::Value, String> {
 }
 
 #[tauri::command]
-pub async fn send_app_focus_event(
-    state: State<'_, Arc<Mutex<AppState>>>,
-) -> Result<String, String> {
-    let (server_url, device_token, device_id) = {
-        let app_state = state.lock().await;
-        (app_state.server_url.clone(), app_state.device_token.clone(), app_state.device_id.clone())
-    };
-
-    if let (Some(server_url), Some(device_token), Some(device_id)) = (server_url, device_token, device_id) {
+pub async fn send_app_focus_event() -> Result<String, String> {
+    if crate::sampling::is_authenticated().await {
+        let client = crate::api::client::ApiClient::new().await.map_err(|e| e.to_string())?;
         // Get current app
         if let Ok(Some(app_info)) = get_current_app().await {
             // Send app_focus event to backend
-            let client = reqwest::Client::new();
-            let events_url = format!("{}/api/ingest/events", server_url.trim_end_matches('/'));
-            
-            let event_data = serde_json::json!({
-                "events": [{
-                    "type": "app_focus",
-                    "timestamp": chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
-                    "data": {
-                        "app_name": app_info.name,
-                        "app_id": app_info.app_id,
-                        "window_title": app_info.window_title.unwrap_or_default()
-                    },
-                    "from": "send_app_focus_event"
-                }]
+            let event_id = crate::storage::offline_queue::new_event_id();
+            let focus_data = serde_json::json!({
+                "app_name": app_info.name,
+                "app_id": app_info.app_id,
+                "window_title": app_info.window_title.unwrap_or_default()
             });
+            let event_data = EventBatch::single(
+                EventEnvelope::new(&event_id, "app_focus", focus_data, chrono::Utc::now()).sent_from("send_app_focus_event"),
+            );
 
             let response = client
-                .post(&events_url)
-                .header("Content-Type", "application/json")
-                .header("Authorization", format!("Bearer {}", device_token))
-                .header("X-Device-ID", device_id)
-                .json(&event_data)
+                .post("/api/ingest/events", &event_data)
                 .send()
                 .await;
 
@@ -1970,29 +1540,19 @@ pub async fn send_app_focus_event(
 }
 
 #[tauri::command]
-pub async fn send_heartbeat(
-    state: State<'_, Arc<Mutex<AppState>>>,
-) -> Result<String, String> {
-    let (server_url, device_token, device_id) = {
-        let app_state = state.lock().await;
-        (app_state.server_url.clone(), app_state.device_token.clone(), app_state.device_id.clone())
-    };
-
-    if let (Some(server_url), Some(device_token), Some(device_id)) = (server_url, device_token, device_id) {
+pub async fn send_heartbeat() -> Result<String, String> {
+    if crate::sampling::is_authenticated().await {
         // Get current app for heartbeat
         let current_app = match get_current_app().await {
-            Ok(Some(app)) => Some(serde_json::json!({
-                "name": app.name,
-                "app_id": app.app_id,
-                "window_title": app.window_title.unwrap_or_default()
-            })),
+            Ok(Some(app)) => Some(HeartbeatApp {
+                name: app.name,
+                app_id: app.app_id,
+                window_title: Some(app.window_title.unwrap_or_default()),
+            }),
             _ => None
         };
 
         // Send heartbeat to backend
-        let client = reqwest::Client::new();
-        let heartbeat_url = format!("{}/api/ingest/heartbeat", server_url.trim_end_matches('/'));
-        
         // Get idle time and work session data for time calculations
         let idle_time = crate::sampling::idle_detector::get_idle_time().await.unwrap_or(0);
         let idle_threshold = crate::sampling::idle_detector::get_idle_threshold();
@@ -2025,38 +1585,27 @@ pub async fn send_heartbeat(
             (now, 0, 0, 0)
         };
 
-        let heartbeat_data = serde_json::json!({
-            "timestamp": now.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
-            "status": if is_idle { "idle" } else { "active" },
-            "currentApp": current_app,
-            "idle_time_seconds": idle_time,
-            "session_start_time": session_start.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
-            "total_session_time_seconds": total_session_time,
-            "active_time_today_seconds": total_active_today,
-            "idle_time_today_seconds": total_idle_today,
-            "is_paused": crate::sampling::is_services_paused().await
-        });
+        let heartbeat_data = Heartbeat {
+            timestamp: wire_timestamp(now),
+            status: if is_idle { "idle" } else { "active" }.to_string(),
+            idle_time_seconds: idle_time,
+            is_idle: None,
+            current_app,
+            session_start_time: wire_timestamp(session_start),
+            total_session_time_seconds: total_session_time,
+            active_time_today_seconds: total_active_today,
+            idle_time_today_seconds: total_idle_today,
+            is_paused: crate::sampling::is_services_paused().await,
+        };
 
-        let response = client
-            .post(&heartbeat_url)
-            .header("Content-Type", "application/json")
-            .header("Authorization", format!("Bearer {}", device_token))
-            .header("X-Device-ID", device_id)
-            .json(&heartbeat_data)
-            .send()
-            .await;
-
-        match response {
-            Ok(resp) if resp.status().is_success() => {
-                Ok("Heartbeat sent".to_string())
-            }
-            Ok(resp) => {
-                log::error!("Failed to send heartbeat2: {}", resp.status());
-                Err("Failed to send heartbeat".to_string())
-            }
+        let heartbeat_data = serde_json::to_value(&heartbeat_data).map_err(|e| e.to_string())?;
+        let event_id = crate::storage::offline_queue::new_event_id();
+
+        match crate::sampling::send_heartbeat_to_backend(&event_id, &heartbeat_data, now).await {
+            Ok(()) => Ok("Heartbeat sent".to_string()),
             Err(e) => {
                 log::error!("Error sending heartbeat: {}", e);
-                Err("Network error sending heartbeat".to_string())
+                Err("Failed to send heartbeat".to_string())
             }
         }
     } else {
@@ -2065,81 +1614,14 @@ pub async fn send_heartbeat(
 }
 
 #[tauri::command]
-pub async fn check_pending_jobs(
-    state: State<'_, Arc<Mutex<AppState>>>,
-) -> Result<String, String> {
-    let (server_url, device_token, device_id) = {
-        let app_state = state.lock().await;
-        (app_state.server_url.clone(), app_state.device_token.clone(), app_state.device_id.clone())
-    };
-
-    if let (Some(server_url), Some(device_token), Some(device_id)) = (server_url, device_token, device_id) {
-        let client = reqwest::Client::new();
-        let jobs_url = format!("{}/api/ingest/jobs", server_url.trim_end_matches('/'));
-        
-        match client
-            .get(&jobs_url)
-            .header("Authorization", format!("Bearer {}", device_token))
-            .header("X-Device-ID", device_id)
-            .send()
-            .await
-        {
-            Ok(response) if response.status().is_success() => {
-                if let Ok(jobs_data) = response.json::<serde_json::Value>().await {
-                    if let Some(jobs) = jobs_data.get("jobs").and_then(|j| j.as_array()) {
-                        for job in jobs {
-                            if let Some(job_type) = job.get("type").and_then(|t| t.as_str()) {
-                                if job_type == "screenshot" {
-                                    if let Some(job_id) = job.get("id").and_then(|id| id.as_str()) {
-                                        // Take screenshot
-                                        match take_screenshot().await {
-                                            Ok(screenshot_data) => {
-                                                // Send screenshot completion event
-                                                let event_data = serde_json::json!({
-                                                    "events": [{
-                                                        "type": "screenshot_taken",
-                                                        "timestamp": chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
-                                                        "data": {
-                                                            "jobId": job_id,
-                                                            "job_id": job_id,
-                                                            "screenshot_data": screenshot_data,
-                                                            "screenshot": screenshot_data,
-                                                            "auto": false
-                                                        }
-                                                    }]
-                                                });
-
-                                                let events_url = format!("{}/api/ingest/events", server_url.trim_end_matches('/'));
-                                                let device_id = crate::storage::get_device_id().await.map_err(|_| anyhow::anyhow!("No device ID available"));
-                                                let _ = client
-                                                    .post(&events_url)
-                                                    .header("Content-Type", "application/json")
-                                                    .header("Authorization", format!("Bearer {}", device_token))
-                                                    .header("X-Device-ID", device_id.expect("REASON").clone())
-                                                    .json(&event_data)
-                                                    .send()
-                                                    .await;
-
-                                            }
-                                            Err(e) => {
-                                                log::error!("Failed to take screenshot for job {}: {}", job_id, e);
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-                Ok("Jobs checked".to_string())
-            }
-            Ok(response) => {
-                log::error!("Failed to check jobs: {}", response.status());
-                Err("Failed to check jobs".to_string())
-            }
+pub async fn check_pending_jobs() -> Result<String, String> {
+    if crate::sampling::is_authenticated().await {
+        // Same poller (and cursor) as the background fallback, so jobs aren't run twice
+        match crate::api::job_polling::poll_now().await {
+            Ok(handled) => Ok(format!("Jobs checked ({} run)", handled)),
             Err(e) => {
                 log::error!("Error checking jobs: {}", e);
-                Err("Network error checking jobs".to_string())
+                Err("Failed to check jobs".to_string())
             }
         }
     } else {
@@ -2149,73 +1631,7 @@ pub async fn check_pending_jobs(
 
 #[tauri::command]
 pub async fn get_idle_time() -> Result<u64, String> {
-    #[cfg(target_os = "macos")]
-    {
-        use std::process::Command;
-        
-        // Use ioreg to get idle time on macOS
-        let output = Command::new("ioreg")
-            .arg("-c")
-            .arg("IOHIDSystem")
-            .output();
-            
-        match output {
-            Ok(result) => {
-                if result.status.success() {
-                    let output_str = String::from_utf8_lossy(&result.stdout);
-                    
-                    // Parse the idle time from ioreg output
-                    for line in output_str.lines() {
-                        if line.contains("HIDIdleTime") {
-                            if let Some(start) = line.find('=') {
-                                if let Some(end) = line[start..].find(' ') {
-                                    let idle_str = &line[start+1..start+end].trim();
-                                    if let Ok(idle_ns) = idle_str.parse::<u64>() {
-                                        // Convert nanoseconds to seconds
-                                        return Ok(idle_ns / 1_000_000_000);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-                Ok(0)
-            }
-            Err(e) => {
-                log::error!("Failed to get idle time: {}", e);
-                Ok(0)
-            }
-        }
-    }
-
-    #[cfg(target_os = "windows")]
-    {
-        use std::mem;
-        use winapi::um::winuser::GetLastInputInfo;
-        use winapi::um::winuser::LASTINPUTINFO;
-        use winapi::um::sysinfoapi::GetTickCount;
-    
-        unsafe {
-            let mut last_input_info = LASTINPUTINFO {
-                cbSize: mem::size_of::<LASTINPUTINFO>() as u32,
-                dwTime: 0,
-            };
-            
-            if GetLastInputInfo(&mut last_input_info) != 0 {
-                let current_time = GetTickCount();
-                let idle_time_ms = current_time - last_input_info.dwTime;
-                Ok(idle_time_ms as u64 / 1000) // Convert to seconds
-            } else {
-                Ok(0)
-            }
-        }
-    }
-    
-    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
-    {
-        // For other platforms, return 0 for now
-        Ok(0)
-    }
+    crate::sampling::idle_detector::get_idle_time().await.map_err(|e| e.to_string())
 }
 
 // Removed send_idle_event command - idle detection is now handled solely by backend services
@@ -2281,15 +1697,13 @@ pub async fn test_server_connection() -> Result<String, String> {
             }
             
             // Test basic connectivity
-            let client = reqwest::Client::builder()
-                .timeout(std::time::Duration::from_secs(5))
-                .connect_timeout(std::time::Duration::from_secs(3))
-                .build()
-                .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-            
-            let test_url = format!("{}/api/health", server_url.trim_end_matches('/'));
-            
-            match client.get(&test_url).send().await {
+            match crate::api::client::ApiClient::with_base_url(&server_url)
+                .get("/api/health")
+                .auth(crate::api::client::Auth::None)
+                .timeout(crate::api::client::Timeout::Quick)
+                .send()
+                .await
+            {
                 Ok(response) => {
                     if response.status().is_success() {
                         Ok(format!("✅ Server is reachable at {}", server_url))
@@ -2297,21 +1711,31 @@ pub async fn test_server_connection() -> Result<String, String> {
                         Err(format!("❌ Server responded with status: {}", response.status()))
                     }
                 },
-                Err(e) => {
-                    if e.is_connect() {
-                        Err(format!("❌ Cannot connect to server at {}. Please ensure the backend is running on the correct port.", server_url))
-                    } else if e.is_timeout() {
-                        Err(format!("❌ Connection timeout to {}. Server may be slow or unresponsive.", server_url))
-                    } else {
-                        Err(format!("❌ Network error: {}", e))
-                    }
-                }
+                Err(e) => Err(format!("❌ {}", e)),
             }
         },
         Err(e) => Err(format!("Failed to get server URL: {}", e))
     }
 }
 
+/// Connectivity as learned from recent requests (see `api::connectivity`)
+#[tauri::command]
+pub async fn get_connectivity_status() -> Result<crate::api::connectivity::ConnectivityStatus, String> {
+    Ok(crate::api::connectivity::status())
+}
+
+/// The collection policy currently in effect (see `policy::sync`)
+#[tauri::command]
+pub async fn get_policy() -> Result<crate::policy::toggles::PolicyConfig, String> {
+    Ok(crate::policy::toggles::get_current_policy())
+}
+
+/// Fetch the organisation policy now; returns whether it changed
+#[tauri::command]
+pub async fn sync_policy() -> Result<bool, String> {
+    crate::policy::sync::sync_policy_now().await.map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn get_current_app_session() -> Result<Option<app_usage::AppUsageSession>, String> {
     Ok(app_usage::get_current_session().await)
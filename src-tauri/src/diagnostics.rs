@@ -0,0 +1,280 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use anyhow::Result;
+use chrono::Utc;
+use regex::Regex;
+use serde_json::{json, Value};
+
+// Diagnostics bundle: a snapshot of the agent's state for support. It goes to
+// the server as the result of a `diagnostics` job, or into a local zip when the
+// user picks "Send Diagnostics". Secrets are redacted before anything leaves
+// this module.
+
+// Log lines included in a bundle
+const LOG_TAIL_LINES: usize = 200;
+// Local bundles kept before the oldest are deleted
+const MAX_SAVED_BUNDLES: usize = 5;
+// Problems reported by the integrity check before SQLite stops looking
+const INTEGRITY_CHECK_LIMIT: u32 = 20;
+
+const REDACTED: &str = "[redacted]";
+
+static SECRET_PATTERNS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    vec![
+        // Authorization: Bearer <token>
+        Regex::new(r"(?i)(bearer\s+)[A-Za-z0-9._~+/=-]+").unwrap(),
+        // token=..., "deviceToken": "...", password: ...
+        Regex::new(r#"(?i)((?:[a-z_]*token|password|secret|api_?key)"?\s*[:=]\s*"?)[^"\s,}&]+"#).unwrap(),
+    ]
+});
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+    ["token", "password", "secret", "api_key", "apikey"].iter().any(|s| key.contains(s))
+}
+
+/// Mask credentials appearing in free text such as log lines
+pub fn redact_text(text: &str) -> String {
+    SECRET_PATTERNS
+        .iter()
+        .fold(text.to_string(), |text, pattern| pattern.replace_all(&text, format!("${{1}}{}", REDACTED)).into_owned())
+}
+
+/// Mask secret fields and credentials inside string values, recursively
+pub fn redact_value(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        Value::Null => Value::Null,
+                        _ if is_secret_key(&key) => Value::String(REDACTED.to_string()),
+                        value => redact_value(value),
+                    };
+                    (key, value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(redact_value).collect()),
+        Value::String(text) => Value::String(redact_text(&text)),
+        other => other,
+    }
+}
+
+fn section<T: serde::Serialize>(result: Result<T>) -> Value {
+    match result.and_then(|value| Ok(serde_json::to_value(value)?)) {
+        Ok(value) => value,
+        Err(e) => json!({ "error": e.to_string() }),
+    }
+}
+
+async fn database_report() -> Value {
+    let path = crate::storage::database::database_path().ok().flatten();
+    let checks = crate::storage::database::with_connection(|conn| {
+        let schema_version = crate::storage::migrations::current_version(conn)?;
+        let integrity: Vec<String> = conn
+            .prepare(&format!("PRAGMA integrity_check({})", INTEGRITY_CHECK_LIMIT))?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        let size_bytes: i64 = conn.query_row(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(json!({
+            "schemaVersion": schema_version,
+            "integrity": integrity,
+            "sizeBytes": size_bytes,
+        }))
+    })
+    .await;
+
+    let mut report = section(checks);
+    if let Some(map) = report.as_object_mut() {
+        map.insert("path".to_string(), json!(path));
+    }
+    report
+}
+
+async fn config_report() -> Value {
+    let session = match crate::storage::get_global_app_state() {
+        Ok(state) => {
+            let state = state.lock().await;
+            json!({
+                "serverUrl": state.server_url,
+                "deviceId": state.device_id,
+                "employeeId": state.employee_id,
+                "deviceToken": state.device_token,
+                "isPaused": state.is_paused,
+            })
+        }
+        Err(e) => json!({ "error": e.to_string() }),
+    };
+
+    let retention = crate::storage::retention::RetentionPolicy::from_env();
+    let env: serde_json::Map<String, Value> = std::env::vars()
+        .filter(|(key, _)| key.starts_with("TRACKEX_"))
+        .map(|(key, value)| (key, Value::String(value)))
+        .collect();
+
+    json!({
+        "session": session,
+        "policy": crate::policy::toggles::get_current_policy(),
+        "intervals": {
+            "appFocusSecs": crate::sampling::get_app_focus_interval(),
+            "heartbeatSecs": crate::sampling::get_heartbeat_interval(),
+            "jobPollingSecs": crate::sampling::get_job_polling_interval(),
+        },
+        "retention": {
+            "deliveredRetentionDays": retention.delivered_retention_days,
+            "maxQueueBytes": retention.max_queue_bytes,
+        },
+        "env": env,
+    })
+}
+
+/// Gather the full bundle, already redacted
+pub async fn collect() -> Value {
+    let bundle = json!({
+        "generatedAt": Utc::now().to_rfc3339(),
+        "agent": {
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "os": {
+            "platform": crate::commands::get_platform_name(),
+            "version": crate::commands::get_os_version(),
+            "arch": std::env::consts::ARCH,
+        },
+        "permissions": crate::permissions::get_permissions_status().await,
+        "services": crate::sampling::get_service_state().await,
+        "connectivity": crate::api::connectivity::status(),
+        "authSuspended": crate::api::auth::is_suspended(),
+        "jobStreamConnected": crate::api::job_stream::is_connected(),
+        "queue": section(crate::storage::offline_queue::get_queue_summary().await),
+        "database": database_report().await,
+        "config": config_report().await,
+        "logs": crate::utils::logging::recent_logs(LOG_TAIL_LINES),
+    });
+
+    redact_value(bundle)
+}
+
+fn diagnostics_dir() -> Result<PathBuf> {
+    let base = match crate::storage::database::database_path()? {
+        Some(path) => path.parent().map(Path::to_path_buf),
+        None => dirs::data_dir().map(|dir| dir.join("TrackEx")),
+    };
+    let dir = base.ok_or_else(|| anyhow::anyhow!("Failed to get data directory"))?.join("diagnostics");
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Write `bundle` as a zip: the report as JSON, the log tail as plain text
+pub fn write_zip(bundle: &Value, path: &Path) -> Result<()> {
+    let mut report = bundle.clone();
+    let logs = report
+        .as_object_mut()
+        .and_then(|map| map.remove("logs"))
+        .and_then(|logs| serde_json::from_value::<Vec<String>>(logs).ok())
+        .unwrap_or_default();
+
+    let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(path)?);
+
+    zip.start_file("diagnostics.json", options)?;
+    zip.write_all(serde_json::to_string_pretty(&report)?.as_bytes())?;
+
+    zip.start_file("logs.txt", options)?;
+    for line in logs {
+        writeln!(zip, "{}", line)?;
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+fn prune_saved_bundles(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut bundles: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "zip"))
+        .collect();
+    // Timestamped names sort oldest first
+    bundles.sort();
+
+    let excess = bundles.len().saturating_sub(MAX_SAVED_BUNDLES);
+    for path in bundles.into_iter().take(excess) {
+        if let Err(e) = std::fs::remove_file(&path) {
+            log::warn!("Failed to remove old diagnostics bundle {:?}: {}", path, e);
+        }
+    }
+}
+
+/// Collect a bundle and save it as a zip in the data directory. Returns its path.
+pub async fn save_bundle() -> Result<PathBuf> {
+    let bundle = collect().await;
+    let dir = diagnostics_dir()?;
+    let path = dir.join(format!("trackex-diagnostics-{}.zip", Utc::now().format("%Y%m%d-%H%M%S")));
+
+    let zip_path = path.clone();
+    tokio::task::spawn_blocking(move || {
+        write_zip(&bundle, &zip_path)?;
+        prune_saved_bundles(&dir);
+        Ok::<_, anyhow::Error>(())
+    })
+    .await??;
+
+    log::info!("🩺 Diagnostics bundle saved to {:?}", path);
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacts_tokens_in_text() {
+        let line = "Authorization: Bearer abc.def-123 sent, refresh_token=xyz&device_id=d1";
+        let redacted = redact_text(line);
+        assert!(!redacted.contains("abc.def-123"));
+        assert!(!redacted.contains("xyz"));
+        assert!(redacted.contains("device_id=d1"));
+    }
+
+    #[test]
+    fn test_redacts_secret_fields_recursively() {
+        let bundle = json!({
+            "config": {
+                "session": { "deviceToken": "tok-1", "deviceId": "dev-1", "serverUrl": null },
+                "env": { "TRACKEX_API_KEY": "k", "TRACKEX_DATA_DIR": "/tmp/x" },
+            },
+            "logs": ["login ok, \"deviceToken\": \"tok-2\""],
+        });
+        let redacted = redact_value(bundle).to_string();
+        assert!(!redacted.contains("tok-1"));
+        assert!(!redacted.contains("tok-2"));
+        assert!(!redacted.contains("\"k\""));
+        assert!(redacted.contains("dev-1"));
+        assert!(redacted.contains("/tmp/x"));
+    }
+
+    #[test]
+    fn test_zip_contains_report_and_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bundle.zip");
+        write_zip(&json!({ "agent": { "version": "1.0" }, "logs": ["first", "second"] }), &path).unwrap();
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let mut logs = String::new();
+        std::io::Read::read_to_string(&mut archive.by_name("logs.txt").unwrap(), &mut logs).unwrap();
+        assert_eq!(logs, "first\nsecond\n");
+
+        let report: Value = serde_json::from_reader(archive.by_name("diagnostics.json").unwrap()).unwrap();
+        assert_eq!(report["agent"]["version"], "1.0");
+        assert!(report.get("logs").is_none());
+    }
+}
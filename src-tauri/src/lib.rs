@@ -1,9 +1,11 @@
 pub mod commands;
 pub mod consent;
+pub mod diagnostics;
 pub mod sampling;
 pub mod screenshots;
 pub mod storage;
 pub mod api;
 pub mod policy;
 pub mod utils;
-pub mod permissions;
\ No newline at end of file
+pub mod permissions;
+pub mod platform;
\ No newline at end of file
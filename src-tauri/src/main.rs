@@ -3,6 +3,7 @@
 
 mod commands;
 mod consent;
+mod diagnostics;
 mod sampling;
 mod screenshots;
 mod storage;
@@ -10,6 +11,7 @@ mod api;
 mod policy;
 mod utils;
 mod permissions;
+mod platform;
 
 use std::sync::Arc;
 use tauri::{Manager, WindowEvent};
@@ -24,6 +26,14 @@ use crate::storage::AppState;
 fn main() {
     // Initialize logging
     logging::init();
+
+    // OS integrations (focus, idle, capture, power, credentials) for everything that follows
+    platform::configure(platform::Platform::native());
+
+    // Resolve the database location before anything touches storage
+    storage::database::configure(
+        storage::database::DatabaseConfig::from_env().with_cli_args(std::env::args().skip(1)),
+    );
     
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
@@ -46,6 +56,13 @@ fn main() {
             get_recent_sessions,
             clear_local_database,
             trigger_sync,
+            get_dead_letters,
+            replay_dead_letters,
+            get_queue_summary,
+            get_queue_items,
+            retry_queue_items,
+            discard_queue_items,
+            export_queue_items,
 
             get_tracking_status,
             take_screenshot,
@@ -77,12 +94,18 @@ fn main() {
             generate_weekly_report,
             generate_monthly_summary,
             test_server_connection,
+            get_connectivity_status,
+            get_policy,
+            sync_policy,
             refresh_work_session
         ])
         .setup(|app| {
             // Set the global app state
             let app_state = app.state::<Arc<Mutex<AppState>>>();
             crate::storage::set_global_app_state(app_state.inner().clone());
+
+            // Lets the API layer tell the UI about auth and connectivity changes
+            crate::api::init(app.handle().clone());
             
             // Initialize the database directly
             let app_handle_for_bg = app.handle().clone();
@@ -90,11 +113,11 @@ fn main() {
                 if let Err(e) = crate::storage::database::init().await {
                     log::error!("Failed to initialize database: {}", e);
                 } else {
-                }
-                
-                if let Err(e) = crate::storage::app_usage::init_database().await {
-                    log::error!("Failed to initialize app usage database: {}", e);
-                } else {
+                    // Keep the local queue bounded (delivered-row purge, heartbeat compaction, size cap)
+                    tokio::spawn(crate::storage::retention::start_retention_service());
+
+                    // Organisation policy: cached copy first, then kept in sync with the server
+                    tokio::spawn(crate::policy::sync::start_policy_sync());
                 }
                 
                 
@@ -103,10 +126,12 @@ fn main() {
                 
                 // Start background services
                 crate::sampling::start_services().await;
-                tokio::spawn(crate::sampling::start_queue_processing_service());
                 
-                // Start sync service for offline/online data synchronization
-                tokio::spawn(crate::sampling::start_sync_service());
+                // Start the sync engine - the only place queued events and heartbeats are sent from
+                tokio::spawn(crate::sampling::sync_engine::start_sync_engine());
+
+                // Probe the server only while requests say it is unreachable
+                tokio::spawn(crate::api::connectivity::start_connectivity_monitor());
                 
                 // Start logging configuration sync service
                 crate::utils::logging::start_logging_config_sync_service().await;
@@ -169,7 +194,11 @@ fn main() {
                         // TODO: Implement resume logic
                     }
                     "diagnostics" => {
-                        println!("Diagnostics requested from tray");
+                        tauri::async_runtime::spawn(async {
+                            if let Err(e) = crate::diagnostics::save_bundle().await {
+                                log::error!("Failed to save diagnostics bundle: {}", e);
+                            }
+                        });
                     }
                     _ => {}
                 })
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+
+use super::{CredentialStore, FocusProvider, IdleProvider, Platform, PlatformFuture, PowerProvider, ScreenCaptureProvider};
+use crate::sampling::app_focus::AppInfo;
+
+// Stand-ins for the platform traits that report exactly what a test tells
+// them to. `FakePlatform::install` makes them the current platform.
+
+/// Reports the app last passed to `set_app`
+#[derive(Default)]
+pub struct FakeFocus {
+    app: Mutex<Option<AppInfo>>,
+}
+
+impl FakeFocus {
+    pub fn set_app(&self, app: Option<AppInfo>) {
+        if let Ok(mut current) = self.app.lock() {
+            *current = app;
+        }
+    }
+}
+
+impl FocusProvider for FakeFocus {
+    fn current_app(&self) -> PlatformFuture<'_, Option<AppInfo>> {
+        let app = self.app.lock().ok().and_then(|app| app.clone());
+        Box::pin(async move { Ok(app) })
+    }
+}
+
+/// Reports the idle time last passed to `set_idle_seconds`
+#[derive(Default)]
+pub struct FakeIdle {
+    seconds: AtomicU64,
+}
+
+impl FakeIdle {
+    pub fn set_idle_seconds(&self, seconds: u64) {
+        self.seconds.store(seconds, Ordering::Relaxed);
+    }
+}
+
+impl IdleProvider for FakeIdle {
+    fn idle_seconds(&self) -> PlatformFuture<'_, u64> {
+        let seconds = self.seconds.load(Ordering::Relaxed);
+        Box::pin(async move { Ok(seconds) })
+    }
+}
+
+/// Returns a fixed image, or fails once `fail` is called
+pub struct FakeScreenCapture {
+    image: Mutex<Option<String>>,
+}
+
+impl Default for FakeScreenCapture {
+    fn default() -> Self {
+        Self {
+            image: Mutex::new(Some(base64::engine::general_purpose::STANDARD.encode(b"fake screenshot"))),
+        }
+    }
+}
+
+impl FakeScreenCapture {
+    pub fn fail(&self) {
+        if let Ok(mut image) = self.image.lock() {
+            *image = None;
+        }
+    }
+}
+
+impl ScreenCaptureProvider for FakeScreenCapture {
+    fn capture_screen(&self) -> PlatformFuture<'_, String> {
+        let image = self.image.lock().ok().and_then(|image| image.clone());
+        Box::pin(async move { image.ok_or_else(|| anyhow::anyhow!("Screen capture failed")) })
+    }
+}
+
+/// A clock that only moves when told to, so a test can make the machine "sleep"
+pub struct FakeClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl Default for FakeClock {
+    fn default() -> Self {
+        Self { now: Mutex::new(Utc::now()) }
+    }
+}
+
+impl FakeClock {
+    pub fn advance(&self, seconds: i64) {
+        if let Ok(mut now) = self.now.lock() {
+            *now += Duration::seconds(seconds);
+        }
+    }
+}
+
+impl PowerProvider for FakeClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.now.lock().map(|now| *now).unwrap_or_else(|_| Utc::now())
+    }
+}
+
+/// Keeps credentials in memory
+#[derive(Default)]
+pub struct MemoryCredentialStore {
+    values: Mutex<HashMap<String, String>>,
+}
+
+impl CredentialStore for MemoryCredentialStore {
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.values.lock().map_err(|_| anyhow::anyhow!("Credential store poisoned"))?.get(key).cloned())
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<()> {
+        self.values
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Credential store poisoned"))?
+            .insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        self.values.lock().map_err(|_| anyhow::anyhow!("Credential store poisoned"))?.remove(key);
+        Ok(())
+    }
+}
+
+/// One of each fake, kept so a test can steer them after installing
+#[derive(Default, Clone)]
+pub struct FakePlatform {
+    pub focus: Arc<FakeFocus>,
+    pub idle: Arc<FakeIdle>,
+    pub capture: Arc<FakeScreenCapture>,
+    pub power: Arc<FakeClock>,
+    pub credentials: Arc<MemoryCredentialStore>,
+}
+
+impl FakePlatform {
+    pub fn platform(&self) -> Platform {
+        Platform {
+            focus: self.focus.clone(),
+            idle: self.idle.clone(),
+            capture: self.capture.clone(),
+            power: self.power.clone(),
+            credentials: self.credentials.clone(),
+        }
+    }
+
+    /// Make these fakes the current platform
+    pub fn install(&self) {
+        super::configure(self.platform());
+    }
+}
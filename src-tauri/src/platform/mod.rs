@@ -0,0 +1,223 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+use crate::sampling::app_focus::AppInfo;
+
+// Only tests build the fakes
+#[cfg(test)]
+mod fake;
+
+// Everything the agent needs from the operating system, one trait per concern.
+// The native implementations live next to the code that uses them (app focus,
+// idle detection, screen capture, power state, secure store). `configure`
+// selects the set to use at startup; tests install the fakes from
+// `platform::fake` instead, so the sampling pipeline runs deterministically
+// without touching the machine.
+
+pub type PlatformFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Nudges from a `FocusProvider` that focus may have moved. Nudges that arrive
+/// before the receiver looks collapse into one.
+pub type FocusChanges = tokio::sync::watch::Receiver<()>;
+
+/// Which app is in front
+pub trait FocusProvider: Send + Sync {
+    /// The app owning the focused window, or `None` when nothing has focus
+    fn current_app(&self) -> PlatformFuture<'_, Option<AppInfo>>;
+
+    /// Focus change notifications, where the platform can push them. `None`
+    /// means focus has to be polled.
+    fn focus_changes(&self) -> Option<FocusChanges> {
+        None
+    }
+}
+
+/// How long since the user last touched the keyboard or mouse
+pub trait IdleProvider: Send + Sync {
+    fn idle_seconds(&self) -> PlatformFuture<'_, u64>;
+}
+
+/// Screenshots, as base64-encoded JPEG
+pub trait ScreenCaptureProvider: Send + Sync {
+    fn capture_screen(&self) -> PlatformFuture<'_, String>;
+}
+
+/// The clock sleep and wake are detected with: time that passes between two
+/// samples without the samplers running was spent asleep
+pub trait PowerProvider: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// Secrets in the OS credential store, by key
+pub trait CredentialStore: Send + Sync {
+    fn get(&self, key: &str) -> Result<Option<String>>;
+    fn set(&self, key: &str, value: &str) -> Result<()>;
+    fn delete(&self, key: &str) -> Result<()>;
+}
+
+#[derive(Clone)]
+pub struct Platform {
+    pub focus: Arc<dyn FocusProvider>,
+    pub idle: Arc<dyn IdleProvider>,
+    pub capture: Arc<dyn ScreenCaptureProvider>,
+    pub power: Arc<dyn PowerProvider>,
+    pub credentials: Arc<dyn CredentialStore>,
+}
+
+impl Platform {
+    /// The implementations for the OS we were built for
+    pub fn native() -> Self {
+        #[cfg(target_os = "linux")]
+        let focus = Arc::new(crate::sampling::linux_focus::X11FocusProvider::new(None));
+        #[cfg(not(target_os = "linux"))]
+        let focus = Arc::new(crate::sampling::app_focus::SystemFocus);
+
+        Self {
+            focus,
+            idle: Arc::new(crate::sampling::idle_detector::SystemIdle),
+            capture: Arc::new(crate::screenshots::screen_capture::SystemScreenCapture),
+            power: Arc::new(crate::sampling::power_state::SystemPower),
+            credentials: Arc::new(crate::storage::secure_store::SystemCredentialStore),
+        }
+    }
+}
+
+static PLATFORM: RwLock<Option<Arc<Platform>>> = RwLock::new(None);
+
+/// Set the platform services used from here on. Called once at startup; tests
+/// call it again to swap in fakes.
+pub fn configure(platform: Platform) {
+    if let Ok(mut current) = PLATFORM.write() {
+        *current = Some(Arc::new(platform));
+    }
+}
+
+/// The configured platform services, the native ones if none were configured
+pub fn current() -> Arc<Platform> {
+    if let Some(platform) = PLATFORM.read().ok().and_then(|current| current.clone()) {
+        return platform;
+    }
+    match PLATFORM.write() {
+        Ok(mut current) => current.get_or_insert_with(|| Arc::new(Platform::native())).clone(),
+        Err(_) => Arc::new(Platform::native()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fake::FakePlatform;
+    use super::*;
+    use crate::api::connectivity::{self, Outcome};
+    use crate::sampling::{app_focus, idle_detector, power_state};
+    use crate::storage::{app_usage, database, offline_queue, secure_store};
+
+    // The platform is process-wide, so tests that install fakes take turns
+    static PLATFORM_TEST: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    fn app(name: &str, app_id: &str) -> AppInfo {
+        AppInfo { name: name.to_string(), app_id: app_id.to_string(), window_title: None }
+    }
+
+    #[tokio::test]
+    async fn test_focus_sampling_runs_on_fakes() {
+        let _turn = PLATFORM_TEST.lock().await;
+        let fakes = FakePlatform::default();
+        fakes.install();
+        let _db = database::test_database().await;
+        let _policy = crate::policy::toggles::test_policy(crate::policy::toggles::PolicyConfig::default()).await;
+        // Keep the focus events off the network
+        connectivity::record(Outcome::Unreachable);
+
+        let mut last = None;
+        fakes.focus.set_app(Some(app("Editor", "org.example.Editor")));
+        app_focus::sample_focus(&mut last).await;
+        let session = app_usage::get_current_session().await.unwrap();
+        assert_eq!(session.app_name, "Editor");
+        assert!(!session.is_idle);
+
+        fakes.idle.set_idle_seconds(idle_detector::get_idle_threshold());
+        app_focus::sample_focus(&mut last).await;
+        assert!(app_usage::get_current_session().await.unwrap().is_idle);
+
+        // Looking at TrackEx itself still counts as the app before it
+        fakes.idle.set_idle_seconds(0);
+        fakes.focus.set_app(Some(app("TrackEx Agent", "com.trackex.agent")));
+        app_focus::sample_focus(&mut last).await;
+        assert_eq!(app_usage::get_current_session().await.unwrap().app_name, "Editor");
+
+        // Focus somewhere the provider can't see isn't credited to the last app
+        fakes.focus.set_app(None);
+        assert!(app_focus::current_app().await.unwrap().is_none());
+        app_focus::sample_focus(&mut last).await;
+        assert!(last.is_none());
+        assert!(app_usage::get_current_session().await.is_none());
+
+        fakes.focus.set_app(Some(app("Browser", "org.example.Browser")));
+        app_focus::sample_focus(&mut last).await;
+        assert_eq!(app_usage::get_current_session().await.unwrap().app_name, "Browser");
+
+        let queued: Vec<String> = offline_queue::get_pending_events()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|event| event.event_type == "app_focus")
+            .map(|event| event.event_data["app_name"].as_str().unwrap_or_default().to_string())
+            .collect();
+        assert_eq!(queued, vec!["Editor", "Browser"]);
+
+        connectivity::record(Outcome::Reached);
+    }
+
+    #[tokio::test]
+    async fn test_capture_and_credentials_use_fakes() {
+        let _turn = PLATFORM_TEST.lock().await;
+        let fakes = FakePlatform::default();
+        fakes.install();
+
+        assert!(!crate::screenshots::screen_capture::capture_screen().await.unwrap().is_empty());
+        fakes.capture.fail();
+        assert!(crate::screenshots::screen_capture::capture_screen().await.is_err());
+
+        secure_store::store_device_token("token-1").await.unwrap();
+        assert_eq!(secure_store::get_device_token().await.unwrap().as_deref(), Some("token-1"));
+        secure_store::delete_device_token().await.unwrap();
+        assert_eq!(secure_store::get_device_token().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_sleep_detected_from_platform_clock() {
+        let _turn = PLATFORM_TEST.lock().await;
+        let fakes = FakePlatform::default();
+        fakes.install();
+
+        power_state::update_last_activity();
+        fakes.power.advance(60);
+        assert_eq!(power_state::detect_time_gap().await, None);
+
+        fakes.power.advance(3600);
+        assert_eq!(power_state::detect_time_gap().await, Some(3660));
+    }
+
+    #[tokio::test]
+    async fn test_wake_event_is_stamped_with_platform_clock() {
+        let _turn = PLATFORM_TEST.lock().await;
+        let fakes = FakePlatform::default();
+        fakes.install();
+        let _db = database::test_database().await;
+        connectivity::record(Outcome::Unreachable);
+
+        fakes.power.advance(-7200);
+        power_state::handle_system_wake(3660).await;
+
+        let events = offline_queue::get_pending_events().await.unwrap();
+        assert_eq!(events[0].event_type, "idle_end");
+        assert_eq!(events[0].timestamp, fakes.power.now());
+        assert_eq!(events[0].event_data["timestamp"], fakes.power.now().to_rfc3339());
+
+        connectivity::record(Outcome::Reached);
+    }
+}
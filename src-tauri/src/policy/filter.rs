@@ -0,0 +1,185 @@
+use serde_json::Value;
+
+use crate::policy::privacy::{extract_domain_from_title, is_allowlisted, should_use_domain_only};
+use crate::policy::toggles::{get_current_policy, PolicyConfig};
+
+// The privacy filter. Every request body the API client sends and every row
+// written to the local queue or app usage table passes through here, so window
+// titles are reduced according to the active policy before they leave the
+// device or touch the disk.
+//
+// The filter is idempotent: filtering an already filtered payload changes
+// nothing, which matters because queued payloads are filtered again when sent.
+
+pub const REDACTED_TITLE: &str = "[Redacted]";
+
+// Payload fields holding a window title, and the fields naming the app it belongs to
+const TITLE_KEYS: &[&str] = &["window_title", "windowTitle"];
+const APP_ID_KEYS: &[&str] = &["app_id", "appId", "bundle_id"];
+
+// Top-level domains a host name may end in. Deliberately a short list without
+// the ones that double as file extensions (.ai, .md, .pl, .py, .rs, .sh, .zip),
+// so "budget.xlsx" or "plan.md" is never taken for a site.
+const KNOWN_TLDS: &[&str] = &[
+    "com", "net", "org", "edu", "gov", "mil", "int", "info", "biz", "io", "dev", "app", "co", "me", "tv",
+    "us", "uk", "ca", "au", "nz", "ie", "de", "fr", "es", "it", "nl", "be", "at", "ch", "se", "no", "dk",
+    "fi", "pt", "br", "mx", "ar", "jp", "cn", "kr", "in", "sg", "hk", "za", "eu",
+];
+
+/// A bare host name such as `github.com`
+fn is_hostname(text: &str) -> bool {
+    let labels: Vec<&str> = text.split('.').collect();
+    labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        && labels.last().is_some_and(|tld| KNOWN_TLDS.contains(&tld.to_ascii_lowercase().as_str()))
+}
+
+/// The host name a title refers to, if it names one
+fn hostname_in(title: &str) -> Option<String> {
+    extract_domain_from_title(title)
+        .filter(|domain| is_hostname(domain))
+        .or_else(|| is_hostname(title).then(|| title.to_string()))
+}
+
+/// The site a browser window shows. Only browsers have sites: in any other app
+/// a `name.ext` in the title is far more likely a file than a host.
+pub fn site_in(app_id: &str, title: &str) -> Option<String> {
+    should_use_domain_only(app_id).then(|| hostname_in(title)).flatten()
+}
+
+/// Reduce a window title according to `policy`:
+/// - redaction off (or not applicable to the app): the title is kept
+/// - browsers in domain-only mode: only the site's host name is kept
+/// - anything else: titles on the organisation's allowlist are kept; the rest
+///   is cut to the site's host name in browsers and redacted elsewhere
+pub fn filter_title(policy: &PolicyConfig, app_id: &str, title: &str) -> String {
+    if !policy.should_redact_title(app_id) || title == REDACTED_TITLE {
+        return title.to_string();
+    }
+
+    let domain_only = policy.domain_only_mode && should_use_domain_only(app_id);
+    if !domain_only && is_allowlisted(title, &policy.allowlist_patterns) {
+        return title.to_string();
+    }
+
+    site_in(app_id, title).unwrap_or_else(|| REDACTED_TITLE.to_string())
+}
+
+/// Apply `policy` to every window title in `payload`, at any depth
+pub fn filter_payload_with(policy: &PolicyConfig, payload: &mut Value) {
+    match payload {
+        Value::Object(map) => {
+            let app_id = APP_ID_KEYS
+                .iter()
+                .find_map(|key| map.get(*key).and_then(Value::as_str))
+                .unwrap_or_default()
+                .to_string();
+
+            for (key, value) in map.iter_mut() {
+                match value {
+                    Value::String(title) if TITLE_KEYS.contains(&key.as_str()) => {
+                        *title = filter_title(policy, &app_id, title);
+                    }
+                    _ => filter_payload_with(policy, value),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| filter_payload_with(policy, item)),
+        _ => {}
+    }
+}
+
+/// Apply the active policy to every window title in `payload`
+pub fn filter_payload(mut payload: Value) -> Value {
+    filter_payload_with(&get_current_policy(), &mut payload);
+    payload
+}
+
+/// Apply the active policy to one app's window title
+pub fn filter_app_title(app_id: &str, title: Option<String>) -> Option<String> {
+    title.map(|title| filter_title(&get_current_policy(), app_id, &title))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_browsers_keep_only_the_host_name() {
+        let policy = PolicyConfig::default();
+        assert_eq!(filter_title(&policy, "com.google.Chrome", "github.com - Pull requests - Google Chrome"), "github.com");
+        assert_eq!(filter_title(&policy, "com.google.Chrome", "https://www.example.com/account/42"), "www.example.com");
+        assert_eq!(filter_title(&policy, "com.google.Chrome", "Inbox (3) - jane@corp.com - Gmail"), REDACTED_TITLE);
+    }
+
+    #[test]
+    fn test_other_apps_use_the_allowlist() {
+        let policy = PolicyConfig { allowlist_patterns: vec!["^Dashboard".to_string()], ..PolicyConfig::default() };
+        assert_eq!(filter_title(&policy, "com.apple.TextEdit", "Dashboard - TrackEx"), "Dashboard - TrackEx");
+        assert_eq!(filter_title(&policy, "com.apple.TextEdit", "Salary review.docx"), REDACTED_TITLE);
+    }
+
+    #[test]
+    fn test_document_titles_never_pass_with_the_default_policy() {
+        let policy = PolicyConfig::default();
+        for (app_id, title) in [
+            ("com.microsoft.Outlook", "Inbox - jane@corp.com - Outlook"),
+            ("com.microsoft.Excel", "Salaries - Alice Smith.xlsx - Excel"),
+            ("com.microsoft.Excel", "layoffs-q3.xlsx - Excel"),
+            ("com.microsoft.Word", "acme-merger.docx"),
+            ("com.apple.TextEdit", "notes.example.org | Notes"),
+        ] {
+            assert_eq!(filter_title(&policy, app_id, title), REDACTED_TITLE, "{} passed", title);
+        }
+    }
+
+    #[test]
+    fn test_file_names_are_not_sites() {
+        let policy = PolicyConfig::default();
+        assert_eq!(filter_title(&policy, "com.google.Chrome", "layoffs-q3.xlsx - Google Chrome"), REDACTED_TITLE);
+        assert_eq!(filter_title(&policy, "com.google.Chrome", "acme-merger.docx"), REDACTED_TITLE);
+        assert_eq!(site_in("com.google.Chrome", "docs.example.co.uk - Google Chrome").as_deref(), Some("docs.example.co.uk"));
+        assert_eq!(site_in("com.apple.TextEdit", "github.com - Notes"), None);
+    }
+
+    #[test]
+    fn test_redaction_can_be_turned_off() {
+        let policy = PolicyConfig { title_redaction_enabled: false, ..PolicyConfig::default() };
+        assert_eq!(filter_title(&policy, "com.google.Chrome", "Secret plans - Google Chrome"), "Secret plans - Google Chrome");
+    }
+
+    #[test]
+    fn test_filter_is_idempotent() {
+        let policy = PolicyConfig { allowlist_patterns: vec![], ..PolicyConfig::default() };
+        for (app_id, title) in [
+            ("com.google.Chrome", "github.com - Pull requests - Google Chrome"),
+            ("com.google.Chrome", "Stack Overflow — Where Developers Learn"),
+            ("com.apple.TextEdit", "Google - Chrome"),
+            ("com.apple.TextEdit", "notes.example.org | Notes"),
+        ] {
+            let once = filter_title(&policy, app_id, title);
+            assert_eq!(filter_title(&policy, app_id, &once), once, "not stable for {}", title);
+        }
+    }
+
+    #[test]
+    fn test_titles_in_payloads_never_pass_unfiltered() {
+        let policy = PolicyConfig::default();
+        let mut heartbeat = json!({
+            "status": "active",
+            "currentApp": { "name": "Chrome", "app_id": "com.google.Chrome", "window_title": "Private chat - Google Chrome" },
+        });
+        let mut batch = json!({
+            "events": [{ "type": "app_focus", "data": { "app_id": "com.apple.TextEdit", "window_title": "Layoffs plan.docx" } }],
+        });
+        filter_payload_with(&policy, &mut heartbeat);
+        filter_payload_with(&policy, &mut batch);
+
+        assert_eq!(heartbeat["currentApp"]["window_title"], REDACTED_TITLE);
+        assert_eq!(heartbeat["currentApp"]["name"], "Chrome");
+        assert_eq!(batch["events"][0]["data"]["window_title"], REDACTED_TITLE);
+    }
+}
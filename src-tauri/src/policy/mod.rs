@@ -1,4 +1,6 @@
 // Policy module - simplified for production testing
 
+pub mod filter;
 pub mod privacy;
+pub mod sync;
 pub mod toggles;
\ No newline at end of file
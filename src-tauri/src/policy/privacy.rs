@@ -1,7 +1,7 @@
-use regex::Regex;
+use regex::{Regex, RegexSet};
+use std::sync::{Arc, RwLock};
 
 // Domain-only mode for browsers
-#[allow(dead_code)]
 pub fn should_use_domain_only(bundle_id_or_process: &str) -> bool {
     #[cfg(target_os = "macos")]
     {
@@ -41,6 +41,7 @@ pub fn should_use_domain_only(bundle_id_or_process: &str) -> bool {
         // Fallback: simple check for common browser process names
         let browser_process_names = [
             "chrome",
+            "chromium",
             "firefox",
             "brave",
             "opera",
@@ -55,7 +56,6 @@ pub fn should_use_domain_only(bundle_id_or_process: &str) -> bool {
 }
 
 // Extract domain from browser window title
-#[allow(dead_code)]
 pub fn extract_domain_from_title(title: &str) -> Option<String> {
     // Simple regex to extract domain from common browser title formats
     let domain_patterns = [
@@ -89,42 +89,29 @@ pub fn extract_domain_from_title(title: &str) -> Option<String> {
     None
 }
 
-// Title redaction using regex allowlist
-#[allow(dead_code)]
-pub fn redact_window_title(title: &str, allowlist_patterns: &[String]) -> String {
-    // If no patterns provided, redact everything except domains
-    if allowlist_patterns.is_empty() {
-        if let Some(domain) = extract_domain_from_title(title) {
-            return domain;
-        }
-        return "[Redacted]".to_string();
-    }
+// The compiled allowlist and the patterns it was built from. Titles are checked
+// on every sample, so the regexes are only rebuilt when the policy changes them.
+static ALLOWLIST: RwLock<Option<(Vec<String>, Arc<RegexSet>)>> = RwLock::new(None);
 
-    // Check if title matches any allowlist pattern
-    for pattern in allowlist_patterns {
-        if let Ok(regex) = Regex::new(pattern) {
-            if regex.is_match(title) {
-                return title.to_string(); // Allow full title
-            }
+/// Compiled form of `allowlist_patterns` (invalid patterns are skipped)
+pub fn compile_allowlist(allowlist_patterns: &[String]) -> Arc<RegexSet> {
+    if let Some((patterns, compiled)) = ALLOWLIST.read().ok().and_then(|cached| cached.clone()) {
+        if patterns == allowlist_patterns {
+            return compiled;
         }
     }
 
-    // Try to extract domain as fallback
-    if let Some(domain) = extract_domain_from_title(title) {
-        domain
-    } else {
-        "[Redacted]".to_string()
+    let valid = allowlist_patterns.iter().filter(|pattern| Regex::new(pattern).is_ok());
+    let compiled = Arc::new(RegexSet::new(valid).unwrap_or_else(|_| RegexSet::empty()));
+    if let Ok(mut cached) = ALLOWLIST.write() {
+        *cached = Some((allowlist_patterns.to_vec(), compiled.clone()));
     }
+    compiled
 }
 
-// Get default title allowlist patterns
-#[allow(dead_code)]
-pub fn get_default_allowlist_patterns() -> Vec<String> {
-    vec![
-        r"^[a-zA-Z0-9\-\.]+\.[a-zA-Z]{2,}".to_string(), // Domain names
-        r"^(Dashboard|Settings|Profile|Home|Login)".to_string(), // Common safe terms
-        r"^\w+\s*-\s*\w+".to_string(), // Simple app names
-    ]
+// True if the title matches any allowlist pattern
+pub fn is_allowlisted(title: &str, allowlist_patterns: &[String]) -> bool {
+    !allowlist_patterns.is_empty() && compile_allowlist(allowlist_patterns).is_match(title)
 }
 
 #[cfg(test)]
@@ -158,17 +145,24 @@ mod tests {
 
     #[test]
     fn test_title_redaction() {
-        let patterns = vec!["^Dashboard".to_string()];
-        
-        assert_eq!(
-            redact_window_title("Dashboard - TrackEx", &patterns),
-            "Dashboard - TrackEx"
-        );
-        
-        assert_eq!(
-            redact_window_title("Secret Document.docx", &patterns),
-            "[Redacted]"
-        );
+        let policy = crate::policy::toggles::PolicyConfig {
+            allowlist_patterns: vec!["^Dashboard".to_string()],
+            ..Default::default()
+        };
+        let filter = |title| crate::policy::filter::filter_title(&policy, "com.trackex.agent", title);
+
+        assert_eq!(filter("Dashboard - TrackEx"), "Dashboard - TrackEx");
+        assert_eq!(filter("Secret Document.docx"), "[Redacted]");
     }
-}
 
+    #[test]
+    fn test_allowlist_skips_invalid_patterns() {
+        let patterns = vec!["^Dashboard".to_string(), "(unclosed".to_string()];
+
+        assert_eq!(compile_allowlist(&patterns).len(), 1);
+
+        assert!(is_allowlisted("Dashboard - TrackEx", &patterns));
+        assert!(!is_allowlisted("Secret Document.docx", &patterns));
+        assert!(!is_allowlisted("Dashboard - TrackEx", &["^Settings".to_string()]));
+    }
+}
@@ -0,0 +1,174 @@
+use std::sync::Mutex;
+
+use anyhow::Result;
+use tokio::time::Duration;
+
+use crate::api::client::{ApiClient, Auth, Timeout};
+use crate::api::error::ApiError;
+use crate::policy::toggles::{self, PolicyConfig};
+use crate::storage::policy_cache;
+
+// Organisation policy from the server. Fetched periodically with the last
+// ETag, so an unchanged policy costs a 304; every new policy is cached in
+// SQLite, applied to the running samplers and announced to the UI.
+
+// Tauri event carrying the new policy
+pub const POLICY_EVENT: &str = "policy-changed";
+
+const POLICY_ENDPOINT: &str = "/api/policy";
+const POLICY_SYNC_INTERVAL_SECS: u64 = 5 * 60;
+
+// ETag of the policy currently applied
+static ETAG: Mutex<Option<String>> = Mutex::new(None);
+
+#[derive(Debug)]
+pub enum PolicyFetch {
+    /// 304: the policy we have is current
+    Unchanged,
+    Updated { policy: PolicyConfig, etag: Option<String> },
+}
+
+/// Ask the server for the policy, sending `etag` as If-None-Match
+pub async fn fetch_policy(client: &ApiClient, auth: Auth, etag: Option<&str>) -> Result<PolicyFetch, ApiError> {
+    let mut request = client.get(POLICY_ENDPOINT).auth(auth).timeout(Timeout::Standard);
+    if let Some(etag) = etag {
+        request = request.header("If-None-Match", etag);
+    }
+    let url = request.url().to_string();
+
+    let response = request.send().await?;
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(PolicyFetch::Unchanged);
+    }
+
+    let etag = response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let body = response.text().await.unwrap_or_default();
+    if !status.is_success() {
+        return Err(ApiError::from_status(status.as_u16(), &body));
+    }
+
+    let policy = serde_json::from_str(&body).map_err(|e| ApiError::Schema { url, reason: e.to_string() })?;
+    Ok(PolicyFetch::Updated { policy, etag })
+}
+
+fn apply(policy: PolicyConfig, source: &str) {
+    if toggles::update_policy(policy.clone()) {
+        log::info!(
+            "📜 Policy applied from {}: screenshots={} every {}m, idle={}s, title redaction={}, domain only={}",
+            source,
+            policy.screenshot_enabled,
+            policy.screenshot_interval_minutes,
+            policy.idle_threshold_seconds,
+            policy.title_redaction_enabled,
+            policy.domain_only_mode
+        );
+        crate::api::emit(POLICY_EVENT, policy);
+    }
+}
+
+/// Start from the last policy the server gave us, if any
+pub async fn load_cached_policy() {
+    match policy_cache::get_cached_policy().await {
+        Ok(Some(cached)) => {
+            log::info!("Using cached policy from {}", cached.fetched_at);
+            if let Ok(mut etag) = ETAG.lock() {
+                *etag = cached.etag;
+            }
+            apply(cached.policy, "cache");
+        }
+        Ok(None) => {}
+        Err(e) => log::warn!("Failed to load cached policy: {}", e),
+    }
+}
+
+/// Fetch the policy now and apply it if it changed. Returns whether it changed.
+pub async fn sync_policy_now() -> Result<bool> {
+    let client = ApiClient::new().await?;
+    let current_etag = ETAG.lock().ok().and_then(|etag| etag.clone());
+
+    match fetch_policy(&client, Auth::Device, current_etag.as_deref()).await? {
+        PolicyFetch::Unchanged => Ok(false),
+        PolicyFetch::Updated { policy, etag } => {
+            // Persist first so a crash can't leave us running a policy we can't restore
+            policy_cache::save_policy(policy.clone(), etag.clone()).await?;
+            if let Ok(mut current) = ETAG.lock() {
+                *current = etag;
+            }
+            apply(policy, "server");
+            Ok(true)
+        }
+    }
+}
+
+/// Drop the policy of the organisation we just logged out of, so the next
+/// login starts from the local defaults and a full fetch
+pub async fn forget_policy() {
+    if let Ok(mut etag) = ETAG.lock() {
+        *etag = None;
+    }
+    if let Err(e) = policy_cache::clear_cached_policy().await {
+        log::warn!("Failed to clear cached policy: {}", e);
+    }
+    apply(PolicyConfig::from_env(), "defaults");
+}
+
+/// Keep the policy in sync with the server for as long as the app runs
+pub async fn start_policy_sync() {
+    load_cached_policy().await;
+
+    let mut interval = tokio::time::interval(Duration::from_secs(POLICY_SYNC_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+
+        // The policy belongs to the organisation behind the device token
+        if !crate::sampling::is_authenticated().await
+            || crate::api::connectivity::is_offline()
+            || crate::api::auth::is_suspended()
+        {
+            continue;
+        }
+
+        if let Err(e) = sync_policy_now().await {
+            match e.downcast_ref::<ApiError>() {
+                // Server without policy support: keep what we have
+                Some(api) if api.status() == Some(404) => log::debug!("Server has no policy endpoint"),
+                _ => log::warn!("Policy sync failed, keeping current policy: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_support::mock_server;
+
+    #[tokio::test]
+    async fn test_fetch_uses_etag_and_keeps_defaults_for_missing_fields() {
+        let (client, server) = mock_server(vec![
+            "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 60\r\n\r\n{\"screenshot_enabled\":true,\"screenshot_interval_minutes\":30}".to_string(),
+            "HTTP/1.1 304 Not Modified\r\nContent-Length: 0\r\n\r\n".to_string(),
+        ])
+        .await;
+
+        let PolicyFetch::Updated { policy, etag } = fetch_policy(&client, Auth::None, None).await.unwrap() else {
+            panic!("expected a policy");
+        };
+        assert!(policy.screenshot_enabled);
+        assert_eq!(policy.screenshot_interval_minutes, 30);
+        assert_eq!(policy.idle_threshold_seconds, PolicyConfig::default().idle_threshold_seconds);
+        assert_eq!(etag.as_deref(), Some("\"v1\""));
+
+        let again = fetch_policy(&client, Auth::None, etag.as_deref()).await.unwrap();
+        assert!(matches!(again, PolicyFetch::Unchanged));
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("get /api/policy"));
+        assert!(requests[1].contains("if-none-match: \"v1\""));
+    }
+}
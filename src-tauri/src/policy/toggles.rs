@@ -1,7 +1,18 @@
 use serde::{Deserialize, Serialize};
-use std::sync::Mutex;
+use std::sync::OnceLock;
+use tokio::sync::watch;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+// Seconds without input before the user counts as idle, unless the policy says otherwise
+pub const DEFAULT_IDLE_THRESHOLD_SECS: u64 = 300;
+
+// Shortest stay on a window title or site that is recorded as its own segment
+pub const DEFAULT_SUB_ACTIVITY_DWELL_SECS: u64 = 10;
+
+/// Collection policy. Starts from the environment and is then replaced by the
+/// organisation's policy from the server (see `policy::sync`); fields the server
+/// leaves out keep their defaults.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
 #[allow(dead_code)]
 pub struct PolicyConfig {
     pub screenshot_enabled: bool,
@@ -10,6 +21,25 @@ pub struct PolicyConfig {
     pub title_redaction_enabled: bool,
     pub idle_threshold_seconds: u64,
     pub allowlist_patterns: Vec<String>,
+    /// Per-app idle thresholds, e.g. a longer one during video calls
+    pub idle_overrides: Vec<IdleOverride>,
+    /// Record title and site changes within an app session as segments
+    pub sub_activity_enabled: bool,
+    pub sub_activity_min_dwell_seconds: u64,
+}
+
+/// Idle threshold for apps whose ID or name contains `app` (case-insensitive)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IdleOverride {
+    pub app: String,
+    pub idle_threshold_seconds: u64,
+}
+
+impl IdleOverride {
+    fn matches(&self, app_id: &str, app_name: &str) -> bool {
+        let app = self.app.to_lowercase();
+        !app.is_empty() && (app_id.to_lowercase().contains(&app) || app_name.to_lowercase().contains(&app))
+    }
 }
 
 impl Default for PolicyConfig {
@@ -19,14 +49,17 @@ impl Default for PolicyConfig {
             screenshot_interval_minutes: 0,
             domain_only_mode: true,
             title_redaction_enabled: true,
-            idle_threshold_seconds: 300, // 5 minutes
-            allowlist_patterns: crate::policy::privacy::get_default_allowlist_patterns(),
+            idle_threshold_seconds: DEFAULT_IDLE_THRESHOLD_SECS,
+            // Only the organisation decides which titles are safe to keep
+            allowlist_patterns: Vec::new(),
+            idle_overrides: Vec::new(),
+            sub_activity_enabled: false,
+            sub_activity_min_dwell_seconds: DEFAULT_SUB_ACTIVITY_DWELL_SECS,
         }
     }
 }
 
 impl PolicyConfig {
-    #[allow(dead_code)]
     pub fn from_env() -> Self {
         let mut config = Self::default();
         
@@ -48,23 +81,33 @@ impl PolicyConfig {
         }
         
         if let Ok(val) = std::env::var("TRACKEX_IDLE_THRESHOLD") {
-            config.idle_threshold_seconds = val.parse().unwrap_or(300);
+            config.idle_threshold_seconds = val.parse().unwrap_or(DEFAULT_IDLE_THRESHOLD_SECS);
+        }
+
+        if let Ok(val) = std::env::var("TRACKEX_SUB_ACTIVITY") {
+            config.sub_activity_enabled = val.parse().unwrap_or(false);
+        }
+
+        if let Ok(val) = std::env::var("TRACKEX_SUB_ACTIVITY_DWELL") {
+            config.sub_activity_min_dwell_seconds = val.parse().unwrap_or(DEFAULT_SUB_ACTIVITY_DWELL_SECS);
         }
         
         config
     }
+
+    /// Idle threshold while `app_id`/`app_name` has focus: the first matching
+    /// override, or the policy-wide threshold
+    pub fn idle_threshold_for(&self, app_id: &str, app_name: &str) -> u64 {
+        self.idle_overrides
+            .iter()
+            .find(|o| o.matches(app_id, app_name))
+            .map_or(self.idle_threshold_seconds, |o| o.idle_threshold_seconds)
+    }
     
-    #[allow(dead_code)]
     pub fn should_take_screenshot(&self) -> bool {
         self.screenshot_enabled && self.screenshot_interval_minutes > 0
     }
     
-    #[allow(dead_code)]
-    pub fn get_screenshot_interval_seconds(&self) -> u64 {
-        (self.screenshot_interval_minutes as u64) * 60
-    }
-    
-    #[allow(dead_code)]
     pub fn should_redact_title(&self, app_id: &str) -> bool {
         if !self.title_redaction_enabled {
             return false;
@@ -80,21 +123,93 @@ impl PolicyConfig {
     }
 }
 
-#[allow(dead_code)]
-static CURRENT_POLICY: Mutex<Option<PolicyConfig>> = Mutex::new(None);
+// The active policy. Everything it governs (idle thresholds, title filtering,
+// sub-activity segments, screenshot jobs) reads it at the point of use, so a
+// policy pushed by the server applies from the next sample without a restart.
+static CURRENT_POLICY: OnceLock<watch::Sender<PolicyConfig>> = OnceLock::new();
+
+fn channel() -> &'static watch::Sender<PolicyConfig> {
+    CURRENT_POLICY.get_or_init(|| watch::channel(PolicyConfig::from_env()).0)
+}
 
-#[allow(dead_code)]
 pub fn get_current_policy() -> PolicyConfig {
-    CURRENT_POLICY.lock().unwrap().clone().unwrap_or_else(|| PolicyConfig::default())
+    channel().borrow().clone()
 }
 
-#[allow(dead_code)]
-pub fn update_policy(config: PolicyConfig) {
-    *CURRENT_POLICY.lock().unwrap() = Some(config);
+/// Replace the active policy. Returns whether anything changed.
+pub fn update_policy(config: PolicyConfig) -> bool {
+    crate::policy::privacy::compile_allowlist(&config.allowlist_patterns);
+    channel().send_if_modified(|current| {
+        if *current == config {
+            return false;
+        }
+        *current = config;
+        true
+    })
 }
-    
-#[allow(dead_code)]
-pub fn initialize_policy() {
-    let config = PolicyConfig::from_env();
+
+// The policy is process-wide, so tests that change it take turns
+#[cfg(test)]
+static TEST_POLICY: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Make `config` the active policy for as long as the guard is held
+#[cfg(test)]
+pub(crate) async fn test_policy(config: PolicyConfig) -> tokio::sync::MutexGuard<'static, ()> {
+    let guard = TEST_POLICY.lock().await;
     update_policy(config);
+    guard
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn override_for(app: &str, idle_threshold_seconds: u64) -> IdleOverride {
+        IdleOverride { app: app.to_string(), idle_threshold_seconds }
+    }
+
+    #[test]
+    fn test_idle_threshold_uses_first_matching_override() {
+        let policy = PolicyConfig {
+            idle_threshold_seconds: 300,
+            idle_overrides: vec![override_for("zoom", 1800), override_for("us.zoom", 60), override_for("", 5)],
+            ..PolicyConfig::default()
+        };
+
+        assert_eq!(policy.idle_threshold_for("us.zoom.xos", "zoom.us"), 1800);
+        // Matched on the name too, ignoring case
+        assert_eq!(policy.idle_threshold_for("com.example.meetings", "Zoom Workplace"), 1800);
+        // An empty pattern matches nothing
+        assert_eq!(policy.idle_threshold_for("org.example.Editor", "Editor"), 300);
+    }
+
+    #[test]
+    fn test_idle_overrides_come_from_the_server_policy() {
+        let policy: PolicyConfig = serde_json::from_str(
+            r#"{"idle_threshold_seconds": 600, "idle_overrides": [{"app": "teams", "idle_threshold_seconds": 3600}]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(policy.idle_threshold_for("com.microsoft.teams2", "Microsoft Teams"), 3600);
+        assert_eq!(policy.idle_threshold_for("org.example.Editor", "Editor"), 600);
+        assert!(!policy.sub_activity_enabled);
+    }
+
+    #[test]
+    fn test_sub_activity_from_env() {
+        // Keep other policy tests from reading the environment while it is changed
+        let _policy = TEST_POLICY.blocking_lock();
+        let config = PolicyConfig::default();
+        assert!(!config.sub_activity_enabled);
+        assert_eq!(config.sub_activity_min_dwell_seconds, 10);
+
+        std::env::set_var("TRACKEX_SUB_ACTIVITY", "true");
+        std::env::set_var("TRACKEX_SUB_ACTIVITY_DWELL", "30");
+        let config = PolicyConfig::from_env();
+        assert!(config.sub_activity_enabled);
+        assert_eq!(config.sub_activity_min_dwell_seconds, 30);
+
+        std::env::remove_var("TRACKEX_SUB_ACTIVITY");
+        std::env::remove_var("TRACKEX_SUB_ACTIVITY_DWELL");
+    }
 }
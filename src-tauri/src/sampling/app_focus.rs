@@ -5,12 +5,12 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 use std::sync::OnceLock;
 
-#[cfg(not(target_os = "windows"))]
+#[cfg(target_os = "macos")]
 use anyhow::Result;
 
 // use crate::storage::app_usage;
 
-use crate::commands::get_current_app;
+use crate::platform::FocusChanges;
 use crate::storage::app_usage;
 use crate::sampling::idle_detector;
 
@@ -70,131 +70,519 @@ pub struct AppInfo {
     pub window_title: Option<String>,
 }
 
-#[allow(dead_code)]
-pub async fn start_sampling(_app_handle: AppHandle) {
-    let interval_seconds = super::get_app_focus_interval();
+/// Foreground app detection for macOS and Windows. Linux uses
+/// `linux_focus::X11FocusProvider`.
+#[cfg(not(target_os = "linux"))]
+pub struct SystemFocus;
 
-    // Initialize app usage tracker
+#[cfg(not(target_os = "linux"))]
+impl crate::platform::FocusProvider for SystemFocus {
+    fn current_app(&self) -> crate::platform::PlatformFuture<'_, Option<AppInfo>> {
+        Box::pin(detect_foreground_app())
+    }
+}
+
+/// The app the user is working in: the focused app, or while TrackEx itself is
+/// focused, the last app before it. This ensures the UI always shows what the
+/// user is actually working on, even when viewing TrackEx. `None` when nothing
+/// the provider can see has focus (e.g. a native Wayland window under XWayland).
+pub async fn current_app() -> anyhow::Result<Option<AppInfo>> {
+    match crate::platform::current().focus.current_app().await? {
+        Some(app) if is_trackex_agent(&app.name, &app.app_id, app.window_title.as_deref()) => {
+            Ok(get_last_non_trackex_app().await)
+        }
+        Some(app) => {
+            set_last_non_trackex_app(app.clone()).await;
+            Ok(Some(app))
+        }
+        None => Ok(None),
+    }
+}
+
+// Helper function to check if an app is the TrackEx Agent itself
+pub fn is_trackex_agent(app_name: &str, app_id: &str, window_title: Option<&str>) -> bool {
+    let app_name_lower = app_name.to_lowercase();
+    let app_id_lower = app_id.to_lowercase();
     
-    // Wait a bit for database initialization to complete
-    tokio::time::sleep(Duration::from_secs(2)).await;
+    // IMPORTANT: Be very specific to avoid false positives
+    // (e.g., Cursor with "trackex-desktop-agent" folder open shouldn't match)
     
-    let mut interval = tokio::time::interval(Duration::from_secs(interval_seconds));
-    let mut last_app_info: Option<crate::sampling::app_focus::AppInfo> = None;
+    // Check app name - must be specifically "TrackEx Agent" or similar (not just containing the words)
+    if app_name_lower == "trackex agent" 
+        || app_name_lower == "trackex-agent" 
+        || app_name_lower == "trackex_agent" {
+        return true;
+    }
     
-    loop {
-        // Check if services should continue running (authenticated AND clocked in)
-        if !super::should_services_run().await {
-            // Stop if user is not authenticated or not clocked in
-            if !super::is_services_running().await {
-                break; // Service stopped completely
+    // Check app ID / bundle ID / executable name - must be the exact TrackEx executable
+    if app_id_lower == "trackex-agent.exe" 
+        || app_id_lower == "trackex_agent.exe"
+        || app_id_lower == "trackex-agent"
+        || app_id_lower == "trackex_agent"
+        || app_id_lower.starts_with("com.trackex.agent")
+        || app_id_lower.starts_with("com.nextup.trackex") {
+        return true;
+    }
+    
+    // Check window title ONLY if it's exactly "TrackEx Agent" or "TrackEx"
+    // Do NOT check if it contains these words (to avoid false positives from folder names)
+    if let Some(title) = window_title {
+        let title_lower = title.trim().to_lowercase();
+        if title_lower == "trackex agent" || title_lower == "trackex" {
+            return true;
+        }
+    }
+    
+    false
+}
+
+// Ask the OS which app is in front
+#[cfg(not(target_os = "linux"))]
+async fn detect_foreground_app() -> anyhow::Result<Option<AppInfo>> {
+    #[cfg(target_os = "macos")]
+    {
+        use std::process::Command;
+
+        // Primary: single AppleScript returning name and bundle id separated by ||
+        let script = r#"
+            tell application "System Events"
+                set p to first application process whose frontmost is true
+                set appName to name of p
+                try
+                    set bid to bundle identifier of p
+                on error
+                    set bid to ""
+                end try
+                return appName & "||" & bid
+            end tell
+        "#;
+        if let Ok(out) = Command::new("osascript").arg("-e").arg(script).output() {
+            let raw = String::from_utf8_lossy(&out.stdout).trim().to_string();
+            crate::utils::logging::log_remote_non_blocking(
+                "macos_app_detect_primary",
+                "debug",
+                "Primary AppleScript output",
+                Some(serde_json::json!({"raw": raw}))
+            ).await;
+            if !raw.is_empty() {
+                let parts: Vec<&str> = raw.split("||").collect();
+                let name = parts.get(0).unwrap_or(&"").trim();
+                let bundle_id = parts.get(1).unwrap_or(&"").trim();
+                if !name.is_empty() {
+                    let is_trackex = is_trackex_agent(name, bundle_id, None);
+                    crate::utils::logging::log_remote_non_blocking(
+                        "macos_app_detect_parsed",
+                        "debug",
+                        "Parsed macOS app",
+                        Some(serde_json::json!({"name": name, "bundle_id": bundle_id, "is_trackex": is_trackex}))
+                    ).await;
+                    return Ok(Some(AppInfo { name: name.to_string(), app_id: bundle_id.to_string(), window_title: Some("Active Window".to_string()) }));
+                }
             }
-            // Otherwise, just wait before checking again
-            interval.tick().await;
-            continue;
         }
 
-        if let Ok(app_info_opt) = get_current_app().await {
-                if let Some(app_info) = app_info_opt {
-                    // Check if app has changed
-                    let app_changed = last_app_info.as_ref().map_or(true, |last| {
-                        last.name != app_info.name || last.app_id != app_info.app_id
-                    });
-                    
-                    // Get idle status
-                    let idle_time = idle_detector::get_idle_time().await.unwrap_or(0);
-                    let idle_threshold = idle_detector::get_idle_threshold();
-                    let is_idle = idle_time >= idle_threshold;
+        // Fallback: separate queries
+        let app_name_result = Command::new("osascript")
+            .arg("-e")
+            .arg("tell application \"System Events\" to get name of first application process whose frontmost is true")
+            .output();
+        let bundle_id_result = Command::new("osascript")
+            .arg("-e")
+            .arg("tell application \"System Events\" to get bundle identifier of first application process whose frontmost is true")
+            .output();
+        if let (Ok(name_output), Ok(bundle_output)) = (app_name_result, bundle_id_result) {
+            let name = String::from_utf8_lossy(&name_output.stdout).trim().to_string();
+            let bundle_id = String::from_utf8_lossy(&bundle_output.stdout).trim().to_string();
+            crate::utils::logging::log_remote_non_blocking(
+                "macos_app_detect_fallback",
+                "debug",
+                "Fallback AppleScript result",
+                Some(serde_json::json!({"name": name, "bundle_id": bundle_id}))
+            ).await;
+            if !name.is_empty() {
+                return Ok(Some(AppInfo { name, app_id: bundle_id, window_title: Some("Active Window".to_string()) }));
+            }
+        }
+
+        // Final fallback
+        crate::utils::logging::log_remote_non_blocking(
+            "macos_app_detect_final_fallback",
+            "warn",
+            "No frontmost app detected (macOS)",
+            None
+        ).await;
+        return Ok(None);
+    }
+    
+    #[cfg(target_os = "windows")]
+    {
+        use crate::utils::windows_imports::*;
+        use windows::Win32::Foundation::HWND;
+
+        use sysinfo::{System};
+
+        use crate::sampling::app_focus::get_windows_process_name;
+        // Note: Shell API imports may not be available in this version
+        // We'll use a simpler approach without UWP app detection
+
+        unsafe {
+            // Get handle to the foreground window
+            let hwnd: HWND = GetForegroundWindow();
+            if hwnd.0 == std::ptr::null_mut() {
+                return Err(anyhow::anyhow!("No active window"));
+            }
+    
+            // Get window title
+            let mut title_buf = [0u16; 512];
+            let len = GetWindowTextW(hwnd, &mut title_buf);
+            let window_title = String::from_utf16_lossy(&title_buf[..len as usize]);
+            let window_title = trim_nulls(&window_title);
+    
+            // Get process ID for identification
+            let mut pid = 0u32;
+            GetWindowThreadProcessId(hwnd, Some(&mut pid));
+
+            if pid == 0 {
+                return Err(anyhow::anyhow!("Failed to get process ID")); // Could not get process ID
+            }
+
+            // First, try to detect if this is a UWP app by checking the window
+            let mut app_name = None;
+            let mut app_id = None;
+
+            if let Some(uwp_package) = crate::sampling::app_focus::get_uwp_app_from_window(hwnd) {
+                app_id = Some(uwp_package.clone());
+                
+                // Map package family name to friendly name
+                app_name = match uwp_package.as_str() {
+                    "Microsoft.WindowsCalculator_8wekyb3d8bbwe" => Some("Calculator".to_string()),
+                    "Microsoft.XboxGamingOverlay_8wekyb3d8bbwe" => Some("Xbox Game Bar".to_string()),
+                    "Microsoft.XboxApp_8wekyb3d8bbwe" => Some("Xbox".to_string()),
+                    "Microsoft.WindowsStore_8wekyb3d8bbwe" => Some("Microsoft Store".to_string()),
+                    "Microsoft.Windows.Settings_8wekyb3d8bbwe" => Some("Settings".to_string()),
+                    "Microsoft.Windows.ShellExperienceHost_cw5n1h2txyewy" => Some("Start Menu".to_string()),
+                    _ => Some(uwp_package), // Use package name as fallback
+                };
+            }
+
+            // If not UWP, use classic Win32 detection
+            if app_name.is_none() {
+                let mut sys = System::new_all();
+                sys.refresh_all(); // Refresh process information
+
+                if let Some(process) = sys.process(sysinfo::Pid::from_u32(pid)) {
+                    let pid = process.pid().as_u32();
                     
-                    if app_changed {
-                        log::info!("📱 App focus changed: {} ({})", app_info.name, app_info.app_id);
-                        
-                        // Trigger immediate heartbeat to reflect app change in real-time
-                        super::heartbeat::trigger_immediate_heartbeat().await;
-                        // Remote debug log
-                        crate::utils::logging::log_remote_non_blocking(
-                            "app_focus_change",
-                            "info",
-                            "Detected app change",
-                            Some(serde_json::json!({
-                                "name": app_info.name,
-                                "app_id": app_info.app_id,
-                                "window_title": app_info.window_title,
-                            }))
-                        ).await;
-                        
-                        // End previous session if it exists
-                        if let Err(e) = app_usage::end_current_session().await {
-                            log::warn!("Failed to end current app session: {}", e);
-                        }
-                        
-                        // Start new session (category classification handled by backend)
-                        if let Err(e) = app_usage::start_app_session(
-                            app_info.name.clone(),
-                            app_info.app_id.clone(),
-                            app_info.window_title.clone(),
-                            is_idle,
-                        ).await {
-                            log::error!("Failed to start new app session: {}", e);
-                        }
+                    // Try to get friendly name via Windows API first
+                    if let Some(name) = get_windows_process_name(pid) {
+                        app_name = Some(trim_nulls(&name));
+                        log::debug!("Got app name from get_windows_process_name: {}", name);
+                    } else {
+                        // Fallback: use sysinfo to get exe path and apply mapping
+                        log::debug!("get_windows_process_name returned None, using sysinfo fallback");
                         
-                        // Send app focus event ONLY when app changes
-                        let event_data = serde_json::json!({
-                            "app_name": app_info.name,
-                            "app_id": app_info.app_id,
-                            "window_title": app_info.window_title,
-                            "timestamp": chrono::Utc::now().to_rfc3339()
-                        });
-
-                        // Try to send immediately for real-time updates
-                        match crate::sampling::send_event_to_backend("app_focus", &event_data).await {
-                            Ok(_) => {
-                                log::info!("✓ App focus event sent: {}", app_info.name);
-                                crate::utils::logging::log_remote_non_blocking(
-                                    "app_focus_sent",
-                                    "info",
-                                    "App focus event sent",
-                                    Some(event_data.clone())
-                                ).await;
-                            }
-                            Err(e) => {
-                                // Only queue if immediate send fails (network issue, etc)
-                                log::warn!("Failed to send app focus event live, queuing: {}", e);
-                                if let Err(queue_err) = crate::storage::offline_queue::queue_event("app_focus", &event_data).await {
-                                    log::error!("CRITICAL: Failed to queue app focus event: {}", queue_err);
-                                    crate::utils::logging::log_remote_non_blocking(
-                                        "app_focus_queue_failed",
-                                        "error",
-                                        &format!("{}", queue_err),
-                                        Some(event_data.clone())
-                                    ).await;
-                                } else {
-                                    log::debug!("App focus event queued for later delivery");
-                                    crate::utils::logging::log_remote_non_blocking(
-                                        "app_focus_queued",
-                                        "warn",
-                                        "App focus queued for later delivery",
-                                        Some(event_data.clone())
-                                    ).await;
+                        // Try to get exe path from sysinfo
+                        if let Some(exe_path) = process.exe() {
+                            let exe_path_str = exe_path.to_string_lossy().to_string();
+                            log::debug!("Process exe path: {}", exe_path_str);
+                            
+                            // Apply the same mapping logic
+                            let exe_lower = exe_path_str.to_lowercase();
+                            
+                            // Check known app mappings (same as in app_focus.rs)
+                            if exe_lower.contains("cursor") {
+                                app_name = Some("Cursor".to_string());
+                            } else if exe_lower.contains("code.exe") || (exe_lower.contains("code") && exe_lower.contains("microsoft")) {
+                                app_name = Some("Visual Studio Code".to_string());
+                            } else if exe_lower.contains("chrome") && !exe_lower.contains("edge") {
+                                app_name = Some("Google Chrome".to_string());
+                            } else if exe_lower.contains("msedge") || (exe_lower.contains("edge") && !exe_lower.contains("edgeupdate")) {
+                                app_name = Some("Microsoft Edge".to_string());
+                            } else if exe_lower.contains("firefox") {
+                                app_name = Some("Mozilla Firefox".to_string());
+                            } else if exe_lower.contains("brave") {
+                                app_name = Some("Brave Browser".to_string());
+                            } else if exe_lower.contains("opera") {
+                                app_name = Some("Opera".to_string());
+                            } else if exe_lower.contains("explorer.exe") || exe_lower.ends_with("\\explorer.exe") {
+                                app_name = Some("File Explorer".to_string());
+                            } else if exe_lower.contains("notepad++") {
+                                app_name = Some("Notepad++".to_string());
+                            } else if exe_lower.contains("notepad.exe") && !exe_lower.contains("++") {
+                                app_name = Some("Notepad".to_string());
+                            } else if exe_lower.contains("devenv") {
+                                app_name = Some("Visual Studio".to_string());
+                            } else if exe_lower.contains("teams") {
+                                app_name = Some("Microsoft Teams".to_string());
+                            } else if exe_lower.contains("slack") {
+                                app_name = Some("Slack".to_string());
+                            } else if exe_lower.contains("discord") {
+                                app_name = Some("Discord".to_string());
+                            } else if exe_lower.contains("zoom") {
+                                app_name = Some("Zoom".to_string());
+                            } else if exe_lower.contains("spotify") {
+                                app_name = Some("Spotify".to_string());
+                            } else if exe_lower.contains("winword") {
+                                app_name = Some("Microsoft Word".to_string());
+                            } else if exe_lower.contains("excel") {
+                                app_name = Some("Microsoft Excel".to_string());
+                            } else if exe_lower.contains("powerpnt") {
+                                app_name = Some("Microsoft PowerPoint".to_string());
+                            } else if exe_lower.contains("outlook") {
+                                app_name = Some("Microsoft Outlook".to_string());
+                            } else {
+                                // Final fallback: clean filename
+                                if let Some(file_name) = exe_path.file_name() {
+                                    let name = file_name.to_string_lossy().to_string();
+                                    // Remove .exe extension
+                                    app_name = Some(if name.to_lowercase().ends_with(".exe") {
+                                        name[..name.len() - 4].to_string()
+                                    } else {
+                                        name
+                                    });
                                 }
                             }
                         }
                         
-                        last_app_info = Some(app_info.clone());
-                    } else {
-                        // App hasn't changed, just update current session's idle status
-                        if let Err(e) = app_usage::update_current_session(is_idle).await {
-                            log::warn!("Failed to update session idle status: {}", e);
+                        if app_name.is_none() {
+                            let proc_name = trim_nulls(process.name());
+                            log::debug!("Final fallback to process.name(): {}", proc_name);
+                            // Remove .exe extension if present
+                            app_name = Some(if proc_name.to_lowercase().ends_with(".exe") {
+                                proc_name[..proc_name.len() - 4].to_string()
+                            } else {
+                                proc_name
+                            });
                         }
                     }
                 } else {
-                    log::trace!("No app detected in current check");
+                    log::warn!("Could not find process with PID: {}", pid);
                 }
-        } else {
+                
+                // Get app ID using Windows-specific logic
+                app_id = crate::sampling::app_focus::get_windows_app_id(pid);
+            }
+            
+            let final_app_name = app_name.unwrap_or_else(|| {
+                log::warn!("No app name found, using Unknown");
+                "Unknown".to_string()
+            });
+            let final_app_id = app_id.unwrap_or_else(|| format!("pid_{}", pid));
+            
+            let app_info = AppInfo {
+                name: final_app_name.clone(),
+                app_id: final_app_id.clone(),
+                window_title: Some(window_title.clone()),
+            };
+            
+            log::debug!("App detection: name='{}', id='{}', title='{}'", 
+                final_app_name, final_app_id,
+                crate::policy::filter::filter_title(&crate::policy::toggles::get_current_policy(), &final_app_id, &window_title));
+            
+            Ok(Some(app_info))
+        }
+    }
+    
+    
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        // No backend for this platform
+        Ok(None)
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn trim_nulls(s: &str) -> String {
+    s.trim_end_matches('\u{0}').to_string()
+}
+
+/// One focus sample: opens a new app session and reports the switch when the
+/// focused app changed, otherwise refreshes the idle flag of the current session
+pub async fn sample_focus(last_app_info: &mut Option<AppInfo>) {
+    let app_info_opt = match current_app().await {
+        Ok(app_info_opt) => app_info_opt,
+        Err(_) => {
             log::trace!("Failed to get current app");
+            return;
         }
+    };
+
+    if let Some(app_info) = app_info_opt {
+        idle_detector::set_focused_app(&app_info.app_id, &app_info.name);
+
+        // Check if app has changed
+        let app_changed = last_app_info.as_ref().map_or(true, |last| {
+            last.name != app_info.name || last.app_id != app_info.app_id
+        });
+        
+        // Get idle status
+        let idle_time = idle_detector::get_idle_time().await.unwrap_or(0);
+        let idle_threshold = idle_detector::get_idle_threshold();
+        let is_idle = idle_time >= idle_threshold;
+        
+        if app_changed {
+            log::info!("📱 App focus changed: {} ({})", app_info.name, app_info.app_id);
+            
+            // Trigger immediate heartbeat to reflect app change in real-time
+            super::heartbeat::trigger_immediate_heartbeat().await;
+            // Remote debug log
+            crate::utils::logging::log_remote_non_blocking(
+                "app_focus_change",
+                "info",
+                "Detected app change",
+                Some(serde_json::json!({
+                    "name": app_info.name,
+                    "app_id": app_info.app_id,
+                    "window_title": app_info.window_title,
+                }))
+            ).await;
+            
+            // End previous session if it exists
+            if let Err(e) = app_usage::end_current_session().await {
+                log::warn!("Failed to end current app session: {}", e);
+            }
+            
+            // Start new session (category classification handled by backend)
+            if let Err(e) = app_usage::start_app_session(
+                app_info.name.clone(),
+                app_info.app_id.clone(),
+                app_info.window_title.clone(),
+                is_idle,
+            ).await {
+                log::error!("Failed to start new app session: {}", e);
+            }
+            
+            // Send app focus event ONLY when app changes
+            let occurred_at = chrono::Utc::now();
+            let event_data = serde_json::json!({
+                "app_name": app_info.name,
+                "app_id": app_info.app_id,
+                "window_title": app_info.window_title,
+                "timestamp": occurred_at.to_rfc3339()
+            });
+
+            // Try to send immediately for real-time updates
+            let event_id = crate::storage::offline_queue::new_event_id();
+            match crate::sampling::send_event_to_backend(&event_id, "app_focus", &event_data, occurred_at).await {
+                Ok(_) => {
+                    log::info!("✓ App focus event sent: {}", app_info.name);
+                    crate::utils::logging::log_remote_non_blocking(
+                        "app_focus_sent",
+                        "info",
+                        "App focus event sent",
+                        Some(event_data.clone())
+                    ).await;
+                }
+                Err(e) => {
+                    // Only queue if immediate send fails (network issue, etc)
+                    log::warn!("Failed to send app focus event live, queuing: {}", e);
+                    if let Err(queue_err) = crate::storage::offline_queue::queue_event_with_id(&event_id, "app_focus", &event_data, occurred_at).await {
+                        log::error!("CRITICAL: Failed to queue app focus event: {}", queue_err);
+                        crate::utils::logging::log_remote_non_blocking(
+                            "app_focus_queue_failed",
+                            "error",
+                            &format!("{}", queue_err),
+                            Some(event_data.clone())
+                        ).await;
+                    } else {
+                        log::debug!("App focus event queued for later delivery");
+                        crate::utils::logging::log_remote_non_blocking(
+                            "app_focus_queued",
+                            "warn",
+                            "App focus queued for later delivery",
+                            Some(event_data.clone())
+                        ).await;
+                    }
+                }
+            }
+            
+            *last_app_info = Some(app_info.clone());
+        } else {
+            // App hasn't changed, just update current session's idle status
+            if let Err(e) = app_usage::update_current_session(is_idle).await {
+                log::warn!("Failed to update session idle status: {}", e);
+            }
+            // A new tab or document starts a segment, if sub-activity tracking is on
+            app_usage::record_window_title(app_info.window_title.clone()).await;
+        }
+    } else if let Some(last) = last_app_info.take() {
+        // Nothing we can see has focus: stop crediting the last app
+        log::info!("📱 No app in focus, closing the {} session", last.name);
+        if let Err(e) = app_usage::end_current_session().await {
+            log::warn!("Failed to end current app session: {}", e);
+        }
+    } else {
+        log::trace!("No app detected in current check");
+    }
+}
+
+/// Wait until focus has been still for `quiet` after the latest event.
+/// Returns false if the event source went away.
+async fn settle(changes: &mut FocusChanges, quiet: Duration) -> bool {
+    loop {
+        match tokio::time::timeout(quiet, changes.changed()).await {
+            Ok(Ok(())) => continue,
+            Ok(Err(_)) => return false,
+            Err(_) => return true,
+        }
+    }
+}
 
+/// Wait until focus should be sampled again: once a focus event has settled,
+/// or at the next tick. Drops back to polling if the events stop.
+async fn wait_for_focus_change(changes: &mut Option<FocusChanges>, interval: &mut tokio::time::Interval) {
+    let Some(events) = changes.as_mut() else {
         interval.tick().await;
+        return;
+    };
+
+    tokio::select! {
+        changed = events.changed() => {
+            let quiet = Duration::from_millis(super::get_focus_debounce_millis());
+            if changed.is_err() || !settle(events, quiet).await {
+                log::warn!("Focus events stopped, polling every {}s instead", super::get_app_focus_interval());
+                *changes = None;
+                *interval = tokio::time::interval(Duration::from_secs(super::get_app_focus_interval()));
+            }
+        }
+        _ = interval.tick() => {}
+    }
+}
+
+pub async fn start_sampling(_app_handle: AppHandle) {
+    let interval_seconds = super::get_app_focus_interval();
+
+    // Initialize app usage tracker
+    
+    // Wait a bit for database initialization to complete
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    
+    // Follow focus events where the platform has them, otherwise poll
+    let mut changes = crate::platform::current().focus.focus_changes();
+    let mut interval = match changes {
+        Some(_) => {
+            log::info!("👀 Following focus events, resampling every {}s", super::get_focus_resync_interval());
+            tokio::time::interval(Duration::from_secs(super::get_focus_resync_interval()))
+        }
+        None => tokio::time::interval(Duration::from_secs(interval_seconds)),
+    };
+    let mut last_app_info: Option<AppInfo> = None;
+    
+    loop {
+        // Check if services should continue running (authenticated AND clocked in)
+        if !super::should_services_run().await {
+            // Stop if user is not authenticated or not clocked in
+            if !super::is_services_running().await {
+                break; // Service stopped completely
+            }
+            // Otherwise, just wait before checking again
+            tokio::time::sleep(Duration::from_secs(interval_seconds)).await;
+            continue;
+        }
+
+        sample_focus(&mut last_app_info).await;
+
+        wait_for_focus_change(&mut changes, &mut interval).await;
     }
 
     // End the last session when stopping
@@ -587,12 +975,43 @@ fn get_clean_filename(exe_path: &OsString) -> Option<String> {
     None
 }
 
-#[cfg(not(any(target_os = "macos", target_os = "windows")))]
-pub async fn get_current_app() -> Result<AppInfo> {
-    // Placeholder for other platforms
-    Ok(AppInfo {
-        name: "Unknown".to_string(),
-        app_id: "unknown.bundle.id".to_string(),
-        window_title: None,
-    })
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_focus_events_settle_before_sampling() {
+        let (sender, mut changes) = tokio::sync::watch::channel(());
+        let quiet = Duration::from_millis(100);
+        let burst = tokio::spawn(async move {
+            // Cycling through windows
+            for _ in 0..3 {
+                sender.send(()).unwrap();
+                tokio::time::sleep(Duration::from_millis(40)).await;
+            }
+            sender
+        });
+
+        let started = tokio::time::Instant::now();
+        changes.changed().await.unwrap();
+        assert!(settle(&mut changes, quiet).await);
+        // Only after the last event plus the quiet period
+        assert!(started.elapsed() >= Duration::from_millis(180));
+
+        drop(burst.await.unwrap());
+        assert!(!settle(&mut changes, quiet).await);
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_to_polling_when_events_stop() {
+        let (sender, receiver) = tokio::sync::watch::channel(());
+        let mut changes = Some(receiver);
+        let mut interval = tokio::time::interval(Duration::from_secs(3600));
+        interval.tick().await;
+
+        drop(sender);
+        wait_for_focus_change(&mut changes, &mut interval).await;
+        assert!(changes.is_none());
+        assert_eq!(interval.period(), Duration::from_secs(crate::sampling::get_app_focus_interval()));
+    }
 }
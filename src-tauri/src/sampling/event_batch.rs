@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+
+use crate::api::client::ApiClient;
+use crate::api::protocol::{EventBatch, EventBatchResponse, EventEnvelope, EventResult};
+use crate::api::error::FailureKind;
+use crate::storage::offline_queue::{FailedItem, QueuedEvent};
+
+// Upper bounds for a single /api/ingest/events request
+pub const MAX_BATCH_EVENTS: usize = 100;
+pub const MAX_BATCH_BYTES: usize = 256 * 1024;
+
+/// Per-item outcome of one batch upload, keyed by outbox row id
+#[derive(Debug, Default)]
+pub struct BatchResult {
+    pub accepted: Vec<i64>,
+    pub failed: Vec<FailedItem>,
+}
+
+/// Take events from the front of `events` until either limit is reached.
+/// The first event is always included so an oversized event can't block the queue.
+pub fn pack_batch(events: &[QueuedEvent], max_events: usize, max_bytes: usize) -> (Vec<EventEnvelope>, usize) {
+    let mut payload = Vec::new();
+    let mut bytes = 0;
+
+    for event in events.iter().take(max_events) {
+        let item = EventEnvelope::new(&event.event_id, &event.event_type, event.event_data.clone(), event.timestamp)
+            .sent_from("send_event_batch");
+        let size = serde_json::to_vec(&item).map(|v| v.len()).unwrap_or(0);
+
+        if !payload.is_empty() && bytes + size > max_bytes {
+            break;
+        }
+        bytes += size;
+        payload.push(item);
+    }
+
+    let count = payload.len();
+    (payload, count)
+}
+
+/// Work out which events the server acknowledged.
+/// A plain 2xx without per-item `results` acknowledges the whole batch; with `results`,
+/// only items reported as accepted (or already seen) count as delivered, and
+/// `rejected`/`invalid` items are treated as permanent failures.
+pub fn parse_batch_response(body: &EventBatchResponse, sent: &[&QueuedEvent]) -> BatchResult {
+    let mut result = BatchResult::default();
+
+    let Some(results) = &body.results else {
+        result.accepted = sent.iter().map(|e| e.id).collect();
+        return result;
+    };
+
+    let statuses: HashMap<&str, &EventResult> = results.iter().map(|r| (r.id.as_str(), r)).collect();
+
+    for event in sent {
+        let Some(item) = statuses.get(event.event_id.as_str()) else {
+            // Not mentioned by the server - not acknowledged, so retry it later
+            result.failed.push(FailedItem {
+                id: event.id,
+                kind: FailureKind::Transient,
+                error: "not acknowledged by server".to_string(),
+            });
+            continue;
+        };
+
+        let error = item.error.clone().unwrap_or_else(|| item.status.clone());
+        match item.status.as_str() {
+            "accepted" | "ok" | "duplicate" => result.accepted.push(event.id),
+            "rejected" | "invalid" => {
+                log::warn!("Server rejected {} event {}: {}", event.event_type, event.event_id, error);
+                result.failed.push(FailedItem { id: event.id, kind: FailureKind::Permanent, error });
+            }
+            _ => result.failed.push(FailedItem { id: event.id, kind: FailureKind::Transient, error }),
+        }
+    }
+
+    result
+}
+
+/// Upload up to `MAX_BATCH_EVENTS` / `MAX_BATCH_BYTES` of the given events in one request.
+/// Returns an error only when nothing was acknowledged (network failure, non-2xx status).
+pub async fn send_event_batch(events: &[QueuedEvent]) -> anyhow::Result<BatchResult> {
+    let (payload, count) = pack_batch(events, MAX_BATCH_EVENTS, MAX_BATCH_BYTES);
+    let sent: Vec<&QueuedEvent> = events.iter().take(count).collect();
+    if sent.is_empty() {
+        return Ok(BatchResult::default());
+    }
+
+    let client = ApiClient::new().await?;
+    let mut request = client.post("/api/ingest/events", &EventBatch { events: payload });
+    log::info!("🔗 Sending batch of {} events to: {}", count, request.url());
+
+    // Multi-event batches are deduplicated by the per-event ids in the body
+    if let [only] = sent.as_slice() {
+        request = request.idempotency_key(&only.event_id);
+    }
+
+    let response = request.send_checked().await?;
+    // A 2xx with an unreadable body still acknowledges the batch
+    let body: EventBatchResponse = response.json().await.unwrap_or_default();
+    Ok(parse_batch_response(&body, &sent))
+}
+
+/// Fallback when the server refuses a whole batch, so only the offending events get dead-lettered
+pub async fn send_individually(events: &[QueuedEvent]) -> BatchResult {
+    let mut result = BatchResult::default();
+    for event in events {
+        match super::send_event_to_backend(&event.event_id, &event.event_type, &event.event_data, event.timestamp).await {
+            Ok(_) => result.accepted.push(event.id),
+            Err(e) => result.failed.push(FailedItem::from_error(event.id, &e)),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use serde_json::{json, Value};
+
+    fn event(id: i64, data: Value) -> QueuedEvent {
+        QueuedEvent {
+            id,
+            event_id: format!("evt-{}", id),
+            event_type: "app_focus".to_string(),
+            event_data: data,
+            timestamp: Utc::now(),
+            retry_count: 0,
+            max_retries: 3,
+        }
+    }
+
+    #[test]
+    fn test_pack_batch_respects_item_limit() {
+        let events: Vec<_> = (1..=5).map(|i| event(i, json!({}))).collect();
+        let (payload, count) = pack_batch(&events, 3, usize::MAX);
+        assert_eq!(count, 3);
+        assert_eq!(payload[0].id, "evt-1");
+    }
+
+    #[test]
+    fn test_pack_batch_keeps_original_occurred_at() {
+        let mut queued = event(1, json!({}));
+        queued.timestamp = Utc::now() - chrono::Duration::hours(3);
+        let (payload, _) = pack_batch(&[queued], 10, usize::MAX);
+
+        assert_eq!(payload[0].timestamp, payload[0].occurred_at);
+        assert!(payload[0].occurred_at < payload[0].sent_at);
+    }
+
+    #[test]
+    fn test_pack_batch_respects_byte_budget() {
+        let big = json!({"title": "x".repeat(1000)});
+        let events: Vec<_> = (1..=5).map(|i| event(i, big.clone())).collect();
+        let (_, count) = pack_batch(&events, 100, 2500);
+        assert_eq!(count, 2);
+
+        // An oversized event still goes out on its own
+        let (_, count) = pack_batch(&events, 100, 10);
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_parse_batch_response_without_results_accepts_all() {
+        let events = [event(1, json!({})), event(2, json!({}))];
+        let sent: Vec<_> = events.iter().collect();
+        let body = serde_json::from_value(json!({"success": true})).unwrap();
+        let result = parse_batch_response(&body, &sent);
+        assert_eq!(result.accepted, vec![1, 2]);
+        assert!(result.failed.is_empty());
+    }
+
+    #[test]
+    fn test_parse_batch_response_partial() {
+        let events = [event(1, json!({})), event(2, json!({})), event(3, json!({}))];
+        let sent: Vec<_> = events.iter().collect();
+        let body = serde_json::from_value(json!({"results": [
+            {"id": "evt-1", "status": "accepted"},
+            {"id": "evt-2", "status": "rejected"},
+        ]})).unwrap();
+        let result = parse_batch_response(&body, &sent);
+        assert_eq!(result.accepted, vec![1]);
+        assert_eq!(result.failed.len(), 2);
+        assert_eq!((result.failed[0].id, result.failed[0].kind), (2, FailureKind::Permanent));
+        assert_eq!((result.failed[1].id, result.failed[1].kind), (3, FailureKind::Transient));
+    }
+}
@@ -1,10 +1,10 @@
 use tauri::AppHandle;
 use tokio::time::Duration;
-use serde_json::json;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use std::sync::OnceLock;
 
+use crate::api::protocol::{wire_timestamp, Heartbeat, HeartbeatApp};
 use crate::sampling::{idle_detector};
 use crate::storage::{work_session, offline_queue};
 
@@ -169,25 +169,27 @@ async fn send_heartbeat() -> anyhow::Result<()> {
     // WORKAROUND: Always send status="active" to keep user in "Online Now" count
     // Backend should ideally treat both 'active' and 'idle' as online, but until then,
     // we send status="active" and let the backend/frontend use idle_time_seconds to show idle state
-    let heartbeat_data = json!({
-        "timestamp": now.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
-        "status": "active",  // Always "active" to stay in Online count (workaround)
-        "idle_time_seconds": idle_time,  // Backend can use this to determine if user is idle
-        "is_idle": is_idle,  // Explicit idle flag for future use
-        "currentApp": current_app.as_ref().map(|app| json!({
-            "name": app.name,
-            "app_id": app.app_id,
-            "window_title": app.window_title
-        })),
-        "session_start_time": session_start.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
-        "total_session_time_seconds": total_session_time,
-        "active_time_today_seconds": total_active_today,
-        "idle_time_today_seconds": total_idle_today,
-        "is_paused": super::is_services_paused().await
-    });
+    let heartbeat = Heartbeat {
+        timestamp: wire_timestamp(now),
+        status: "active".to_string(),  // Always "active" to stay in Online count (workaround)
+        idle_time_seconds: idle_time,  // Backend can use this to determine if user is idle
+        is_idle: Some(is_idle),  // Explicit idle flag for future use
+        current_app: current_app.map(|app| HeartbeatApp {
+            name: app.name,
+            app_id: app.app_id,
+            window_title: app.window_title,
+        }),
+        session_start_time: wire_timestamp(session_start),
+        total_session_time_seconds: total_session_time,
+        active_time_today_seconds: total_active_today,
+        idle_time_today_seconds: total_idle_today,
+        is_paused: super::is_services_paused().await,
+    };
+    let heartbeat_data = serde_json::to_value(&heartbeat)?;
 
     // Try to send heartbeat live first, fallback to queue if failed
-    match super::send_heartbeat_to_backend(&heartbeat_data).await {
+    let event_id = offline_queue::new_event_id();
+    match super::send_heartbeat_to_backend(&event_id, &heartbeat_data, now).await {
         Ok(_) => {
             log::info!("✓ Heartbeat sent (status=active, idle_time={}s, user_is_idle={})", 
                 idle_time, is_idle);
@@ -196,7 +198,7 @@ async fn send_heartbeat() -> anyhow::Result<()> {
         Err(e) => {
             log::warn!("Failed to send heartbeat live, queuing for later: {}", e);
             // Queue heartbeat for offline processing
-            match offline_queue::queue_heartbeat(&heartbeat_data).await {
+            match offline_queue::queue_heartbeat_with_id(&event_id, &heartbeat_data, now).await {
                 Ok(_) => {
                     log::debug!("Heartbeat queued for later delivery");
                     Ok(())
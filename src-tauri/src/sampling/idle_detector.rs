@@ -10,9 +10,22 @@ use winapi::{
     um::sysinfoapi::GetTickCount,
 };
 
-#[cfg(target_os = "macos")]
-#[allow(dead_code)]
+/// Idle time as the OS reports it
+pub struct SystemIdle;
+
+impl crate::platform::IdleProvider for SystemIdle {
+    fn idle_seconds(&self) -> crate::platform::PlatformFuture<'_, u64> {
+        Box::pin(native_idle_time())
+    }
+}
+
+/// Seconds since the user's last input, from the current platform
 pub async fn get_idle_time() -> Result<u64> {
+    crate::platform::current().idle.idle_seconds().await
+}
+
+#[cfg(target_os = "macos")]
+async fn native_idle_time() -> Result<u64> {
     use std::process::Command;
     
     // Use ioreg to get idle time on macOS
@@ -71,8 +84,7 @@ pub async fn get_idle_time() -> Result<u64> {
 }
 
 #[cfg(target_os = "windows")]
-#[allow(dead_code)]
-pub async fn get_idle_time() -> Result<u64> {
+async fn native_idle_time() -> Result<u64> {
     use std::mem;
     
     unsafe {
@@ -122,7 +134,6 @@ pub async fn is_system_idle(threshold_seconds: u64) -> Result<bool> {
     Ok(idle_time >= threshold_seconds)
 }
 
-#[cfg(any(target_os = "windows", target_os = "macos"))]
 #[allow(dead_code)]
 pub async fn get_detailed_idle_info() -> Result<IdleInfo> {
     let idle_time = get_idle_time().await?;
@@ -145,8 +156,20 @@ pub struct IdleInfo {
     pub last_activity_time: chrono::DateTime<chrono::Utc>,
 }
 
-#[cfg(not(any(target_os = "macos", target_os = "windows")))]
-pub async fn get_idle_time() -> Result<u64> {
+#[cfg(target_os = "linux")]
+async fn native_idle_time() -> Result<u64> {
+    let state = super::linux_idle::idle_state().await?;
+    log::trace!("Linux idle time: {}s, screen locked: {}", state.idle_seconds, state.locked);
+
+    // A locked screen means the user is away, however recent the last input
+    if state.locked {
+        return Ok(state.idle_seconds.max(get_idle_threshold()));
+    }
+    Ok(state.idle_seconds)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+async fn native_idle_time() -> Result<u64> {
     // Placeholder for other platforms
     Ok(0)
 }
@@ -157,11 +180,50 @@ pub async fn is_user_idle(threshold_seconds: u64) -> Result<bool> {
     Ok(idle_time >= threshold_seconds)
 }
 
-#[allow(dead_code)]
+// App in the foreground as last seen by the app focus sampler, as (app_id, name).
+// Per-app idle overrides are matched against it.
+static FOCUSED_APP: std::sync::Mutex<Option<(String, String)>> = std::sync::Mutex::new(None);
+
+/// Record the focused app so idle checks apply its threshold
+pub fn set_focused_app(app_id: &str, app_name: &str) {
+    if let Ok(mut focused) = FOCUSED_APP.lock() {
+        *focused = Some((app_id.to_string(), app_name.to_string()));
+    }
+}
+
+/// The one idle threshold every idle check uses: the active policy's, or its
+/// override for the focused app. Follows policy changes at runtime.
 pub fn get_idle_threshold() -> u64 {
-    // Default idle threshold: 5 minutes (300 seconds)
-    std::env::var("TRACKEX_IDLE_THRESHOLD")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(120)
+    let policy = crate::policy::toggles::get_current_policy();
+    match FOCUSED_APP.lock().ok().and_then(|focused| focused.clone()) {
+        Some((app_id, app_name)) => policy.idle_threshold_for(&app_id, &app_name),
+        None => policy.idle_threshold_seconds,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::toggles::{self, IdleOverride, PolicyConfig};
+
+    #[tokio::test]
+    async fn test_idle_threshold_follows_focused_app_and_policy() {
+        let policy = PolicyConfig {
+            idle_threshold_seconds: 300,
+            idle_overrides: vec![IdleOverride { app: "zoom".to_string(), idle_threshold_seconds: 1800 }],
+            ..PolicyConfig::default()
+        };
+        let _policy = toggles::test_policy(policy.clone()).await;
+
+        set_focused_app("org.example.Editor", "Editor");
+        assert_eq!(get_idle_threshold(), 300);
+
+        // The override applies as soon as the sampler reports the new app
+        set_focused_app("us.zoom.xos", "zoom.us");
+        assert_eq!(get_idle_threshold(), 1800);
+
+        // And a new policy takes effect without restarting anything
+        toggles::update_policy(PolicyConfig { idle_overrides: Vec::new(), ..policy });
+        assert_eq!(get_idle_threshold(), 300);
+    }
 }
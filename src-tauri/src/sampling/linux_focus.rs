@@ -0,0 +1,534 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::errors::ReplyError;
+use x11rb::properties::WmClass;
+use x11rb::protocol::xproto::{AtomEnum, ChangeWindowAttributesAux, ConnectionExt, EventMask, Window};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+
+use super::app_focus::AppInfo;
+use crate::platform::{FocusChanges, FocusProvider, PlatformFuture};
+
+// Foreground app on Linux under X11 (including XWayland). The window manager
+// publishes the focused window as `_NET_ACTIVE_WINDOW` on the root window
+// (EWMH); its `_NET_WM_PID` leads to the executable in /proc and its
+// `WM_CLASS` to the app's .desktop file, which supplies the name users know.
+// Changes to `_NET_ACTIVE_WINDOW` arrive as PropertyNotify events on the root
+// window, and title changes as PropertyNotify events on the focused window, so
+// both are seen as they happen rather than polled.
+
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        _NET_ACTIVE_WINDOW,
+        _NET_WM_PID,
+        _NET_WM_NAME,
+        UTF8_STRING,
+    }
+}
+
+// Longest window title read, in 32-bit units
+const MAX_TITLE_LEN: u32 = 1024;
+
+/// What X11 tells us about the focused window
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WindowProps {
+    pub pid: Option<u32>,
+    pub wm_instance: Option<String>,
+    pub wm_class: Option<String>,
+    pub title: Option<String>,
+}
+
+struct X11Session {
+    conn: RustConnection,
+    root: Window,
+    atoms: Atoms,
+}
+
+impl X11Session {
+    fn connect(display: Option<&str>) -> Result<Self> {
+        let (conn, screen) = x11rb::connect(display)?;
+        let root = conn.setup().roots[screen].root;
+        let atoms = Atoms::new(&conn)?.reply()?;
+        Ok(Self { conn, root, atoms })
+    }
+
+    /// Send a nudge on `changes` whenever `_NET_ACTIVE_WINDOW` or the focused
+    /// window's title changes. Blocking; returns once nobody is listening or the
+    /// connection fails.
+    fn forward_focus_changes(&self, changes: &tokio::sync::watch::Sender<()>) -> Result<()> {
+        let mut focused = self.follow_titles(x11rb::NONE, self.active_window_id()?)?;
+        loop {
+            // Errors from windows closing under us also arrive here and are skipped
+            let Event::PropertyNotify(event) = self.conn.wait_for_event()? else {
+                continue;
+            };
+            let changed = if event.window == self.root && event.atom == self.atoms._NET_ACTIVE_WINDOW {
+                focused = self.follow_titles(focused, self.active_window_id()?)?;
+                true
+            } else {
+                event.window == focused && (event.atom == self.atoms._NET_WM_NAME || event.atom == u32::from(AtomEnum::WM_NAME))
+            };
+            if changed && changes.send(()).is_err() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Move the property watch from the previously focused window to `window`
+    fn follow_titles(&self, previous: Window, window: Window) -> Result<Window> {
+        if previous == window {
+            return Ok(window);
+        }
+        if previous != x11rb::NONE && previous != self.root {
+            self.conn.change_window_attributes(previous, &ChangeWindowAttributesAux::new().event_mask(EventMask::NO_EVENT))?;
+        }
+        if window != x11rb::NONE && window != self.root {
+            self.conn.change_window_attributes(window, &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE))?;
+        }
+        self.conn.flush()?;
+        Ok(window)
+    }
+
+    fn active_window_id(&self) -> Result<Window, ReplyError> {
+        let active = self
+            .conn
+            .get_property(false, self.root, self.atoms._NET_ACTIVE_WINDOW, AtomEnum::WINDOW, 0, 1)?
+            .reply()?;
+        Ok(active.value32().and_then(|mut values| values.next()).unwrap_or(x11rb::NONE))
+    }
+
+    fn string_property(&self, window: Window, property: u32, kind: u32) -> Result<Option<String>, ReplyError> {
+        let reply = self.conn.get_property(false, window, property, kind, 0, MAX_TITLE_LEN)?.reply()?;
+        Ok((!reply.value.is_empty()).then(|| String::from_utf8_lossy(&reply.value).into_owned()))
+    }
+
+    fn active_window(&self) -> Result<Option<WindowProps>, ReplyError> {
+        let window = match self.active_window_id()? {
+            x11rb::NONE => return Ok(None),
+            window => window,
+        };
+
+        let pid = self
+            .conn
+            .get_property(false, window, self.atoms._NET_WM_PID, AtomEnum::CARDINAL, 0, 1)?
+            .reply()?
+            .value32()
+            .and_then(|mut values| values.next());
+
+        let class = WmClass::get(&self.conn, window)?.reply()?;
+        let text = |bytes: &[u8]| (!bytes.is_empty()).then(|| String::from_utf8_lossy(bytes).into_owned());
+
+        // EWMH title first; legacy clients only set WM_NAME
+        let title = match self.string_property(window, self.atoms._NET_WM_NAME, self.atoms.UTF8_STRING)? {
+            Some(title) => Some(title),
+            None => self.string_property(window, AtomEnum::WM_NAME.into(), AtomEnum::ANY.into())?,
+        };
+
+        Ok(Some(WindowProps {
+            pid,
+            wm_instance: class.as_ref().and_then(|class| text(class.instance())),
+            wm_class: class.as_ref().and_then(|class| text(class.class())),
+            title,
+        }))
+    }
+}
+
+/// An installed application, from its .desktop file
+#[derive(Debug, Clone, PartialEq)]
+pub struct DesktopEntry {
+    /// Desktop file ID, e.g. `firefox` or `org.gnome.Nautilus`
+    pub id: String,
+    pub name: String,
+    pub exec: Option<String>,
+    pub startup_wm_class: Option<String>,
+}
+
+/// Parse the `[Desktop Entry]` group of a .desktop file. Only applications qualify.
+pub fn parse_desktop_entry(id: &str, contents: &str) -> Option<DesktopEntry> {
+    let mut in_entry = false;
+    let mut fields: HashMap<&str, &str> = HashMap::new();
+
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+        } else if let (true, Some((key, value))) = (in_entry, line.split_once('=')) {
+            // Unlocalised keys only; `Name[de]` and friends are skipped
+            fields.entry(key.trim()).or_insert(value.trim());
+        }
+    }
+
+    if fields.get("Type") != Some(&"Application") {
+        return None;
+    }
+    Some(DesktopEntry {
+        id: id.to_string(),
+        name: fields.get("Name")?.to_string(),
+        exec: fields.get("Exec").map(|exec| exec.to_string()),
+        startup_wm_class: fields.get("StartupWMClass").map(|class| class.to_string()),
+    })
+}
+
+/// `applications` directories in XDG order, user's own first
+fn desktop_dirs() -> Vec<PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME").map(PathBuf::from).or_else(dirs::data_dir);
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+
+    data_home
+        .into_iter()
+        .chain(std::env::split_paths(&data_dirs))
+        .map(|dir| dir.join("applications"))
+        .collect()
+}
+
+fn collect_desktop_files(dir: &Path, prefix: &str, entries: &mut Vec<DesktopEntry>) {
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for path in read_dir.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if path.is_dir() {
+            // applications/kde4/foo.desktop has the ID kde4-foo
+            collect_desktop_files(&path, &format!("{}{}-", prefix, file_name), entries);
+        } else if let Some(stem) = file_name.strip_suffix(".desktop") {
+            let id = format!("{}{}", prefix, stem);
+            // Earlier directories take precedence for the same ID
+            if entries.iter().any(|entry| entry.id == id) {
+                continue;
+            }
+            if let Some(entry) = std::fs::read_to_string(&path).ok().and_then(|contents| parse_desktop_entry(&id, &contents)) {
+                entries.push(entry);
+            }
+        }
+    }
+}
+
+pub fn load_desktop_entries(dirs: &[PathBuf]) -> Vec<DesktopEntry> {
+    let mut entries = Vec::new();
+    for dir in dirs {
+        collect_desktop_files(dir, "", &mut entries);
+    }
+    entries
+}
+
+/// File name of the program an `Exec` line starts, skipping an `env VAR=value` prefix
+fn exec_program(exec: &str) -> Option<&str> {
+    exec.split_whitespace()
+        .find(|token| *token != "env" && !token.contains('='))
+        .map(|program| program.trim_matches('"').rsplit('/').next().unwrap_or(program))
+}
+
+/// Executable behind `pid`
+pub fn process_exe(pid: u32) -> Option<PathBuf> {
+    let exe = std::fs::read_link(format!("/proc/{}/exe", pid)).ok()?;
+    // An upgraded package leaves the running binary marked as deleted
+    let exe = exe.to_string_lossy();
+    Some(PathBuf::from(exe.strip_suffix(" (deleted)").unwrap_or(&exe)))
+}
+
+/// Name the app owning `window`: its .desktop entry if one matches the window
+/// class or the executable, otherwise whatever the window and process tell us
+pub fn resolve_app(window: &WindowProps, exe: Option<&Path>, entries: &[DesktopEntry]) -> AppInfo {
+    let exe_name = exe.and_then(|exe| exe.file_name()).map(|name| name.to_string_lossy().into_owned());
+    let classes: Vec<&str> = [&window.wm_class, &window.wm_instance].into_iter().flatten().map(String::as_str).collect();
+
+    let by_class = |entry: &&DesktopEntry| {
+        classes.iter().any(|class| {
+            entry.startup_wm_class.as_deref().is_some_and(|wm_class| wm_class.eq_ignore_ascii_case(class))
+                || entry.id.eq_ignore_ascii_case(class)
+        })
+    };
+    let by_exe = |entry: &&DesktopEntry| {
+        exe_name.as_deref().is_some_and(|exe_name| entry.exec.as_deref().and_then(exec_program) == Some(exe_name))
+    };
+
+    match entries.iter().find(by_class).or_else(|| entries.iter().find(by_exe)) {
+        Some(entry) => AppInfo {
+            name: entry.name.clone(),
+            app_id: entry.id.clone(),
+            window_title: window.title.clone(),
+        },
+        None => AppInfo {
+            name: window.wm_class.clone().or_else(|| exe_name.clone()).unwrap_or_else(|| "Unknown".to_string()),
+            app_id: exe_name.or_else(|| window.wm_class.as_ref().map(|class| class.to_lowercase())).unwrap_or_else(|| "unknown".to_string()),
+            window_title: window.title.clone(),
+        },
+    }
+}
+
+// Window class and executable, and the (name, app_id) they resolved to
+type AppKey = (Option<String>, Option<PathBuf>);
+type ResolvedApp = (String, String);
+
+// Clones share the connection and the resolved apps
+#[derive(Clone)]
+pub struct X11FocusProvider {
+    display: Option<String>,
+    session: Arc<Mutex<Option<X11Session>>>,
+    // The .desktop files are only searched the first time an app is seen
+    resolved: Arc<Mutex<HashMap<AppKey, ResolvedApp>>>,
+    // One event thread, shared by every sampler that asks for focus changes
+    changes: Arc<Mutex<Option<FocusChanges>>>,
+}
+
+impl X11FocusProvider {
+    /// Provider for `display`, or `$DISPLAY` when `None`. Connects on first use.
+    pub fn new(display: Option<&str>) -> Self {
+        Self {
+            display: display.map(str::to_string),
+            session: Arc::new(Mutex::new(None)),
+            resolved: Arc::new(Mutex::new(HashMap::new())),
+            changes: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Start a thread that nudges the returned receiver on every focus or
+    /// title change. It runs until its connection fails or every receiver,
+    /// including the one kept in `changes`, is gone.
+    fn watch_focus(&self) -> Option<FocusChanges> {
+        // A connection of its own, as it spends its life waiting for events
+        let watch = X11Session::connect(self.display.as_deref()).and_then(|session| {
+            let events = ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE);
+            session.conn.change_window_attributes(session.root, &events)?.check()?;
+            Ok(session)
+        });
+        let session = match watch {
+            Ok(session) => session,
+            Err(e) => {
+                log::warn!("X11 focus events unavailable, polling instead: {}", e);
+                return None;
+            }
+        };
+
+        let (sender, receiver) = tokio::sync::watch::channel(());
+        let spawned = std::thread::Builder::new().name("x11-focus-events".to_string()).spawn(move || {
+            if let Err(e) = session.forward_focus_changes(&sender) {
+                log::warn!("X11 focus events stopped: {}", e);
+            }
+        });
+        if let Err(e) = spawned {
+            log::warn!("Failed to start X11 focus event thread: {}", e);
+            return None;
+        }
+        Some(receiver)
+    }
+
+    fn active_window(&self) -> Result<Option<WindowProps>> {
+        let mut session = self.session.lock().map_err(|_| anyhow::anyhow!("X11 session lock poisoned"))?;
+        let current = match session.take() {
+            Some(current) => current,
+            None => X11Session::connect(self.display.as_deref())?,
+        };
+
+        match current.active_window() {
+            Ok(window) => {
+                *session = Some(current);
+                Ok(window)
+            }
+            // The window closed while we were reading it
+            Err(ReplyError::X11Error(e)) => {
+                log::debug!("Focused window went away: {:?}", e.error_kind);
+                *session = Some(current);
+                Ok(None)
+            }
+            // Dropping the session makes the next call reconnect
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The app owning the focused window. Blocking.
+    pub fn focused_app(&self) -> Result<Option<AppInfo>> {
+        let Some(window) = self.active_window()? else {
+            return Ok(None);
+        };
+        let exe = window.pid.and_then(process_exe);
+
+        let key = (window.wm_class.clone(), exe.clone());
+        if let Some((name, app_id)) = self.resolved.lock().ok().and_then(|resolved| resolved.get(&key).cloned()) {
+            return Ok(Some(AppInfo { name, app_id, window_title: window.title }));
+        }
+
+        let app = resolve_app(&window, exe.as_deref(), &load_desktop_entries(&desktop_dirs()));
+        if let Ok(mut resolved) = self.resolved.lock() {
+            resolved.insert(key, (app.name.clone(), app.app_id.clone()));
+        }
+        Ok(Some(app))
+    }
+}
+
+impl FocusProvider for X11FocusProvider {
+    fn current_app(&self) -> PlatformFuture<'_, Option<AppInfo>> {
+        let provider = self.clone();
+        Box::pin(async move { tokio::task::spawn_blocking(move || provider.focused_app()).await? })
+    }
+
+    fn focus_changes(&self) -> Option<FocusChanges> {
+        let mut changes = self.changes.lock().ok()?;
+        // Reuse the running thread; start a new one only once it has stopped
+        if let Some(running) = changes.as_ref().filter(|running| running.has_changed().is_ok()) {
+            return Some(running.clone());
+        }
+
+        let receiver = self.watch_focus()?;
+        *changes = Some(receiver.clone());
+        Some(receiver)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x11rb::protocol::xproto::{CreateWindowAux, PropMode, WindowClass};
+    use x11rb::wrapper::ConnectionExt as _;
+
+    // The X11 tests publish _NET_ACTIVE_WINDOW on the one shared display, so
+    // they take turns
+    static X11_TEST: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    fn entry(id: &str, name: &str, exec: &str, wm_class: Option<&str>) -> DesktopEntry {
+        DesktopEntry {
+            id: id.to_string(),
+            name: name.to_string(),
+            exec: Some(exec.to_string()),
+            startup_wm_class: wm_class.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_parses_only_the_desktop_entry_group() {
+        let contents = "[Desktop Entry]\nType=Application\nName=Firefox Web Browser\nName[de]=Firefox-Webbrowser\n\
+                        Exec=firefox %u\nStartupWMClass=firefox\n\n[Desktop Action new-window]\nName=New Window\nExec=firefox --new-window %u\n";
+        let parsed = parse_desktop_entry("firefox", contents).unwrap();
+        assert_eq!(parsed, entry("firefox", "Firefox Web Browser", "firefox %u", Some("firefox")));
+
+        assert!(parse_desktop_entry("link", "[Desktop Entry]\nType=Link\nName=Docs\nURL=https://example.com\n").is_none());
+    }
+
+    #[test]
+    fn test_desktop_files_are_found_by_id_with_user_dirs_first() {
+        let user = tempfile::tempdir().unwrap();
+        let system = tempfile::tempdir().unwrap();
+        std::fs::create_dir(system.path().join("kde4")).unwrap();
+        std::fs::write(user.path().join("code.desktop"), "[Desktop Entry]\nType=Application\nName=Code (mine)\nExec=code\n").unwrap();
+        std::fs::write(system.path().join("code.desktop"), "[Desktop Entry]\nType=Application\nName=Visual Studio Code\nExec=code\n").unwrap();
+        std::fs::write(system.path().join("kde4/kate.desktop"), "[Desktop Entry]\nType=Application\nName=Kate\nExec=kate\n").unwrap();
+
+        let entries = load_desktop_entries(&[user.path().to_path_buf(), system.path().to_path_buf()]);
+        let names: HashMap<_, _> = entries.iter().map(|entry| (entry.id.as_str(), entry.name.as_str())).collect();
+        assert_eq!(names.len(), 2);
+        assert_eq!(names["code"], "Code (mine)");
+        assert_eq!(names["kde4-kate"], "Kate");
+    }
+
+    #[test]
+    fn test_resolves_by_window_class_then_executable() {
+        let entries = vec![
+            entry("google-chrome", "Google Chrome", "/usr/bin/google-chrome-stable %U", Some("Google-chrome")),
+            entry("org.gnome.Nautilus", "Files", "nautilus --new-window %U", None),
+            entry("code", "Visual Studio Code", "env ELECTRON_NO_ATTACH=1 /usr/share/code/code --unity-launch %F", None),
+        ];
+        let window = |class: &str| WindowProps {
+            wm_class: Some(class.to_string()),
+            title: Some("Title".to_string()),
+            ..WindowProps::default()
+        };
+
+        let chrome = resolve_app(&window("Google-chrome"), Some(Path::new("/opt/google/chrome/chrome")), &entries);
+        assert_eq!((chrome.name.as_str(), chrome.app_id.as_str()), ("Google Chrome", "google-chrome"));
+
+        let files = resolve_app(&window("org.gnome.nautilus"), None, &entries);
+        assert_eq!(files.app_id, "org.gnome.Nautilus");
+
+        let code = resolve_app(&window("Electron"), Some(Path::new("/usr/share/code/code")), &entries);
+        assert_eq!((code.name.as_str(), code.window_title.as_deref()), ("Visual Studio Code", Some("Title")));
+
+        let unknown = resolve_app(&window("XTerm"), Some(Path::new("/usr/bin/xterm")), &entries);
+        assert_eq!((unknown.name.as_str(), unknown.app_id.as_str()), ("XTerm", "xterm"));
+    }
+
+    // Runs against a real X server: `xvfb-run cargo test linux_focus -- --ignored`.
+    // There is no window manager under Xvfb, so the test publishes
+    // _NET_ACTIVE_WINDOW itself, as a window manager would.
+    #[test]
+    #[ignore = "needs an X server (xvfb-run)"]
+    fn test_reads_the_active_window_from_x11() {
+        let _turn = X11_TEST.blocking_lock();
+        let (conn, screen) = x11rb::connect(None).unwrap();
+        let root = conn.setup().roots[screen].root;
+        let atoms = Atoms::new(&conn).unwrap().reply().unwrap();
+
+        let window = conn.generate_id().unwrap();
+        conn.create_window(0, window, root, 0, 0, 10, 10, 0, WindowClass::INPUT_OUTPUT, 0, &CreateWindowAux::new())
+            .unwrap();
+        conn.change_property8(PropMode::REPLACE, window, AtomEnum::WM_CLASS, AtomEnum::STRING, b"xvfb-test\0XvfbTest\0")
+            .unwrap();
+        conn.change_property8(PropMode::REPLACE, window, atoms._NET_WM_NAME, atoms.UTF8_STRING, "Focus — test".as_bytes())
+            .unwrap();
+        conn.change_property32(PropMode::REPLACE, window, atoms._NET_WM_PID, AtomEnum::CARDINAL, &[std::process::id()])
+            .unwrap();
+        conn.change_property32(PropMode::REPLACE, root, atoms._NET_ACTIVE_WINDOW, AtomEnum::WINDOW, &[window])
+            .unwrap();
+        conn.sync().unwrap();
+
+        let provider = X11FocusProvider::new(None);
+        let app = provider.focused_app().unwrap().unwrap();
+        assert_eq!(app.name, "XvfbTest");
+        assert_eq!(app.window_title.as_deref(), Some("Focus — test"));
+        // Our own PID, so the executable is this test binary
+        let test_binary = std::env::current_exe().unwrap();
+        assert_eq!(app.app_id, test_binary.file_name().unwrap().to_string_lossy());
+
+        conn.change_property32(PropMode::REPLACE, root, atoms._NET_ACTIVE_WINDOW, AtomEnum::WINDOW, &[x11rb::NONE])
+            .unwrap();
+        conn.sync().unwrap();
+        assert!(provider.focused_app().unwrap().is_none());
+    }
+
+    // Runs against a real X server, like the test above
+    #[tokio::test]
+    #[ignore = "needs an X server (xvfb-run)"]
+    async fn test_active_window_changes_arrive_as_events() {
+        let _turn = X11_TEST.lock().await;
+        let provider = X11FocusProvider::new(None);
+        let mut changes = provider.focus_changes().unwrap();
+        // Every sampler shares the one event thread
+        assert!(provider.focus_changes().unwrap().same_channel(&changes));
+
+        let (conn, screen) = x11rb::connect(None).unwrap();
+        let root = conn.setup().roots[screen].root;
+        let atoms = Atoms::new(&conn).unwrap().reply().unwrap();
+        conn.change_property32(PropMode::REPLACE, root, atoms._NET_WM_PID, AtomEnum::CARDINAL, &[0]).unwrap();
+        conn.sync().unwrap();
+        // Other root properties don't count as focus changes
+        let wait = std::time::Duration::from_millis(200);
+        assert!(tokio::time::timeout(wait, changes.changed()).await.is_err());
+
+        let window = conn.generate_id().unwrap();
+        conn.create_window(0, window, root, 0, 0, 10, 10, 0, WindowClass::INPUT_OUTPUT, 0, &CreateWindowAux::new())
+            .unwrap();
+        conn.change_property32(PropMode::REPLACE, root, atoms._NET_ACTIVE_WINDOW, AtomEnum::WINDOW, &[window])
+            .unwrap();
+        conn.sync().unwrap();
+        let wait = std::time::Duration::from_secs(2);
+        assert!(tokio::time::timeout(wait, changes.changed()).await.unwrap().is_ok());
+
+        // A new tab or document in the focused window
+        conn.change_property8(PropMode::REPLACE, window, atoms._NET_WM_NAME, atoms.UTF8_STRING, b"Second tab").unwrap();
+        conn.sync().unwrap();
+        assert!(tokio::time::timeout(wait, changes.changed()).await.unwrap().is_ok());
+
+        conn.change_property32(PropMode::REPLACE, root, atoms._NET_ACTIVE_WINDOW, AtomEnum::WINDOW, &[x11rb::NONE])
+            .unwrap();
+        conn.delete_property(root, atoms._NET_WM_PID).unwrap();
+        conn.destroy_window(window).unwrap();
+        conn.sync().unwrap();
+    }
+}
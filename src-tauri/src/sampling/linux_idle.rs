@@ -0,0 +1,241 @@
+use std::sync::Mutex;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use x11rb::connection::Connection;
+use x11rb::protocol::screensaver::ConnectionExt as _;
+use x11rb::protocol::xproto::Window;
+use x11rb::rust_connection::RustConnection;
+use zbus::proxy::CacheProperties;
+
+// Idle time and screen lock on Linux. On X11 the X server knows when input
+// last arrived (MIT-SCREEN-SAVER extension), so that is asked first. On Wayland
+// `DISPLAY` leads to XWayland, which only sees input sent to X clients, so the
+// idle hint logind gets from the compositor is asked first instead; it is
+// coarser, as the desktop only sets it once its own idle delay has passed.
+// Whether the screen is locked always comes from logind.
+
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1/session/auto"
+)]
+pub trait LogindSession {
+    #[zbus(property)]
+    fn idle_hint(&self) -> zbus::Result<bool>;
+
+    /// When the session went idle, in microseconds since the epoch
+    #[zbus(property)]
+    fn idle_since_hint(&self) -> zbus::Result<u64>;
+
+    #[zbus(property)]
+    fn locked_hint(&self) -> zbus::Result<bool>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogindState {
+    /// Set while logind considers the session idle
+    pub idle_since: Option<DateTime<Utc>>,
+    pub locked: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleSource {
+    X11,
+    Logind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdleState {
+    pub idle_seconds: u64,
+    pub locked: bool,
+}
+
+// Kept open between checks; dropped on failure so the next check reconnects
+static X11: Mutex<Option<(RustConnection, Window)>> = Mutex::new(None);
+static LOGIND: tokio::sync::Mutex<Option<LogindSessionProxy<'static>>> = tokio::sync::Mutex::const_new(None);
+
+/// Milliseconds since the last keyboard or mouse input on `$DISPLAY`. Blocking.
+pub fn x11_idle_millis() -> Result<u64> {
+    let mut session = X11.lock().map_err(|_| anyhow::anyhow!("X11 session lock poisoned"))?;
+    let (conn, root) = match session.take() {
+        Some(current) => current,
+        None => {
+            let (conn, screen) = x11rb::connect(None)?;
+            let root = conn.setup().roots[screen].root;
+            (conn, root)
+        }
+    };
+
+    let info = conn.screensaver_query_info(root)?.reply()?;
+    *session = Some((conn, root));
+    Ok(u64::from(info.ms_since_user_input))
+}
+
+/// Read the idle and lock hints of the session behind `session`
+pub async fn logind_state(session: &LogindSessionProxy<'_>) -> Result<LogindState> {
+    let locked = session.locked_hint().await?;
+    let idle_since = if session.idle_hint().await? {
+        DateTime::from_timestamp_micros(session.idle_since_hint().await? as i64)
+    } else {
+        None
+    };
+    Ok(LogindState { idle_since, locked })
+}
+
+/// logind's view of the session this agent runs in, over the system bus
+async fn current_logind_state() -> Result<LogindState> {
+    let mut proxy = LOGIND.lock().await;
+    let session = match proxy.take() {
+        Some(session) => session,
+        None => {
+            let conn = zbus::Connection::system().await?;
+            LogindSessionProxy::builder(&conn).cache_properties(CacheProperties::No).build().await?
+        }
+    };
+
+    let state = logind_state(&session).await?;
+    *proxy = Some(session);
+    Ok(state)
+}
+
+fn is_wayland_session(session_type: Option<&str>, wayland_display: Option<&str>) -> bool {
+    session_type.is_some_and(|kind| kind.eq_ignore_ascii_case("wayland")) || wayland_display.is_some_and(|display| !display.is_empty())
+}
+
+/// The idle sources to try, most trusted first
+pub fn idle_sources(wayland: bool) -> [IdleSource; 2] {
+    if wayland {
+        [IdleSource::Logind, IdleSource::X11]
+    } else {
+        [IdleSource::X11, IdleSource::Logind]
+    }
+}
+
+fn seconds_since(since: Option<DateTime<Utc>>, now: DateTime<Utc>) -> u64 {
+    since.map_or(0, |since| (now - since).num_seconds().max(0) as u64)
+}
+
+/// How long the user has been idle, and whether the screen is locked
+pub async fn idle_state() -> Result<IdleState> {
+    let logind = current_logind_state().await;
+    if let Err(e) = &logind {
+        log::trace!("logind session unavailable: {}", e);
+    }
+
+    let wayland = is_wayland_session(
+        std::env::var("XDG_SESSION_TYPE").ok().as_deref(),
+        std::env::var("WAYLAND_DISPLAY").ok().as_deref(),
+    );
+
+    let mut errors = Vec::new();
+    let mut idle_seconds = None;
+    for source in idle_sources(wayland) {
+        match source {
+            IdleSource::X11 => match tokio::task::spawn_blocking(x11_idle_millis).await? {
+                Ok(millis) => idle_seconds = Some(millis / 1000),
+                Err(e) => errors.push(format!("X11: {}", e)),
+            },
+            IdleSource::Logind => match &logind {
+                Ok(state) => idle_seconds = Some(seconds_since(state.idle_since, Utc::now())),
+                Err(e) => errors.push(format!("logind: {}", e)),
+            },
+        }
+        if idle_seconds.is_some() {
+            break;
+        }
+    }
+    let Some(idle_seconds) = idle_seconds else {
+        anyhow::bail!("No idle source available ({})", errors.join(", "));
+    };
+
+    Ok(IdleState {
+        idle_seconds,
+        locked: logind.is_ok_and(|state| state.locked),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logind_idle_time_counts_from_idle_since() {
+        let now = Utc::now();
+        assert_eq!(seconds_since(None, now), 0);
+        assert_eq!(seconds_since(Some(now - chrono::Duration::seconds(90)), now), 90);
+        // Clock went backwards
+        assert_eq!(seconds_since(Some(now + chrono::Duration::seconds(5)), now), 0);
+    }
+
+    #[test]
+    fn test_wayland_sessions_trust_logind_before_xwayland() {
+        assert!(is_wayland_session(Some("wayland"), None));
+        assert!(is_wayland_session(Some("x11"), Some("wayland-0")));
+        assert!(!is_wayland_session(Some("x11"), None));
+        assert!(!is_wayland_session(None, Some("")));
+
+        assert_eq!(idle_sources(true), [IdleSource::Logind, IdleSource::X11]);
+        assert_eq!(idle_sources(false), [IdleSource::X11, IdleSource::Logind]);
+    }
+
+    // Runs against a real X server: `xvfb-run cargo test linux_idle -- --ignored`
+    #[test]
+    #[ignore = "needs an X server (xvfb-run)"]
+    fn test_reads_idle_time_from_x11() {
+        let first = x11_idle_millis().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        // Nobody is typing on a virtual display
+        assert!(x11_idle_millis().unwrap() >= first + 200);
+    }
+
+    struct FakeSession {
+        idle_since: Option<DateTime<Utc>>,
+        locked: bool,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.login1.Session")]
+    impl FakeSession {
+        #[zbus(property)]
+        fn idle_hint(&self) -> bool {
+            self.idle_since.is_some()
+        }
+
+        #[zbus(property)]
+        fn idle_since_hint(&self) -> u64 {
+            self.idle_since.map_or(0, |since| since.timestamp_micros() as u64)
+        }
+
+        #[zbus(property)]
+        fn locked_hint(&self) -> bool {
+            self.locked
+        }
+    }
+
+    // Runs against a private session bus standing in for logind:
+    // `dbus-run-session -- cargo test linux_idle -- --ignored`
+    #[tokio::test]
+    #[ignore = "needs a session bus (dbus-run-session)"]
+    async fn test_reads_logind_hints_over_dbus() {
+        let idle_since = DateTime::from_timestamp_micros(Utc::now().timestamp_micros() - 42_000_000);
+        let path = "/org/freedesktop/login1/session/auto";
+        let service = zbus::connection::Builder::session()
+            .unwrap()
+            .name("org.freedesktop.login1")
+            .unwrap()
+            .serve_at(path, FakeSession { idle_since, locked: true })
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+
+        let client = zbus::Connection::session().await.unwrap();
+        let session = LogindSessionProxy::builder(&client).cache_properties(CacheProperties::No).build().await.unwrap();
+        let state = logind_state(&session).await.unwrap();
+        assert_eq!(state, LogindState { idle_since, locked: true });
+
+        let fake = service.object_server().interface::<_, FakeSession>(path).await.unwrap();
+        fake.get_mut().await.idle_since = None;
+        assert_eq!(logind_state(&session).await.unwrap().idle_since, None);
+    }
+}
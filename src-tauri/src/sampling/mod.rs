@@ -1,10 +1,15 @@
 // Sampling module - simplified for production testing
 
 pub mod app_focus;
+pub mod event_batch;
 pub mod idle_detector;
 pub mod heartbeat;
+#[cfg(target_os = "linux")]
+pub mod linux_focus;
+#[cfg(target_os = "linux")]
+pub mod linux_idle;
 pub mod power_state;
-pub mod queue_processor;
+pub mod sync_engine;
 
 #[allow(dead_code)]
 pub fn is_dev_mode() -> bool {
@@ -20,6 +25,22 @@ pub fn get_app_focus_interval() -> u64 {
     }
 }
 
+/// How long focus has to stay put after a focus event before it is sampled,
+/// so cycling through windows records only where the user lands
+pub fn get_focus_debounce_millis() -> u64 {
+    300
+}
+
+/// While focus events are available, focus is still sampled this often to
+/// catch missed events and keep the session's idle flag current
+pub fn get_focus_resync_interval() -> u64 {
+    if is_dev_mode() {
+        5
+    } else {
+        30
+    }
+}
+
 #[allow(dead_code)]
 pub fn get_heartbeat_interval() -> u64 {
     if is_dev_mode() {
@@ -32,6 +53,8 @@ pub fn get_heartbeat_interval() -> u64 {
 use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::sync::RwLock;
 use crate::storage::offline_queue;
+use crate::api::client::ApiClient;
+use crate::api::protocol::{wire_timestamp, EventBatch, EventEnvelope, HeartbeatEnvelope};
 
 // Global state for background services
 static SERVICES_RUNNING: AtomicBool = AtomicBool::new(false);
@@ -201,19 +224,8 @@ pub async fn start_all_background_services(app_handle: tauri::AppHandle) {
         crate::api::job_polling::start_job_polling(app_handle4).await;
     });
     
-    // Start offline queue processor (runs even after clock out for 1 min to send pending events)
-    let app_handle5 = app_handle.clone();
-    tokio::spawn(async move {
-        update_service_state(|state| {
-            state.queue_processor_running = true;
-        }).await;
-        
-        queue_processor::start_queue_processor(app_handle5).await;
-        
-        update_service_state(|state| {
-            state.queue_processor_running = false;
-        }).await;
-    });
+    // Flush anything queued while clocked out; the sync engine itself runs for the app's lifetime
+    sync_engine::request_sync();
     
 }
 
@@ -235,7 +247,7 @@ async fn start_idle_detection_service(_app_handle: tauri::AppHandle) {
     let interval_seconds = 3; // Check idle status every 3 seconds for better responsiveness
 
     let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(interval_seconds));
-    let mut last_check_time = chrono::Utc::now();
+    let mut last_check_time = power_state::now();
     
     loop {
         // Check if services should continue running (authenticated AND clocked in)
@@ -254,7 +266,7 @@ async fn start_idle_detection_service(_app_handle: tauri::AppHandle) {
         }
 
         // Detect potential sleep/wake events by checking for large time gaps
-        let now = chrono::Utc::now();
+        let now = power_state::now();
         let time_since_last_check = (now - last_check_time).num_seconds() as u64;
         
         // If more than 2x the interval has passed, we likely woke from sleep
@@ -304,22 +316,24 @@ async fn start_idle_detection_service(_app_handle: tauri::AppHandle) {
             // Send idle events only when status changes AND user is clocked in
             if state_changed && should_services_run().await {
                 let event_type = if is_idle { "idle_start" } else { "idle_end" };
+                let occurred_at = chrono::Utc::now();
                 let event_data = serde_json::json!({
                     "idle_time_seconds": idle_time,
                     "threshold_seconds": threshold,
                     "is_idle": is_idle,
-                    "timestamp": chrono::Utc::now().to_rfc3339(),
+                    "timestamp": occurred_at.to_rfc3339(),
                     "reason": "user_activity"
                 });
                 log::debug!("Sending idle event: {} (idle_time: {}s)", event_type, idle_time);
                 // Try to send live first, fallback to queue if failed
-                match send_event_to_backend(event_type, &event_data).await {
+                let event_id = offline_queue::new_event_id();
+                match send_event_to_backend(&event_id, event_type, &event_data, occurred_at).await {
                     Ok(_) => {
                         log::debug!("✓ Idle event sent successfully");
                     }
                     Err(e) => {
                         log::warn!("🔍 Failed to send idle event live, queuing for later: {}", e);
-                        if let Err(e) = crate::storage::offline_queue::queue_event(event_type, &event_data).await {
+                        if let Err(e) = offline_queue::queue_event_with_id(&event_id, event_type, &event_data, occurred_at).await {
                             log::error!("Failed to queue idle event: {}", e);
                         }
                     }
@@ -343,316 +357,72 @@ pub fn get_job_polling_interval() -> u64 {
     }
 }
 
-// Queue processing service
-#[allow(dead_code)]
-pub async fn start_queue_processing_service() {
-    
-    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(30));
-    
-    loop {
-        if !SERVICES_RUNNING.load(Ordering::Relaxed) {
-            break;
-        }
-
-        // Only process queue when authenticated
-        if !is_authenticated().await {
-            interval.tick().await;
-            continue;
-        }
-
-        // Process pending heartbeats
-        if let Ok(heartbeats) = offline_queue::get_pending_heartbeats().await {
-            if !heartbeats.is_empty() {
-            }
-            for heartbeat in heartbeats {
-                if let Err(e) = send_heartbeat_to_backend(&heartbeat.heartbeat_data).await {
-                    log::error!("Failed to send heartbeat4: {}", e);
-                    if let Err(e) = offline_queue::mark_heartbeat_failed(heartbeat.id).await {
-                        log::error!("Failed to mark heartbeat as failed: {}", e);
-                    }
-                } else {
-                    if let Err(e) = offline_queue::mark_heartbeat_processed(heartbeat.id).await {
-                        log::error!("Failed to mark heartbeat as processed: {}", e);
-                    }
-                }
-            }
-        } else {
-        }
-
-        // Process pending events
-        if let Ok(events) = offline_queue::get_pending_events().await {
-            for event in events {
-                log::debug!("Sending event: 1");
-                if let Err(e) = send_event_to_backend(&event.event_type, &event.event_data).await {
-                    log::error!("Failed to send event: {}", e);
-                    if let Err(e) = offline_queue::mark_event_failed(event.id).await {
-                        log::error!("Failed to mark event as failed: {}", e);
-                    }
-                } else {
-                    if let Err(e) = offline_queue::mark_event_processed(event.id).await {
-                        log::error!("Failed to mark event as processed: {}", e);
-                    }
-                }
-            }
-        }
-
-        interval.tick().await;
-    }
-
-}
+// Removed sync_local_app_usage_sessions function - no longer needed
+// App usage is now tracked solely via app_focus events, eliminating duplication
 
-// Enhanced sync service that syncs all local data when reconnected
-#[allow(dead_code)]
-pub async fn start_sync_service() {
-    
-    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(30));
+/// Deliver a heartbeat. `event_id` is the outbox ID and is reused on every retry
+/// so the server can discard duplicates; `occurred_at` is when it was sampled.
+pub async fn send_heartbeat_to_backend(event_id: &str, heartbeat_data: &serde_json::Value, occurred_at: chrono::DateTime<chrono::Utc>) -> anyhow::Result<()> {
+    let client = ApiClient::new().await?;
+
+    let heartbeat_payload = HeartbeatEnvelope {
+        id: event_id,
+        occurred_at: wire_timestamp(occurred_at),
+        sent_at: wire_timestamp(chrono::Utc::now()),
+        heartbeat: heartbeat_data,
+    };
+
+    let request = client
+        .post("/api/ingest/heartbeat", &heartbeat_payload)
+        .idempotency_key(event_id);
+    log::info!("🔗 Attempting to send heartbeat to: {}", request.url());
+    log::debug!("Heartbeat data: {}", serde_json::to_string_pretty(&heartbeat_payload).unwrap_or_default());
     
-    loop {
-        if !SERVICES_RUNNING.load(Ordering::Relaxed) {
-            break;
-        }
-
-        // Only sync when authenticated and online
-        if !is_authenticated().await {
-            interval.tick().await;
-            continue;
-        }
-
-        // Check if we're online and have pending data to sync
-        if is_online().await {
-            
-            // Sync pending heartbeats
-            if let Ok(heartbeats) = offline_queue::get_pending_heartbeats().await {
-                if !heartbeats.is_empty() {
-                    for heartbeat in heartbeats {
-                        if let Err(e) = send_heartbeat_to_backend(&heartbeat.heartbeat_data).await {
-                            log::error!("Failed to sync heartbeat {}: {}", heartbeat.id, e);
-                            if let Err(e) = offline_queue::mark_heartbeat_failed(heartbeat.id).await {
-                                log::error!("Failed to mark heartbeat as failed: {}", e);
-                            }
-                        } else {
-                            if let Err(e) = offline_queue::mark_heartbeat_processed(heartbeat.id).await {
-                                log::error!("Failed to mark heartbeat as processed: {}", e);
-                            }
-                        }
-                    }
-                }
-            }
-
-            // Sync pending events
-            if let Ok(events) = offline_queue::get_pending_events().await {
-                if !events.is_empty() {
-                    for event in events {
-                        log::debug!("Sending event: {:?}", event);
-                        if let Err(e) = send_event_to_backend(&event.event_type, &event.event_data).await {
-                            log::error!("Failed to sync event {}: {}", event.id, e);
-                            if let Err(e) = offline_queue::mark_event_failed(event.id).await {
-                                log::error!("Failed to mark event as failed: {}", e);
-                            }
-                        } else {
-                            if let Err(e) = offline_queue::mark_event_processed(event.id).await {
-                                log::error!("Failed to mark event as processed: {}", e);
-                            }
-                        }
-                    }
-                }
-            }
-
-            // Skip syncing app_usage sessions - app_focus events already handle this
-            // if let Err(e) = sync_local_app_usage_sessions().await {
-            //     log::error!("Failed to sync local app usage sessions: {}", e);
-            // }
-        } else {
-        }
-
-        interval.tick().await;
+    // Don't spend a request while the connectivity monitor knows the server is gone
+    if crate::api::connectivity::is_offline() {
+        return Err(anyhow::anyhow!("Server at {} is offline, heartbeat will be queued", client.base_url()));
     }
-
-}
-
-// Check if server is reachable with a simple connectivity test
-async fn is_server_reachable(server_url: &str) -> bool {
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(5))
-        .connect_timeout(std::time::Duration::from_secs(3))
-        .build();
     
-    if let Ok(client) = client {
-        let test_url = format!("{}/api/health", server_url.trim_end_matches('/'));
-        log::debug!("Testing server connectivity to: {}", test_url);
-        
-        match client.get(&test_url).send().await {
-            Ok(response) => {
-                log::debug!("Server connectivity test: {}", response.status());
-                response.status().is_success()
-            },
-            Err(e) => {
-                log::debug!("Server connectivity test failed: {}", e);
-                false
-            }
+    match request.send_checked().await {
+        Ok(response) => {
+            log::trace!("Heartbeat sent successfully (status: {})", response.status());
+            Ok(())
         }
-    } else {
-        false
-    }
-}
-
-// Check if we're online by testing a simple API call
-async fn is_online() -> bool {
-    if let Ok(server_url) = crate::storage::get_server_url().await {
-        if let Ok(device_token) = crate::storage::get_device_token().await {
-            if !server_url.is_empty() && !device_token.is_empty() {
-                let device_id = crate::storage::get_device_id().await
-                    .map_err(|_| anyhow::anyhow!("No device ID available"));
-                let client = reqwest::Client::new();
-                let test_url = format!("{}/api/auth/simple-session", server_url.trim_end_matches('/'));
-                
-                log::info!("🔍 Testing connectivity to: {}", test_url);
-                
-                match client
-                    .get(&test_url)
-                    .header("Authorization", format!("Bearer {}", device_token))
-                    .header("X-Device-ID", device_id.expect("REASON").clone())
-                    .timeout(std::time::Duration::from_secs(10))
-                    .send()
-                    .await
-                {
-                    Ok(response) => {
-                        log::info!("✅ Connectivity test successful: {}", response.status());
-                        return response.status().is_success();
-                    },
-                    Err(e) => {
-                        log::warn!("❌ Connectivity test failed: {}", e);
-                        return false;
-                    },
-                }
-            }
+        Err(e) => {
+            log::error!("❌ Heartbeat failed: {}", e);
+            Err(e.into())
         }
     }
-    log::warn!("❌ Cannot test connectivity: missing server URL or device token");
-    false
 }
 
-// Removed sync_local_app_usage_sessions function - no longer needed
-// App usage is now tracked solely via app_focus events, eliminating duplication
-
-pub async fn send_heartbeat_to_backend(heartbeat_data: &serde_json::Value) -> anyhow::Result<()> {
-    // Get server URL and device token from storage
-    let server_url = crate::storage::get_server_url().await?;
-    let device_token = crate::storage::get_device_token().await?;
-    let device_id = crate::storage::get_device_id().await?;
-
-    
-    
-    if server_url.is_empty() || device_token.is_empty() {
-        log::warn!("Cannot send heartbeat: server_url or device_token is empty");
-        return Err(anyhow::anyhow!("Server URL or device token is empty"));
-    }
-    
-    // Create HTTP client with reasonable timeouts
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(30))
-        .connect_timeout(std::time::Duration::from_secs(10)) 
-        .build()
-        .map_err(|e| anyhow::anyhow!("Failed to create HTTP client: {}", e))?;
-    
-    let heartbeat_url = format!("{}/api/ingest/heartbeat", server_url.trim_end_matches('/'));
+/// Deliver a single event. `event_id` is the outbox ID and is reused on every retry
+/// so the server can discard duplicates. `occurred_at` is when the action happened;
+/// `sent_at` is stamped here so the server can tell late deliveries apart.
+pub async fn send_event_to_backend(event_id: &str, event_type: &str, event_data: &serde_json::Value, occurred_at: chrono::DateTime<chrono::Utc>) -> anyhow::Result<()> {
+    let client = ApiClient::new().await?;
     
-    log::info!("🔗 Attempting to send heartbeat to: {}", heartbeat_url);
-    log::debug!("Heartbeat data: {}", serde_json::to_string_pretty(heartbeat_data).unwrap_or_default());
+    let event_payload = EventBatch::single(
+        EventEnvelope::new(event_id, event_type, event_data.clone(), occurred_at).sent_from("send_event_to_backend"),
+    );
     
-    // First, test if the server is reachable with a simple connectivity check
-    if !is_server_reachable(&server_url).await {
-        return Err(anyhow::anyhow!("Server is not reachable at {}. Please ensure the backend is running on the correct port.", server_url));
-    }
-    
-    let response = client
-        .post(&heartbeat_url)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", device_token))
-        .header("X-Device-ID", device_id)
-        .json(heartbeat_data)
-        .send()
-        .await
-        .map_err(|e| {
-            log::error!("❌ Heartbeat request failed: {}", e);
-            if e.is_connect() {
-                anyhow::anyhow!("Network error: Cannot connect to server at {}. Please check your network connection and ensure the backend is running.", heartbeat_url)
-            } else if e.is_timeout() {
-                anyhow::anyhow!("Network error: Request timeout after 10 seconds. Server may be slow or unresponsive.")
-            } else {
-                anyhow::anyhow!("Network error: {}", e)
-            }
-        })?;
-    
-    if response.status().is_success() {
-        log::trace!("Heartbeat sent successfully (status: {})", response.status());
-        Ok(())
-    } else {
-        let status = response.status();
-        let text = response.text().await.unwrap_or_default();
-        log::error!("Heartbeat failed with status {}: {}", status, text);
-        Err(anyhow::anyhow!("Heartbeat failed with status {}: {}", status, text))
-    }
-}
+    let request = client
+        .post("/api/ingest/events", &event_payload)
+        .idempotency_key(event_id);
+    log::info!("🔗 Attempting to send {} event to: {}", event_type, request.url());
+    log::debug!("Event payload: {}", serde_json::to_string_pretty(&event_payload).unwrap_or_default());
 
-pub async fn send_event_to_backend(event_type: &str, event_data: &serde_json::Value) -> anyhow::Result<()> {
-    // Get server URL and device token from storage
-    let server_url = crate::storage::get_server_url().await?;
-    let device_token = crate::storage::get_device_token().await?;
-    let device_id = crate::storage::get_device_id().await?;
-    
-    if server_url.is_empty() || device_token.is_empty() {
-        log::warn!("Cannot send event: missing server URL or device token");
-        return Ok(());
+    if crate::api::connectivity::is_offline() {
+        return Err(anyhow::anyhow!("Server at {} is offline, {} event will be queued", client.base_url(), event_type));
     }
     
-    // Create HTTP client with reasonable timeouts
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(30))
-        .connect_timeout(std::time::Duration::from_secs(10))
-        .build()
-        .map_err(|e| anyhow::anyhow!("Failed to create HTTP client: {}", e))?;
-    
-    let events_url = format!("{}/api/ingest/events", server_url.trim_end_matches('/'));
-    
-    let event_payload = serde_json::json!({
-        "events": [{
-            "type": event_type,
-            "timestamp": chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
-            "data": event_data,
-            "from": "send_event_to_backend"
-        }]
-    });
-    
-    log::info!("🔗 Attempting to send {} event to: {}", event_type, events_url);
-    log::debug!("Event payload: {}", serde_json::to_string_pretty(&event_payload).unwrap_or_default());
-    
-    let response = client
-        .post(&events_url)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", device_token))
-        .header("X-Device-ID", device_id.clone())
-        .json(&event_payload)
-        .send()
-        .await
-        .map_err(|e| {
-            log::error!("❌ Event request failed: {}", e);
-            if e.is_connect() {
-                anyhow::anyhow!("Network error: Cannot connect to server at {}. Please check your network connection and ensure the backend is running.", events_url)
-            } else if e.is_timeout() {
-                anyhow::anyhow!("Network error: Request timeout after 30 seconds. Server may be slow or unresponsive.")
-            } else {
-                anyhow::anyhow!("Network error: {}", e)
-            }
-        })?;
-    
-    if response.status().is_success() {
-        log::debug!("✓ {} event sent successfully", event_type);
-        Ok(())
-    } else {
-        let status = response.status();
-        let text = response.text().await.unwrap_or_default();
-        log::warn!("Event failed with status {}: {}", status, text);
-        Err(anyhow::anyhow!("Event failed with status {}: {}", status, text))
+    match request.send_checked().await {
+        Ok(_) => {
+            log::debug!("✓ {} event sent successfully", event_type);
+            Ok(())
+        }
+        Err(e) => {
+            log::warn!("❌ {} event failed: {}", event_type, e);
+            Err(e.into())
+        }
     }
 }
\ No newline at end of file
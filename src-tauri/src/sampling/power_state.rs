@@ -1,6 +1,6 @@
 // Power state monitoring module for detecting sleep/wake events
 use std::sync::atomic::{AtomicU64, Ordering};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 
 // Track the last activity timestamp
 static LAST_ACTIVITY_TIMESTAMP: AtomicU64 = AtomicU64::new(0);
@@ -11,16 +11,34 @@ static SLEEP_START_TIME: AtomicU64 = AtomicU64::new(0);
 // Track if system is currently sleeping
 static IS_SLEEPING: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
 
+/// Sleep is detected from the system clock
+pub struct SystemPower;
+
+impl crate::platform::PowerProvider for SystemPower {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Current time on the platform clock, used for every sleep/wake decision
+pub fn now() -> DateTime<Utc> {
+    crate::platform::current().power.now()
+}
+
+fn now_timestamp() -> u64 {
+    now().timestamp() as u64
+}
+
 /// Initialize power state monitoring
 pub fn init() {
-    let now = Utc::now().timestamp() as u64;
+    let now = now_timestamp();
     LAST_ACTIVITY_TIMESTAMP.store(now, Ordering::Relaxed);
     log::info!("Power state monitoring initialized");
 }
 
 /// Update the last activity timestamp
 pub fn update_last_activity() {
-    let now = Utc::now().timestamp() as u64;
+    let now = now_timestamp();
     LAST_ACTIVITY_TIMESTAMP.store(now, Ordering::Relaxed);
 }
 
@@ -33,7 +51,7 @@ pub fn get_last_activity_timestamp() -> u64 {
 /// Mark system as entering sleep
 #[allow(dead_code)]
 pub fn mark_sleep_start() {
-    let now = Utc::now().timestamp() as u64;
+    let now = now_timestamp();
     SLEEP_START_TIME.store(now, Ordering::Relaxed);
     IS_SLEEPING.store(true, Ordering::Relaxed);
     log::info!("System entering sleep mode at {}", now);
@@ -42,7 +60,7 @@ pub fn mark_sleep_start() {
 /// Mark system as waking up and return sleep duration
 pub fn mark_wake_up() -> u64 {
     let sleep_start = SLEEP_START_TIME.load(Ordering::Relaxed);
-    let now = Utc::now().timestamp() as u64;
+    let now = now_timestamp();
     let sleep_duration = if sleep_start > 0 {
         now.saturating_sub(sleep_start)
     } else {
@@ -66,7 +84,7 @@ pub fn is_system_sleeping() -> bool {
 #[allow(dead_code)]
 pub async fn detect_time_gap() -> Option<u64> {
     let last_activity = get_last_activity_timestamp();
-    let now = Utc::now().timestamp() as u64;
+    let now = now_timestamp();
     
     // If more than 10 minutes have passed since last activity, consider it a sleep event
     const SLEEP_THRESHOLD: u64 = 600; // 10 minutes
@@ -184,16 +202,18 @@ pub async fn handle_system_sleep() {
     log::info!("🌙 System is going to sleep");
     
     // Send idle_start event
+    let occurred_at = now();
     let event_data = serde_json::json!({
         "reason": "system_sleep",
-        "timestamp": Utc::now().to_rfc3339(),
+        "timestamp": occurred_at.to_rfc3339(),
         "idle_time_seconds": 0,
     });
     
-    if let Err(e) = crate::sampling::send_event_to_backend("idle_start", &event_data).await {
+    let event_id = crate::storage::offline_queue::new_event_id();
+    if let Err(e) = crate::sampling::send_event_to_backend(&event_id, "idle_start", &event_data, occurred_at).await {
         log::error!("Failed to send sleep idle_start event: {}", e);
         // Queue the event for later
-        if let Err(e) = crate::storage::offline_queue::queue_event("idle_start", &event_data).await {
+        if let Err(e) = crate::storage::offline_queue::queue_event_with_id(&event_id, "idle_start", &event_data, occurred_at).await {
             log::error!("Failed to queue sleep event: {}", e);
         }
     }
@@ -214,17 +234,19 @@ pub async fn handle_system_wake(sleep_duration: u64) {
     log::info!("☀️ System woke up after {} seconds", actual_duration);
     
     // Send idle_end event with the sleep duration
+    let occurred_at = now();
     let event_data = serde_json::json!({
         "reason": "system_wake",
-        "timestamp": Utc::now().to_rfc3339(),
+        "timestamp": occurred_at.to_rfc3339(),
         "idle_time_seconds": actual_duration,
         "sleep_duration_seconds": actual_duration,
     });
     
-    if let Err(e) = crate::sampling::send_event_to_backend("idle_end", &event_data).await {
+    let event_id = crate::storage::offline_queue::new_event_id();
+    if let Err(e) = crate::sampling::send_event_to_backend(&event_id, "idle_end", &event_data, occurred_at).await {
         log::error!("Failed to send wake idle_end event: {}", e);
         // Queue the event for later
-        if let Err(e) = crate::storage::offline_queue::queue_event("idle_end", &event_data).await {
+        if let Err(e) = crate::storage::offline_queue::queue_event_with_id(&event_id, "idle_end", &event_data, occurred_at).await {
             log::error!("Failed to queue wake event: {}", e);
         }
     }
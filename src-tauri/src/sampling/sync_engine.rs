@@ -0,0 +1,215 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use serde::Serialize;
+use tokio::sync::{Mutex, Notify};
+
+use super::event_batch::{self, MAX_BATCH_BYTES, MAX_BATCH_EVENTS};
+use crate::api::error::FailureKind;
+use crate::storage::offline_queue::{self, FailedItem};
+
+// How long a claimed row stays reserved for this agent. Longer than a full
+// batch round trip (30s HTTP timeout) so a slow upload isn't picked up twice.
+const LEASE_SECS: i64 = 120;
+
+// Upper bound on batches per run so one sync can't hold the engine forever
+// after a very long offline period
+const MAX_ROUNDS_PER_SYNC: usize = 50;
+
+// The one place the outbox is drained from. Periodic ticks, clock in/out and
+// the manual "sync now" button all funnel into `sync_now`.
+static SYNC_LOCK: Mutex<()> = Mutex::const_new(());
+static SYNC_REQUESTED: Notify = Notify::const_new();
+static ENGINE_RUNNING: AtomicBool = AtomicBool::new(false);
+static WORKER_ID: OnceLock<String> = OnceLock::new();
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct SyncReport {
+    pub heartbeats_sent: usize,
+    pub events_sent: usize,
+    pub failed: usize,
+}
+
+fn get_sync_interval() -> u64 {
+    if super::is_dev_mode() {
+        5 // 5 seconds for development
+    } else {
+        15 // retries are paced by the outbox backoff schedule, not by this tick
+    }
+}
+
+/// Lease owner for this process. Unique per run so a restarted agent doesn't
+/// inherit (and double-send) rows still leased by its previous instance.
+fn worker_id() -> &'static str {
+    WORKER_ID.get_or_init(|| format!("agent-{}-{}", std::process::id(), uuid::Uuid::new_v4()))
+}
+
+fn lease() -> chrono::Duration {
+    chrono::Duration::seconds(LEASE_SECS)
+}
+
+/// Ask the engine to sync as soon as possible without waiting for the result
+pub fn request_sync() {
+    SYNC_REQUESTED.notify_one();
+}
+
+/// Start the sync scheduler. Safe to call more than once; only the first call runs it.
+pub async fn start_sync_engine() {
+    if ENGINE_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(get_sync_interval()));
+    log::info!("📦 Sync engine starting (interval: {}s, worker: {})", get_sync_interval(), worker_id());
+
+    super::update_service_state(|state| {
+        state.queue_processor_running = true;
+    }).await;
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = SYNC_REQUESTED.notified() => {}
+        }
+
+        // Only sync when authenticated, and not while a rejected device token is being
+        // renewed or the server is known to be unreachable (coming back online wakes us up)
+        if !super::is_authenticated().await || crate::api::auth::is_suspended() || crate::api::connectivity::is_offline() {
+            continue;
+        }
+
+        match sync_now().await {
+            Ok(report) => {
+                if report.heartbeats_sent + report.events_sent > 0 {
+                    log::info!("✓ Synced {} heartbeats and {} events", report.heartbeats_sent, report.events_sent);
+                }
+            }
+            Err(e) => log::warn!("Sync failed: {}", e),
+        }
+    }
+}
+
+/// Drain everything that is due right now. Concurrent callers wait for the
+/// running sync instead of sending the same rows again.
+pub async fn sync_now() -> anyhow::Result<SyncReport> {
+    let _guard = SYNC_LOCK.lock().await;
+    let mut report = SyncReport::default();
+
+    if crate::api::auth::is_suspended() {
+        anyhow::bail!("Sync is paused until the device is re-authenticated");
+    }
+    if crate::api::connectivity::is_offline() {
+        anyhow::bail!("Server is offline; queued items will be sent once it is reachable");
+    }
+
+    drain_heartbeats(&mut report).await?;
+    drain_events(&mut report).await?;
+
+    Ok(report)
+}
+
+async fn drain_heartbeats(report: &mut SyncReport) -> anyhow::Result<()> {
+    for _ in 0..MAX_ROUNDS_PER_SYNC {
+        let heartbeats = offline_queue::claim_pending_heartbeats(worker_id(), MAX_BATCH_EVENTS, lease()).await?;
+        if heartbeats.is_empty() {
+            return Ok(());
+        }
+
+        let mut had_failures = false;
+        for heartbeat in heartbeats {
+            match super::send_heartbeat_to_backend(&heartbeat.event_id, &heartbeat.heartbeat_data, heartbeat.timestamp).await {
+                Ok(_) => {
+                    offline_queue::mark_heartbeat_processed(heartbeat.id).await?;
+                    report.heartbeats_sent += 1;
+                }
+                Err(e) => {
+                    log::warn!("Failed to send queued heartbeat (retry {}/{}): {}",
+                        heartbeat.retry_count + 1, heartbeat.max_retries, e);
+                    offline_queue::mark_heartbeat_failed(heartbeat.id, &e).await?;
+                    report.failed += 1;
+                    had_failures = true;
+                }
+            }
+        }
+
+        // Leave the rest for the next tick rather than hammering an unhappy server
+        if had_failures {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+async fn drain_events(report: &mut SyncReport) -> anyhow::Result<()> {
+    for _ in 0..MAX_ROUNDS_PER_SYNC {
+        let events = offline_queue::claim_pending_events(worker_id(), MAX_BATCH_EVENTS, lease()).await?;
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        // Rows beyond the byte budget stay leased to us and are picked up next round
+        let (_, count) = event_batch::pack_batch(&events, MAX_BATCH_EVENTS, MAX_BATCH_BYTES);
+        let result = match event_batch::send_event_batch(&events).await {
+            Ok(result) => result,
+            // The server refused the batch as a whole; send the events one by one so
+            // only the offending ones end up in the dead-letter table
+            Err(e) if count > 1 && offline_queue::classify_error(&e) == FailureKind::Permanent => {
+                log::warn!("Event batch rejected ({}), retrying events individually", e);
+                event_batch::send_individually(&events[..count]).await
+            }
+            Err(e) => {
+                // Nothing was acknowledged: count the attempt against the whole batch
+                let failures: Vec<FailedItem> = events
+                    .iter()
+                    .take(count)
+                    .map(|event| FailedItem::from_error(event.id, &e))
+                    .collect();
+                report.failed += failures.len();
+                offline_queue::mark_events_failed(failures).await?;
+                return if report.events_sent > 0 { Ok(()) } else { Err(e) };
+            }
+        };
+
+        report.events_sent += result.accepted.len();
+        report.failed += result.failed.len();
+        let had_failures = !result.failed.is_empty();
+        offline_queue::mark_events_processed(result.accepted).await?;
+        offline_queue::mark_events_failed(result.failed).await?;
+
+        // Partial acceptance usually means the server is unhappy - try again next tick
+        if had_failures {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::database;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_rows_leased_by_a_previous_run_are_not_resent() {
+        let _db = database::test_database().await;
+        offline_queue::queue_event("clock_in", &json!({})).await.unwrap();
+
+        // A crashed earlier instance still holds the lease
+        let previous = offline_queue::claim_pending_events("agent-previous", MAX_BATCH_EVENTS, lease()).await.unwrap();
+        assert_eq!(previous.len(), 1);
+        assert_ne!(worker_id(), "agent-previous");
+        assert!(offline_queue::claim_pending_events(worker_id(), MAX_BATCH_EVENTS, lease()).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_engine_picks_up_its_own_unfinished_batch() {
+        let _db = database::test_database().await;
+        offline_queue::queue_heartbeat(&json!({"status": "active"})).await.unwrap();
+
+        let first = offline_queue::claim_pending_heartbeats(worker_id(), MAX_BATCH_EVENTS, lease()).await.unwrap();
+        let again = offline_queue::claim_pending_heartbeats(worker_id(), MAX_BATCH_EVENTS, lease()).await.unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(again[0].event_id, first[0].event_id);
+    }
+}
@@ -14,7 +14,21 @@ use windows::{
     },
 };
 
+/// Screenshots taken with the OS capture APIs
+pub struct SystemScreenCapture;
+
+impl crate::platform::ScreenCaptureProvider for SystemScreenCapture {
+    fn capture_screen(&self) -> crate::platform::PlatformFuture<'_, String> {
+        Box::pin(native_capture_screen())
+    }
+}
+
+/// Capture the screen with the current platform, as base64-encoded JPEG
 pub async fn capture_screen() -> Result<String> {
+    crate::platform::current().capture.capture_screen().await
+}
+
+async fn native_capture_screen() -> Result<String> {
     #[cfg(target_os = "macos")]
     {
         capture_screen_macos().await
@@ -5,6 +5,8 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 use super::database;
+use crate::policy::filter::{filter_title, site_in};
+use crate::policy::toggles::{get_current_policy, PolicyConfig};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppUsageSession {
@@ -17,6 +19,90 @@ pub struct AppUsageSession {
     pub duration_seconds: i64,
     pub is_idle: bool,
     pub is_active: bool,
+    /// Title and site changes within the session, when sub-activity tracking is on
+    #[serde(default)]
+    pub segments: Vec<ActivitySegment>,
+}
+
+/// Part of an app session spent on one window title or site. Titles are
+/// filtered before they get here, so two titles that filter to the same text
+/// (two redacted documents, two pages of one site) are one segment.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActivitySegment {
+    pub window_title: Option<String>,
+    pub domain: Option<String>,
+    pub start_time: DateTime<Utc>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub duration_seconds: i64,
+}
+
+impl ActivitySegment {
+    fn new(window_title: Option<String>, domain: Option<String>, start_time: DateTime<Utc>) -> Self {
+        Self { window_title, domain, start_time, end_time: None, duration_seconds: 0 }
+    }
+
+    fn closed(mut self, end_time: DateTime<Utc>) -> Self {
+        self.end_time = Some(end_time);
+        self.duration_seconds = (end_time - self.start_time).num_seconds();
+        self
+    }
+}
+
+/// Splits the current session into segments as its title changes. A segment
+/// shorter than the minimum dwell time is dropped and its time goes to the
+/// segment before it, so flicking through tabs doesn't record every tab.
+#[derive(Debug, Clone, Default)]
+pub struct SegmentTracker {
+    // Each segment ends where the next one starts
+    recorded: Vec<ActivitySegment>,
+    open: Option<ActivitySegment>,
+}
+
+impl SegmentTracker {
+    /// Note the (filtered) title and the site it shows, if a browser's
+    pub fn observe(&mut self, window_title: Option<String>, domain: Option<String>, now: DateTime<Utc>, min_dwell: Duration) {
+        let open = match self.open.take() {
+            Some(open) if open.window_title != window_title => open,
+            Some(open) => {
+                self.open = Some(open);
+                return;
+            }
+            None => {
+                self.open = Some(ActivitySegment::new(window_title, domain, now));
+                return;
+            }
+        };
+
+        self.open = if now - open.start_time >= min_dwell {
+            self.recorded.push(open);
+            Some(ActivitySegment::new(window_title, domain, now))
+        } else if self.recorded.last().is_some_and(|last| last.window_title == window_title) {
+            // Back from a short detour
+            self.recorded.pop()
+        } else if self.recorded.is_empty() {
+            // Nothing before it to take the time, so the next segment does
+            Some(ActivitySegment::new(window_title, domain, open.start_time))
+        } else {
+            Some(ActivitySegment::new(window_title, domain, now))
+        };
+    }
+
+    /// The segments so far, closed at `end`
+    pub fn segments(&self, end: DateTime<Utc>, min_dwell: Duration) -> Vec<ActivitySegment> {
+        let mut segments: Vec<&ActivitySegment> = self.recorded.iter().collect();
+        if let Some(open) = &self.open {
+            if end - open.start_time >= min_dwell || segments.is_empty() {
+                segments.push(open);
+            }
+        }
+
+        let ends: Vec<DateTime<Utc>> = segments.iter().skip(1).map(|next| next.start_time).chain([end]).collect();
+        segments.into_iter().zip(ends).map(|(segment, end)| segment.clone().closed(end)).collect()
+    }
+}
+
+fn min_dwell(policy: &PolicyConfig) -> Duration {
+    Duration::seconds(policy.sub_activity_min_dwell_seconds as i64)
 }
 
 #[derive(Debug, Clone)]
@@ -24,6 +110,7 @@ pub struct AppUsageTracker {
     current_session: Option<AppUsageSession>,
     session_history: Vec<AppUsageSession>,
     total_idle_time: i64,
+    segments: SegmentTracker,
 }
 
 impl AppUsageTracker {
@@ -32,6 +119,7 @@ impl AppUsageTracker {
             current_session: None,
             session_history: Vec::new(),
             total_idle_time: 0,
+            segments: SegmentTracker::default(),
         }
     }
 
@@ -49,6 +137,7 @@ impl AppUsageTracker {
             current.end_time = Some(now);
             current.duration_seconds = (now - current.start_time).num_seconds();
             current.is_active = false;
+            current.segments = self.finish_segments(now);
             
             
             // Save to database
@@ -58,6 +147,8 @@ impl AppUsageTracker {
         }
 
         // Start new session
+        let window_title_for_segments = window_title.clone();
+        let window_title = crate::policy::filter::filter_app_title(&app_id, window_title);
         let new_session = AppUsageSession {
             id: None,
             app_name,
@@ -68,9 +159,12 @@ impl AppUsageTracker {
             duration_seconds: 0,
             is_idle,
             is_active: true,
+            segments: Vec::new(),
         };
 
         self.current_session = Some(new_session);
+        self.segments = SegmentTracker::default();
+        self.record_window_title(window_title_for_segments, now);
         
         Ok(())
     }
@@ -82,12 +176,40 @@ impl AppUsageTracker {
         Ok(())
     }
 
+    /// Note the focused window's title, which starts a new segment when it
+    /// changed and sub-activity tracking is on
+    pub fn record_window_title(&mut self, window_title: Option<String>, now: DateTime<Utc>) {
+        let policy = get_current_policy();
+        let Some(session) = &self.current_session else {
+            return;
+        };
+        if !policy.sub_activity_enabled {
+            return;
+        }
+
+        let window_title = window_title.map(|title| filter_title(&policy, &session.app_id, &title));
+        let domain = window_title.as_deref().and_then(|title| site_in(&session.app_id, title));
+        self.segments.observe(window_title, domain, now, min_dwell(&policy));
+    }
+
+    fn finish_segments(&mut self, end: DateTime<Utc>) -> Vec<ActivitySegment> {
+        std::mem::take(&mut self.segments).segments(end, min_dwell(&get_current_policy()))
+    }
+
+    /// The current session with its segments so far
+    pub fn current_session_snapshot(&self) -> Option<AppUsageSession> {
+        let mut session = self.get_current_session().cloned()?;
+        session.segments = self.segments.segments(Utc::now(), min_dwell(&get_current_policy()));
+        Some(session)
+    }
+
     pub async fn end_current_session(&mut self) -> Result<()> {
         if let Some(mut current) = self.current_session.take() {
             let now = Utc::now();
             current.end_time = Some(now);
             current.duration_seconds = (now - current.start_time).num_seconds();
             current.is_active = false;
+            current.segments = self.finish_segments(now);
             
             
             // Save to database
@@ -140,14 +262,17 @@ impl AppUsageTracker {
 
 
     async fn save_session_to_db(&self, session: &AppUsageSession) -> Result<()> {
-        let conn = database::get_connection()?;
-        
-        conn.execute(
-            "INSERT INTO app_usage_sessions (
-                app_name, app_id, window_title, 
-                start_time, end_time, duration_seconds, is_idle, is_active, synced
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-            params![
+        let session = session.clone();
+
+        database::with_connection(move |conn| {
+            let tx = conn.transaction()?;
+            tx.prepare_cached(
+                "INSERT INTO app_usage_sessions (
+                    app_name, app_id, window_title, 
+                    start_time, end_time, duration_seconds, is_idle, is_active, synced
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )?
+            .execute(params![
                 session.app_name,
                 session.app_id,
                 session.window_title,
@@ -157,42 +282,92 @@ impl AppUsageTracker {
                 session.is_idle,
                 session.is_active,
                 true, // Set synced = true since app_focus handles backend sync
-            ],
-        )?;
-        
-        Ok(())
+            ])?;
+
+            let session_id = tx.last_insert_rowid();
+            {
+                let mut insert = tx.prepare_cached(
+                    "INSERT INTO app_usage_segments (
+                        session_id, window_title, domain, start_time, end_time, duration_seconds
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                )?;
+                for segment in &session.segments {
+                    let window_title = crate::policy::filter::filter_app_title(&session.app_id, segment.window_title.clone());
+                    insert.execute(params![
+                        session_id,
+                        window_title,
+                        segment.domain,
+                        segment.start_time,
+                        segment.end_time,
+                        segment.duration_seconds,
+                    ])?;
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        }).await
     }
 
     // Removed send_session_to_backend - app_focus events handle all backend syncing
 
     pub async fn load_recent_sessions(&mut self, hours: i64) -> Result<()> {
-        let conn = database::get_connection()?;
         let cutoff_time = Utc::now() - Duration::hours(hours);
         
-        let mut stmt = conn.prepare(
-            "SELECT id, app_name, app_id, window_title, 
-                    start_time, end_time, duration_seconds, is_idle, is_active
-             FROM app_usage_sessions 
-             WHERE start_time >= ?1 
-             ORDER BY start_time DESC"
-        )?;
-        
-        let rows = stmt.query_map(params![cutoff_time], |row| {
-            Ok(AppUsageSession {
-                id: Some(row.get(0)?),
-                app_name: row.get(1)?,
-                app_id: row.get(2)?,
-                window_title: row.get(3)?,
-                start_time: row.get(4)?,
-                end_time: row.get(5)?,
-                duration_seconds: row.get(6)?,
-                is_idle: row.get(7)?,
-                is_active: row.get(8)?,
-            })
-        })?;
+        let sessions = database::with_connection(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, app_name, app_id, window_title, 
+                        start_time, end_time, duration_seconds, is_idle, is_active
+                 FROM app_usage_sessions 
+                 WHERE start_time >= ?1 
+                 ORDER BY start_time DESC"
+            )?;
+
+            let rows = stmt.query_map(params![cutoff_time], |row| {
+                Ok(AppUsageSession {
+                    id: Some(row.get(0)?),
+                    app_name: row.get(1)?,
+                    app_id: row.get(2)?,
+                    window_title: row.get(3)?,
+                    start_time: row.get(4)?,
+                    end_time: row.get(5)?,
+                    duration_seconds: row.get(6)?,
+                    is_idle: row.get(7)?,
+                    is_active: row.get(8)?,
+                    segments: Vec::new(),
+                })
+            })?;
+
+            let mut sessions = Vec::new();
+            for row in rows {
+                sessions.push(row?);
+            }
+
+            let mut stmt = conn.prepare(
+                "SELECT s.session_id, s.window_title, s.domain, s.start_time, s.end_time, s.duration_seconds
+                 FROM app_usage_segments s
+                 JOIN app_usage_sessions a ON a.id = s.session_id
+                 WHERE a.start_time >= ?1
+                 ORDER BY s.start_time"
+            )?;
+            let rows = stmt.query_map(params![cutoff_time], |row| {
+                Ok((row.get::<_, i64>(0)?, ActivitySegment {
+                    window_title: row.get(1)?,
+                    domain: row.get(2)?,
+                    start_time: row.get(3)?,
+                    end_time: row.get(4)?,
+                    duration_seconds: row.get(5)?,
+                }))
+            })?;
+            for row in rows {
+                let (session_id, segment) = row?;
+                if let Some(session) = sessions.iter_mut().find(|session| session.id == Some(session_id)) {
+                    session.segments.push(segment);
+                }
+            }
+            Ok(sessions)
+        }).await?;
         
-        for row in rows {
-            let session = row?;
+        for session in sessions {
             if session.is_active {
                 self.current_session = Some(session);
             } else {
@@ -265,9 +440,14 @@ pub async fn end_current_session() -> Result<()> {
     tracker.end_current_session().await
 }
 
+pub async fn record_window_title(window_title: Option<String>) {
+    let mut tracker = APP_USAGE_TRACKER.lock().await;
+    tracker.record_window_title(window_title, Utc::now());
+}
+
 pub async fn get_current_session() -> Option<AppUsageSession> {
     let tracker = APP_USAGE_TRACKER.lock().await;
-    tracker.get_current_session().cloned()
+    tracker.current_session_snapshot()
 }
 
 
@@ -295,6 +475,7 @@ pub async fn reset_tracker() -> Result<()> {
         current.end_time = Some(now);
         current.duration_seconds = (now - current.start_time).num_seconds();
         current.is_active = false;
+        current.segments = tracker.finish_segments(now);
         
         
         // Save to database
@@ -319,6 +500,7 @@ pub async fn handle_system_wake(_sleep_duration_seconds: u64) -> Result<()> {
         session.end_time = Some(chrono::Utc::now());
         session.duration_seconds = (session.end_time.unwrap() - session.start_time).num_seconds() as i64;
         session.is_idle = true; // Mark as idle since system was sleeping
+        session.segments = tracker.finish_segments(session.end_time.unwrap());
         
         // Save the session
         tracker.save_session_to_db(&session).await?;
@@ -330,38 +512,75 @@ pub async fn handle_system_wake(_sleep_duration_seconds: u64) -> Result<()> {
     Ok(())
 }
 
-// Initialize database table for app usage sessions
-pub async fn init_database() -> Result<()> {
-    let conn = database::get_connection()?;
-    
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS app_usage_sessions (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            app_name TEXT NOT NULL,
-            app_id TEXT NOT NULL,
-            window_title TEXT,
-            start_time DATETIME NOT NULL,
-            end_time DATETIME,
-            duration_seconds INTEGER NOT NULL DEFAULT 0,
-            is_idle BOOLEAN NOT NULL DEFAULT 0,
-            is_active BOOLEAN NOT NULL DEFAULT 1,
-            synced BOOLEAN NOT NULL DEFAULT 0,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-        )",
-        [],
-    )?;
-    
-    // Create indexes for better performance
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_app_usage_app_name ON app_usage_sessions(app_name)",
-        [],
-    )?;
-    
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_app_usage_start_time ON app_usage_sessions(start_time)",
-        [],
-    )?;
-    
-    
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn titles(segments: &[ActivitySegment]) -> Vec<(&str, i64)> {
+        segments
+            .iter()
+            .map(|segment| (segment.window_title.as_deref().unwrap_or_default(), segment.duration_seconds))
+            .collect()
+    }
+
+    #[test]
+    fn test_short_detours_fold_into_the_segment_before() {
+        let start = Utc::now();
+        let at = |seconds| start + Duration::seconds(seconds);
+        let dwell = Duration::seconds(10);
+        let mut tracker = SegmentTracker::default();
+
+        let mut visit = |site: &str, seconds| tracker.observe(Some(site.to_string()), Some(site.to_string()), at(seconds), dwell);
+        visit("github.com", 0);
+        visit("news.example.com", 60);
+        visit("github.com", 63);
+        visit("docs.example.org", 120);
+
+        let segments = tracker.segments(at(200), dwell);
+        assert_eq!(titles(&segments), vec![("github.com", 120), ("docs.example.org", 80)]);
+        assert_eq!(segments[0].domain.as_deref(), Some("github.com"));
+        assert_eq!(segments[1].start_time, at(120));
+        assert_eq!(segments[1].end_time, Some(at(200)));
+    }
+
+    #[test]
+    fn test_short_first_and_last_segments_are_absorbed() {
+        let start = Utc::now();
+        let at = |seconds| start + Duration::seconds(seconds);
+        let dwell = Duration::seconds(10);
+        let mut tracker = SegmentTracker::default();
+
+        tracker.observe(Some("Untitled".to_string()), None, at(0), dwell);
+        tracker.observe(Some("Report".to_string()), None, at(2), dwell);
+        tracker.observe(Some("Budget".to_string()), None, at(30), dwell);
+
+        // The session starts with a segment and ends with the last long one
+        assert_eq!(titles(&tracker.segments(at(33), dwell)), vec![("Report", 33)]);
+    }
+
+    #[test]
+    fn test_titles_that_filter_alike_are_one_segment() {
+        let policy = PolicyConfig::default();
+        let start = Utc::now();
+        let dwell = Duration::seconds(10);
+        let mut tracker = SegmentTracker::default();
+
+        for (seconds, title) in [(0, "github.com - Pull request #1 - Google Chrome"), (30, "github.com - Pull request #2 - Google Chrome")] {
+            let title = filter_title(&policy, "com.google.Chrome", title);
+            let domain = site_in("com.google.Chrome", &title);
+            tracker.observe(Some(title), domain, start + Duration::seconds(seconds), dwell);
+        }
+
+        let segments = tracker.segments(start + Duration::seconds(60), dwell);
+        assert_eq!(titles(&segments), vec![("github.com", 60)]);
+        assert_eq!(segments[0].domain.as_deref(), Some("github.com"));
+    }
+
+    #[test]
+    fn test_file_names_in_other_apps_have_no_domain() {
+        let policy = PolicyConfig { title_redaction_enabled: false, ..PolicyConfig::default() };
+        let title = filter_title(&policy, "com.microsoft.Excel", "layoffs-q3.xlsx - Excel");
+        assert_eq!(title, "layoffs-q3.xlsx - Excel");
+        assert_eq!(site_in("com.microsoft.Excel", &title), None);
+    }
 }
@@ -13,51 +13,51 @@ pub struct ConsentRecord {
 }
 
 pub async fn accept_consent(version: &str) -> Result<()> {
-    let conn = database::get_connection()?;
-    
+    let version = version.to_string();
     let now = Utc::now().to_rfc3339();
     
     // Insert or update consent record
-    conn.execute(
-        "INSERT OR REPLACE INTO consent (id, accepted, version, accepted_at) 
-         VALUES (1, 1, ?1, ?2)",
-        params![version, now],
-    )?;
-    
-    Ok(())
+    database::with_connection(move |conn| {
+        conn.execute(
+            "INSERT OR REPLACE INTO consent (id, accepted, version, accepted_at) 
+             VALUES (1, 1, ?1, ?2)",
+            params![version, now],
+        )?;
+        Ok(())
+    }).await
 }
 
 pub async fn get_consent_status() -> Result<ConsentRecord> {
-    let conn = database::get_connection()?;
-    
-    let mut stmt = conn.prepare(
-        "SELECT accepted, version, accepted_at FROM consent WHERE id = 1"
-    )?;
-    
-    match stmt.query_row([], |row| {
-        let accepted: bool = row.get(0)?;
-        let version: String = row.get(1)?;
-        let accepted_at_str: Option<String> = row.get(2)?;
-        
-        let accepted_at = accepted_at_str
-            .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
-            .map(|dt| dt.with_timezone(&Utc));
-        
-        Ok(ConsentRecord {
-            accepted,
-            version,
-            accepted_at,
-        })
-    }) {
-        Ok(record) => Ok(record),
-        Err(rusqlite::Error::QueryReturnedNoRows) => {
-            // No consent record exists, return default
+    database::with_connection(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT accepted, version, accepted_at FROM consent WHERE id = 1"
+        )?;
+
+        match stmt.query_row([], |row| {
+            let accepted: bool = row.get(0)?;
+            let version: String = row.get(1)?;
+            let accepted_at_str: Option<String> = row.get(2)?;
+
+            let accepted_at = accepted_at_str
+                .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+                .map(|dt| dt.with_timezone(&Utc));
+
             Ok(ConsentRecord {
-                accepted: false,
-                version: "1.0.0".to_string(),
-                accepted_at: None,
+                accepted,
+                version,
+                accepted_at,
             })
+        }) {
+            Ok(record) => Ok(record),
+            Err(rusqlite::Error::QueryReturnedNoRows) => {
+                // No consent record exists, return default
+                Ok(ConsentRecord {
+                    accepted: false,
+                    version: "1.0.0".to_string(),
+                    accepted_at: None,
+                })
+            }
+            Err(e) => Err(e.into()),
         }
-        Err(e) => Err(e.into()),
-    }
-}
\ No newline at end of file
+    }).await
+}
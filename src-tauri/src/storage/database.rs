@@ -1,136 +1,421 @@
 use anyhow::Result;
 use rusqlite::Connection;
-use std::path::PathBuf;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::time::Duration;
 
-fn get_db_path() -> Result<PathBuf> {
-    let mut path = dirs::data_dir().ok_or_else(|| anyhow::anyhow!("Failed to get data directory"))?;
-    path.push("TrackEx");
-    
-    // Create directory with better error handling
-    if let Err(e) = std::fs::create_dir_all(&path) {
-        log::error!("Failed to create TrackEx data directory at {:?}: {}", path, e);
-        return Err(anyhow::anyhow!("Failed to create data directory: {}", e));
+// Connection pool tuning. SQLite only allows one writer at a time even in WAL
+// mode, so a small pool plus a busy timeout is enough for our background tasks.
+const POOL_MAX_SIZE: usize = 4;
+const POOL_CHECKOUT_TIMEOUT: Duration = Duration::from_secs(30);
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+const STATEMENT_CACHE_CAPACITY: usize = 32;
+
+const DB_FILE_NAME: &str = "agent.db";
+const MEMORY_DB: &str = ":memory:";
+
+lazy_static::lazy_static! {
+    static ref DATABASE_CONFIG: RwLock<Option<DatabaseConfig>> = RwLock::new(None);
+    static ref POOL: RwLock<Option<Arc<ConnectionPool>>> = RwLock::new(None);
+}
+
+/// Where the agent database lives
+#[derive(Debug, Clone, PartialEq)]
+pub enum DatabaseLocation {
+    /// Default per-user location: `<data dir>/TrackEx/agent.db`
+    Default,
+    /// A specific database file
+    File(PathBuf),
+    /// Private in-memory database, discarded when the pool is dropped
+    InMemory,
+}
+
+/// Database settings, resolved once at startup (or per test) and applied with `configure`
+#[derive(Debug, Clone)]
+pub struct DatabaseConfig {
+    pub location: DatabaseLocation,
+    pub pool_size: usize,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            location: DatabaseLocation::Default,
+            pool_size: POOL_MAX_SIZE,
+        }
     }
-    
-    path.push("agent.db");
-    log::info!("Database path: {:?}", path);
-    Ok(path)
 }
 
-pub async fn init() -> Result<()> {
-    log::info!("Initializing database...");
-    let db_path = get_db_path()?;
-    log::info!("Opening database connection at {:?}", db_path);
-    let conn = Connection::open(&db_path)?;
-    log::info!("Database connection opened successfully");
-    
-    // Create tables
-    log::info!("Creating database tables...");
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS consent (
-            id INTEGER PRIMARY KEY,
-            accepted BOOLEAN NOT NULL DEFAULT 0,
-            version TEXT NOT NULL,
-            accepted_at DATETIME,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-        )",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS event_queue (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            event_type TEXT NOT NULL,
-            event_data TEXT NOT NULL,
-            timestamp DATETIME NOT NULL,
-            processed BOOLEAN NOT NULL DEFAULT 0,
-            retry_count INTEGER NOT NULL DEFAULT 0,
-            max_retries INTEGER NOT NULL DEFAULT 3,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-        )",
-        [],
-    )?;
-
-            conn.execute(
-                "CREATE TABLE IF NOT EXISTS heartbeat_queue (
-                    id INTEGER PRIMARY KEY AUTOINCREMENT,
-                    heartbeat_data TEXT NOT NULL,
-                    timestamp DATETIME NOT NULL,
-                    processed BOOLEAN NOT NULL DEFAULT 0,
-                    retry_count INTEGER NOT NULL DEFAULT 0,
-                    max_retries INTEGER NOT NULL DEFAULT 3,
-                    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-                )",
-                [],
-            )?;
+impl DatabaseConfig {
+    /// Read the database location from the environment.
+    /// `TRACKEX_DB_PATH` (a file path or `:memory:`) wins over `TRACKEX_DATA_DIR`.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
 
-            conn.execute(
-                "CREATE TABLE IF NOT EXISTS app_usage_sessions (
-                    id INTEGER PRIMARY KEY AUTOINCREMENT,
-                    app_name TEXT NOT NULL,
-                    app_id TEXT NOT NULL,
-                    window_title TEXT,
-                    start_time DATETIME NOT NULL,
-                    end_time DATETIME,
-                    duration_seconds INTEGER NOT NULL DEFAULT 0,
-                    is_idle BOOLEAN NOT NULL DEFAULT 0,
-                    is_active BOOLEAN NOT NULL DEFAULT 1,
-                    synced BOOLEAN NOT NULL DEFAULT 0,
-                    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-                )",
-                [],
-            )?;
+        if let Ok(val) = std::env::var("TRACKEX_DATA_DIR") {
+            if !val.trim().is_empty() {
+                config = config.with_data_dir(val.trim());
+            }
+        }
 
-            // Migration: Recreate app_usage_sessions table with correct schema
-            // This ensures the table has the right structure for the app usage tracker
-            let table_exists = conn.query_row(
-                "SELECT name FROM sqlite_master WHERE type='table' AND name='app_usage_sessions'",
-                [],
-                |row| Ok(row.get::<_, String>(0)?)
-            ).is_ok();
-
-            if table_exists {
-                
-                // Drop existing table (data will be lost, but this is for development)
-                conn.execute("DROP TABLE app_usage_sessions", [])?;
-                
-                // Recreate with correct schema including synced column
-                conn.execute(
-                    "CREATE TABLE app_usage_sessions (
-                        id INTEGER PRIMARY KEY AUTOINCREMENT,
-                        app_name TEXT NOT NULL,
-                        app_id TEXT NOT NULL,
-                        window_title TEXT,
-                        start_time DATETIME NOT NULL,
-                        end_time DATETIME,
-                        duration_seconds INTEGER NOT NULL DEFAULT 0,
-                        is_idle BOOLEAN NOT NULL DEFAULT 0,
-                        is_active BOOLEAN NOT NULL DEFAULT 1,
-                        synced BOOLEAN NOT NULL DEFAULT 0,
-                        created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-                    )",
-                    [],
-                )?;
-                
+        if let Ok(val) = std::env::var("TRACKEX_DB_PATH") {
+            if !val.trim().is_empty() {
+                config = config.with_db_path(val.trim());
             }
+        }
 
-            conn.execute(
-                "CREATE TABLE IF NOT EXISTS work_sessions (
-                    id INTEGER PRIMARY KEY AUTOINCREMENT,
-                    started_at DATETIME NOT NULL,
-                    ended_at DATETIME,
-                    is_active BOOLEAN NOT NULL DEFAULT 1,
-                    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-                )",
-                [],
-            )?;
+        config
+    }
+
+    /// Apply `--data-dir <dir>` / `--db-path <file|:memory:>` command line overrides
+    pub fn with_cli_args<I: IntoIterator<Item = String>>(mut self, args: I) -> Self {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+
+            if flag != "--data-dir" && flag != "--db-path" {
+                continue;
+            }
+
+            let Some(value) = inline_value.or_else(|| args.next()) else {
+                log::warn!("Missing value for {} command line flag", flag);
+                continue;
+            };
+
+            self = if flag == "--data-dir" {
+                self.with_data_dir(value)
+            } else {
+                self.with_db_path(value)
+            };
+        }
+        self
+    }
+
+    pub fn in_memory() -> Self {
+        Self {
+            location: DatabaseLocation::InMemory,
+            // Every in-memory connection is its own database, so keep exactly one
+            pool_size: 1,
+        }
+    }
+
+    pub fn with_data_dir<P: AsRef<Path>>(self, dir: P) -> Self {
+        self.with_db_path(dir.as_ref().join(DB_FILE_NAME))
+    }
+
+    pub fn with_db_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        if path.as_ref().as_os_str() == MEMORY_DB {
+            return Self::in_memory();
+        }
+        self.location = DatabaseLocation::File(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Resolve the concrete database file, creating its parent directory.
+    /// Returns None for in-memory databases.
+    fn resolve_path(&self) -> Result<Option<PathBuf>> {
+        let path = match &self.location {
+            DatabaseLocation::InMemory => return Ok(None),
+            DatabaseLocation::File(path) => path.clone(),
+            DatabaseLocation::Default => {
+                let mut path = dirs::data_dir().ok_or_else(|| anyhow::anyhow!("Failed to get data directory"))?;
+                path.push("TrackEx");
+                path.push(DB_FILE_NAME);
+                path
+            }
+        };
+
+        // Create directory with better error handling
+        if let Some(parent) = path.parent() {
+            if let Err(e) = std::fs::create_dir_all(parent) {
+                log::error!("Failed to create TrackEx data directory at {:?}: {}", parent, e);
+                return Err(anyhow::anyhow!("Failed to create data directory: {}", e));
+            }
+        }
+
+        Ok(Some(path))
+    }
+}
+
+/// Set the database location used by all storage calls.
+/// Replaces any existing pool, so calling it again (e.g. from tests) starts from a fresh database handle.
+pub fn configure(config: DatabaseConfig) {
+    log::info!("Database location configured: {:?}", config.location);
+    if let Ok(mut current) = DATABASE_CONFIG.write() {
+        *current = Some(config);
+    }
+    if let Ok(mut pool) = POOL.write() {
+        *pool = None;
+    }
+}
+
+fn current_config() -> DatabaseConfig {
+    DATABASE_CONFIG
+        .read()
+        .ok()
+        .and_then(|config| config.clone())
+        .unwrap_or_else(DatabaseConfig::from_env)
+}
+
+/// Where the database lives (None for in-memory), e.g. for diagnostics
+pub fn database_path() -> Result<Option<PathBuf>> {
+    current_config().resolve_path()
+}
+
+struct PoolState {
+    idle: Vec<Connection>,
+    open: usize,
+}
+
+/// Small blocking pool of SQLite connections sharing one database
+pub struct ConnectionPool {
+    path: Option<PathBuf>,
+    max_size: usize,
+    state: Mutex<PoolState>,
+    available: Condvar,
+}
+
+impl ConnectionPool {
+    fn new(path: Option<PathBuf>, max_size: usize) -> Self {
+        Self {
+            path,
+            max_size,
+            state: Mutex::new(PoolState { idle: Vec::new(), open: 0 }),
+            available: Condvar::new(),
+        }
+    }
+
+    fn open_connection(&self) -> Result<Connection> {
+        let conn = match &self.path {
+            Some(path) => Connection::open(path)?,
+            None => Connection::open_in_memory()?,
+        };
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        // Off by default in SQLite; the schema relies on it to cascade deletes
+        conn.pragma_update(None, "foreign_keys", "ON")?;
+        // Only takes effect on a brand new database; existing files are converted by the retention pass
+        conn.pragma_update(None, "auto_vacuum", "INCREMENTAL")?;
+        if self.path.is_some() {
+            // journal_mode returns the resulting mode as a row, so it can't go through execute()
+            let mode: String = conn.query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))?;
+            if !mode.eq_ignore_ascii_case("wal") {
+                log::warn!("SQLite WAL mode not available, using journal_mode={}", mode);
+            }
+            conn.pragma_update(None, "synchronous", "NORMAL")?;
+        }
+        conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+        Ok(conn)
+    }
+
+    fn get(self: &Arc<Self>) -> Result<PooledConnection> {
+        let mut state = self.state.lock().map_err(|_| anyhow::anyhow!("Connection pool lock poisoned"))?;
+        loop {
+            if let Some(conn) = state.idle.pop() {
+                return Ok(PooledConnection { conn: Some(conn), pool: Arc::clone(self) });
+            }
+
+            if state.open < self.max_size {
+                state.open += 1;
+                drop(state);
+                return match self.open_connection() {
+                    Ok(conn) => Ok(PooledConnection { conn: Some(conn), pool: Arc::clone(self) }),
+                    Err(e) => {
+                        self.release_slot();
+                        Err(e)
+                    }
+                };
+            }
+
+            let (guard, timeout) = self.available
+                .wait_timeout(state, POOL_CHECKOUT_TIMEOUT)
+                .map_err(|_| anyhow::anyhow!("Connection pool lock poisoned"))?;
+            state = guard;
+            if timeout.timed_out() && state.idle.is_empty() && state.open >= self.max_size {
+                return Err(anyhow::anyhow!("Timed out waiting for a database connection"));
+            }
+        }
+    }
 
-    log::info!("Database initialized successfully");
+    fn put_back(&self, conn: Connection) {
+        if let Ok(mut state) = self.state.lock() {
+            state.idle.push(conn);
+        }
+        self.available.notify_one();
+    }
+
+    fn release_slot(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.open = state.open.saturating_sub(1);
+        }
+        self.available.notify_one();
+    }
+}
+
+/// A connection checked out of the pool; returned automatically on drop
+pub struct PooledConnection {
+    conn: Option<Connection>,
+    pool: Arc<ConnectionPool>,
+}
+
+impl Deref for PooledConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("pooled connection already released")
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn.as_mut().expect("pooled connection already released")
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool.put_back(conn);
+        }
+    }
+}
+
+fn get_pool() -> Result<Arc<ConnectionPool>> {
+    if let Some(pool) = POOL.read().ok().and_then(|pool| pool.clone()) {
+        return Ok(pool);
+    }
+
+    let mut pool = POOL.write().map_err(|_| anyhow::anyhow!("Connection pool lock poisoned"))?;
+    if let Some(pool) = pool.as_ref() {
+        return Ok(Arc::clone(pool));
+    }
+
+    let config = current_config();
+    let db_path = config.resolve_path()?;
+    match &db_path {
+        Some(path) => log::info!("Database path: {:?}", path),
+        None => log::info!("Database path: in-memory"),
+    }
+
+    let new_pool = Arc::new(ConnectionPool::new(db_path, config.pool_size));
+    *pool = Some(Arc::clone(&new_pool));
+    Ok(new_pool)
+}
+
+pub async fn init() -> Result<()> {
+    log::info!("Initializing database...");
+
+    // All schema changes live in the versioned migration list
+    let version = with_connection(|conn| {
+        super::migrations::run(conn)?;
+        super::migrations::current_version(conn)
+    }).await?;
+
+    log::info!("Database initialized successfully (schema version {})", version);
     Ok(())
 }
 
-pub fn get_connection() -> Result<Connection> {
-    let db_path = get_db_path()?;
-    let conn = Connection::open(&db_path)?;
-    Ok(conn)
-}
\ No newline at end of file
+/// Check a connection out of the shared pool.
+/// This blocks while the pool is exhausted, so async code should prefer `with_connection`.
+pub fn get_connection() -> Result<PooledConnection> {
+    get_pool()?.get()
+}
+
+/// Run a database operation on the blocking thread pool so SQLite I/O never
+/// stalls the async executor
+pub async fn with_connection<F, T>(f: F) -> Result<T>
+where
+    F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(move || {
+        let mut conn = get_connection()?;
+        f(&mut conn)
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Database task failed: {}", e))?
+}
+
+// The pool is process-wide, so tests that go through it take turns
+#[cfg(test)]
+static TEST_DATABASE: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Point the storage calls at a fresh in-memory database for as long as the guard is held
+#[cfg(test)]
+pub(crate) async fn test_database() -> tokio::sync::MutexGuard<'static, ()> {
+    let guard = TEST_DATABASE.lock().await;
+    configure(DatabaseConfig::in_memory());
+    init().await.unwrap();
+    guard
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_cli_data_dir() {
+        let config = DatabaseConfig::default().with_cli_args(args(&["--data-dir", "/tmp/trackex-a"]));
+        assert_eq!(config.location, DatabaseLocation::File(PathBuf::from("/tmp/trackex-a/agent.db")));
+
+        let config = DatabaseConfig::default().with_cli_args(args(&["--data-dir=/tmp/trackex-b"]));
+        assert_eq!(config.location, DatabaseLocation::File(PathBuf::from("/tmp/trackex-b/agent.db")));
+    }
+
+    #[test]
+    fn test_cli_memory_db_path() {
+        let config = DatabaseConfig::default().with_cli_args(args(&["--verbose", "--db-path", ":memory:"]));
+        assert_eq!(config.location, DatabaseLocation::InMemory);
+        assert_eq!(config.pool_size, 1);
+    }
+
+    #[test]
+    fn test_in_memory_pool_reuses_database() {
+        let config = DatabaseConfig::in_memory();
+        let pool = Arc::new(ConnectionPool::new(config.resolve_path().unwrap(), config.pool_size));
+
+        pool.get().unwrap().execute_batch("CREATE TABLE t (x INTEGER); INSERT INTO t VALUES (1);").unwrap();
+
+        let count: i64 = pool.get().unwrap().query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_data_dir_database() {
+        let _guard = TEST_DATABASE.lock().await;
+        let dir = tempfile::tempdir().unwrap();
+        configure(DatabaseConfig::default().with_data_dir(dir.path()));
+        init().await.unwrap();
+
+        crate::storage::offline_queue::queue_event("test_event", &serde_json::json!({"test": "data"})).await.unwrap();
+
+        assert!(dir.path().join(DB_FILE_NAME).exists());
+        assert_eq!(database_path().unwrap(), Some(dir.path().join(DB_FILE_NAME)));
+        assert_eq!(crate::storage::offline_queue::get_pending_events().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_deleting_a_session_deletes_its_segments() {
+        let _guard = test_database().await;
+
+        let segments = with_connection(|conn| {
+            conn.execute_batch(
+                "INSERT INTO app_usage_sessions (app_name, app_id, start_time) VALUES ('Editor', 'editor', CURRENT_TIMESTAMP);
+                 INSERT INTO app_usage_segments (session_id, start_time) VALUES (last_insert_rowid(), CURRENT_TIMESTAMP);
+                 DELETE FROM app_usage_sessions;",
+            )?;
+            Ok(conn.query_row("SELECT COUNT(*) FROM app_usage_segments", [], |row| row.get::<_, i64>(0))?)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(segments, 0);
+    }
+}
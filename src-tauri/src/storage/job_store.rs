@@ -0,0 +1,236 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::Value;
+
+use super::database;
+
+// Journal of every job the server has sent us. A job is recorded before it runs,
+// so the same job ID is never run twice (even across restarts), and a job that
+// was interrupted by a shutdown is picked up again on the next start.
+
+/// Runs allowed per job; a job interrupted more often than this is failed
+pub const MAX_JOB_ATTEMPTS: i32 = 3;
+// Finished and reported jobs are kept this long for troubleshooting
+const JOB_RETENTION_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Received,
+    InProgress,
+    Succeeded,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Received => "received",
+            JobStatus::InProgress => "in_progress",
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Failed => "failed",
+        }
+    }
+
+    fn parse(status: &str) -> Self {
+        match status {
+            "in_progress" => JobStatus::InProgress,
+            "succeeded" => JobStatus::Succeeded,
+            "failed" => JobStatus::Failed,
+            _ => JobStatus::Received,
+        }
+    }
+
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Succeeded | JobStatus::Failed)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StoredJob {
+    pub job_id: String,
+    pub job_type: String,
+    pub payload: Value,
+    pub status: JobStatus,
+    pub attempts: i32,
+    pub result: Option<Value>,
+    pub error: Option<String>,
+}
+
+const JOB_COLUMNS: &str = "job_id, job_type, payload, status, attempts, result, error";
+
+fn job_from_row(row: &rusqlite::Row) -> rusqlite::Result<StoredJob> {
+    let payload: String = row.get(2)?;
+    let status: String = row.get(3)?;
+    let result: Option<String> = row.get(5)?;
+    Ok(StoredJob {
+        job_id: row.get(0)?,
+        job_type: row.get(1)?,
+        payload: serde_json::from_str(&payload).unwrap_or(Value::Null),
+        status: JobStatus::parse(&status),
+        attempts: row.get(4)?,
+        result: result.and_then(|r| serde_json::from_str(&r).ok()),
+        error: row.get(6)?,
+    })
+}
+
+/// Record a newly received job. Returns false if the job ID is already known.
+pub fn insert_received(conn: &Connection, job_id: &str, job_type: &str, payload: &Value, now: DateTime<Utc>) -> Result<bool> {
+    let inserted = conn
+        .prepare_cached(
+            "INSERT OR IGNORE INTO jobs (job_id, job_type, payload, status, received_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?
+        .execute(params![job_id, job_type, payload.to_string(), JobStatus::Received.as_str(), now])?;
+    Ok(inserted == 1)
+}
+
+/// Mark a job as running and count the attempt. Returns the attempt number.
+pub fn start(conn: &Connection, job_id: &str, now: DateTime<Utc>) -> Result<i32> {
+    conn.prepare_cached(
+        "UPDATE jobs SET status = ?2, attempts = attempts + 1, started_at = ?3 WHERE job_id = ?1",
+    )?
+    .execute(params![job_id, JobStatus::InProgress.as_str(), now])?;
+    let attempts = conn
+        .prepare_cached("SELECT attempts FROM jobs WHERE job_id = ?1")?
+        .query_row(params![job_id], |row| row.get(0))
+        .optional()?
+        .unwrap_or(1);
+    Ok(attempts)
+}
+
+pub fn finish(conn: &Connection, job_id: &str, outcome: &std::result::Result<Value, String>, now: DateTime<Utc>) -> Result<()> {
+    let (status, result, error) = match outcome {
+        Ok(result) => (JobStatus::Succeeded, Some(result.to_string()), None),
+        Err(error) => (JobStatus::Failed, None, Some(error.as_str())),
+    };
+    conn.prepare_cached(
+        "UPDATE jobs SET status = ?2, result = ?3, error = ?4, finished_at = ?5, reported_at = NULL WHERE job_id = ?1",
+    )?
+    .execute(params![job_id, status.as_str(), result, error, now])?;
+    Ok(())
+}
+
+/// The server has acknowledged the job's final status
+pub fn mark_reported(conn: &Connection, job_id: &str, now: DateTime<Utc>) -> Result<()> {
+    conn.prepare_cached("UPDATE jobs SET reported_at = ?2 WHERE job_id = ?1")?
+        .execute(params![job_id, now])?;
+    Ok(())
+}
+
+/// Jobs that still have to run or whose final status hasn't reached the server
+pub fn outstanding(conn: &Connection) -> Result<Vec<StoredJob>> {
+    let sql = format!(
+        "SELECT {} FROM jobs
+         WHERE status IN ('received', 'in_progress') OR reported_at IS NULL
+         ORDER BY received_at ASC",
+        JOB_COLUMNS
+    );
+    let jobs = conn
+        .prepare_cached(&sql)?
+        .query_map([], job_from_row)?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(jobs)
+}
+
+pub fn purge_reported(conn: &Connection, now: DateTime<Utc>) -> Result<usize> {
+    let cutoff = now - chrono::Duration::days(JOB_RETENTION_DAYS);
+    Ok(conn.execute(
+        "DELETE FROM jobs WHERE reported_at IS NOT NULL AND reported_at < ?1",
+        params![cutoff],
+    )?)
+}
+
+pub async fn record_received(job_id: &str, job_type: &str, payload: &Value) -> Result<bool> {
+    let (job_id, job_type, payload) = (job_id.to_string(), job_type.to_string(), payload.clone());
+    database::with_connection(move |conn| insert_received(conn, &job_id, &job_type, &payload, Utc::now())).await
+}
+
+pub async fn record_started(job_id: &str) -> Result<i32> {
+    let job_id = job_id.to_string();
+    database::with_connection(move |conn| start(conn, &job_id, Utc::now())).await
+}
+
+pub async fn record_finished(job_id: &str, outcome: std::result::Result<Value, String>) -> Result<()> {
+    let job_id = job_id.to_string();
+    database::with_connection(move |conn| finish(conn, &job_id, &outcome, Utc::now())).await
+}
+
+pub async fn record_reported(job_id: &str) -> Result<()> {
+    let job_id = job_id.to_string();
+    database::with_connection(move |conn| mark_reported(conn, &job_id, Utc::now())).await
+}
+
+pub async fn get_outstanding() -> Result<Vec<StoredJob>> {
+    database::with_connection(|conn| outstanding(conn)).await
+}
+
+pub async fn purge_old_jobs() -> Result<usize> {
+    database::with_connection(|conn| purge_reported(conn, Utc::now())).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn setup() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        super::super::migrations::run(&mut conn).unwrap();
+        conn
+    }
+
+    fn get(conn: &Connection, job_id: &str) -> Option<StoredJob> {
+        let sql = format!("SELECT {} FROM jobs WHERE job_id = ?1", JOB_COLUMNS);
+        conn.query_row(&sql, params![job_id], job_from_row).optional().unwrap()
+    }
+
+    #[test]
+    fn test_job_ids_are_deduplicated() {
+        let conn = setup();
+        let now = Utc::now();
+        assert!(insert_received(&conn, "job-1", "screenshot", &json!({}), now).unwrap());
+        assert!(!insert_received(&conn, "job-1", "screenshot", &json!({}), now).unwrap());
+    }
+
+    #[test]
+    fn test_lifecycle_and_outstanding_jobs() {
+        let conn = setup();
+        let now = Utc::now();
+        insert_received(&conn, "done", "screenshot", &json!({}), now).unwrap();
+        insert_received(&conn, "interrupted", "screenshot", &json!({"quality": 80}), now).unwrap();
+        insert_received(&conn, "waiting", "diagnostics", &json!({}), now).unwrap();
+
+        assert_eq!(start(&conn, "done", now).unwrap(), 1);
+        finish(&conn, "done", &Ok(json!({"ok": true})), now).unwrap();
+        assert_eq!(start(&conn, "interrupted", now).unwrap(), 1);
+
+        // Finished but not yet reported stays outstanding
+        let ids: Vec<String> = outstanding(&conn).unwrap().into_iter().map(|j| j.job_id).collect();
+        assert_eq!(ids, vec!["done", "interrupted", "waiting"]);
+
+        mark_reported(&conn, "done", now).unwrap();
+        let jobs = outstanding(&conn).unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].status, JobStatus::InProgress);
+        assert_eq!(jobs[0].payload, json!({"quality": 80}));
+
+        let done = get(&conn, "done").unwrap();
+        assert_eq!(done.status, JobStatus::Succeeded);
+        assert_eq!(done.result, Some(json!({"ok": true})));
+    }
+
+    #[test]
+    fn test_failed_jobs_keep_error_and_old_reported_jobs_are_purged() {
+        let conn = setup();
+        let long_ago = Utc::now() - chrono::Duration::days(30);
+        insert_received(&conn, "old", "screenshot", &json!({}), long_ago).unwrap();
+        start(&conn, "old", long_ago).unwrap();
+        finish(&conn, "old", &Err("capture failed".to_string()), long_ago).unwrap();
+
+        assert_eq!(get(&conn, "old").unwrap().error.as_deref(), Some("capture failed"));
+
+        mark_reported(&conn, "old", long_ago).unwrap();
+        assert_eq!(purge_reported(&conn, Utc::now()).unwrap(), 1);
+        assert!(get(&conn, "old").is_none());
+    }
+}
@@ -0,0 +1,314 @@
+use anyhow::Result;
+use rusqlite::Connection;
+
+/// A single schema change. Migrations are applied in order and each one runs
+/// inside its own transaction, so a failure leaves the database at the last
+/// fully applied version.
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+// Ordered list of all schema migrations. Never edit a migration that has
+// already shipped - append a new one instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        // IF NOT EXISTS so databases created before versioning was introduced
+        // (user_version = 0) are adopted without losing data
+        sql: "
+            CREATE TABLE IF NOT EXISTS consent (
+                id INTEGER PRIMARY KEY,
+                accepted BOOLEAN NOT NULL DEFAULT 0,
+                version TEXT NOT NULL,
+                accepted_at DATETIME,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS event_queue (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                event_type TEXT NOT NULL,
+                event_data TEXT NOT NULL,
+                timestamp DATETIME NOT NULL,
+                processed BOOLEAN NOT NULL DEFAULT 0,
+                retry_count INTEGER NOT NULL DEFAULT 0,
+                max_retries INTEGER NOT NULL DEFAULT 3,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS heartbeat_queue (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                heartbeat_data TEXT NOT NULL,
+                timestamp DATETIME NOT NULL,
+                processed BOOLEAN NOT NULL DEFAULT 0,
+                retry_count INTEGER NOT NULL DEFAULT 0,
+                max_retries INTEGER NOT NULL DEFAULT 3,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS app_usage_sessions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                app_name TEXT NOT NULL,
+                app_id TEXT NOT NULL,
+                window_title TEXT,
+                start_time DATETIME NOT NULL,
+                end_time DATETIME,
+                duration_seconds INTEGER NOT NULL DEFAULT 0,
+                is_idle BOOLEAN NOT NULL DEFAULT 0,
+                is_active BOOLEAN NOT NULL DEFAULT 1,
+                synced BOOLEAN NOT NULL DEFAULT 0,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_app_usage_app_name ON app_usage_sessions(app_name);
+            CREATE INDEX IF NOT EXISTS idx_app_usage_start_time ON app_usage_sessions(start_time);
+
+            CREATE TABLE IF NOT EXISTS work_sessions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                started_at DATETIME NOT NULL,
+                ended_at DATETIME,
+                is_active BOOLEAN NOT NULL DEFAULT 1,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+        ",
+    },
+    Migration {
+        version: 2,
+        description: "unified outbox with client-generated event ids",
+        // Undelivered rows from the old per-kind queues are carried over with a
+        // freshly generated UUID v4 so they still get an idempotency key
+        sql: "
+            CREATE TABLE outbox (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                event_id TEXT NOT NULL UNIQUE,
+                kind TEXT NOT NULL,
+                event_type TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                timestamp DATETIME NOT NULL,
+                retry_count INTEGER NOT NULL DEFAULT 0,
+                max_retries INTEGER NOT NULL DEFAULT 3,
+                delivered_at DATETIME,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE INDEX idx_outbox_pending ON outbox(kind, delivered_at, timestamp);
+
+            INSERT INTO outbox (event_id, kind, event_type, payload, timestamp, retry_count, max_retries)
+            SELECT
+                lower(hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4' || substr(hex(randomblob(2)), 2) || '-'
+                    || substr('89ab', 1 + (abs(random()) % 4), 1) || substr(hex(randomblob(2)), 2) || '-' || hex(randomblob(6))),
+                'event', event_type, event_data, timestamp, retry_count, max_retries
+            FROM event_queue
+            WHERE processed = 0;
+
+            INSERT INTO outbox (event_id, kind, event_type, payload, timestamp, retry_count, max_retries)
+            SELECT
+                lower(hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4' || substr(hex(randomblob(2)), 2) || '-'
+                    || substr('89ab', 1 + (abs(random()) % 4), 1) || substr(hex(randomblob(2)), 2) || '-' || hex(randomblob(6))),
+                'heartbeat', 'heartbeat', heartbeat_data, timestamp, retry_count, max_retries
+            FROM heartbeat_queue
+            WHERE processed = 0;
+
+            DROP TABLE event_queue;
+            DROP TABLE heartbeat_queue;
+        ",
+    },
+    Migration {
+        version: 3,
+        description: "retry schedule and dead-letter table",
+        // Pending rows get the larger retry budget that comes with backoff, which also
+        // revives rows the old 3-attempt limit had abandoned
+        sql: "
+            ALTER TABLE outbox ADD COLUMN next_attempt_at DATETIME;
+            ALTER TABLE outbox ADD COLUMN last_error TEXT;
+            UPDATE outbox SET max_retries = 10 WHERE delivered_at IS NULL;
+
+            CREATE TABLE dead_letter (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                event_id TEXT NOT NULL UNIQUE,
+                kind TEXT NOT NULL,
+                event_type TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                timestamp DATETIME NOT NULL,
+                retry_count INTEGER NOT NULL DEFAULT 0,
+                last_error TEXT,
+                failed_at DATETIME NOT NULL
+            );
+        ",
+    },
+    Migration {
+        version: 4,
+        description: "outbox delivery leases",
+        sql: "
+            ALTER TABLE outbox ADD COLUMN claimed_by TEXT;
+            ALTER TABLE outbox ADD COLUMN lease_until DATETIME;
+        ",
+    },
+    Migration {
+        version: 5,
+        description: "job journal",
+        sql: "
+            CREATE TABLE jobs (
+                job_id TEXT PRIMARY KEY,
+                job_type TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                status TEXT NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                result TEXT,
+                error TEXT,
+                received_at DATETIME NOT NULL,
+                started_at DATETIME,
+                finished_at DATETIME,
+                reported_at DATETIME
+            );
+
+            CREATE INDEX idx_jobs_status ON jobs(status, reported_at);
+        ",
+    },
+    Migration {
+        version: 6,
+        description: "cached server policy",
+        sql: "
+            CREATE TABLE policy_cache (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                policy TEXT NOT NULL,
+                etag TEXT,
+                fetched_at DATETIME NOT NULL
+            );
+        ",
+    },
+    Migration {
+        version: 7,
+        description: "sub-activity segments of app sessions",
+        sql: "
+            CREATE TABLE app_usage_segments (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id INTEGER NOT NULL REFERENCES app_usage_sessions(id) ON DELETE CASCADE,
+                window_title TEXT,
+                domain TEXT,
+                start_time DATETIME NOT NULL,
+                end_time DATETIME,
+                duration_seconds INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE INDEX idx_app_usage_segments_session ON app_usage_segments(session_id);
+        ",
+    },
+];
+
+/// Latest schema version this binary knows how to work with
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+pub fn current_version(conn: &Connection) -> Result<i64> {
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    Ok(version)
+}
+
+/// Bring the database up to `latest_version()`.
+/// Refuses to touch a database written by a newer agent, since downgrading
+/// the schema in place could silently drop data.
+pub fn run(conn: &mut Connection) -> Result<()> {
+    let current = current_version(conn)?;
+    let latest = latest_version();
+
+    if current > latest {
+        return Err(anyhow::anyhow!(
+            "Database schema version {} is newer than this agent supports ({}). Please update TrackEx Agent.",
+            current,
+            latest
+        ));
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        log::info!("Applying database migration {}: {}", migration.version, migration.description);
+
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql)
+            .map_err(|e| anyhow::anyhow!("Migration {} ({}) failed: {}", migration.version, migration.description, e))?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_are_ordered() {
+        let mut last = 0;
+        for migration in MIGRATIONS {
+            assert!(migration.version > last, "migration {} is out of order", migration.version);
+            last = migration.version;
+        }
+    }
+
+    #[test]
+    fn test_run_on_fresh_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        run(&mut conn).unwrap();
+
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        conn.execute("INSERT INTO work_sessions (started_at) VALUES (CURRENT_TIMESTAMP)", []).unwrap();
+        conn.execute(
+            "INSERT INTO outbox (event_id, kind, event_type, payload, timestamp) VALUES ('id-1', 'event', 'app_focus', '{}', CURRENT_TIMESTAMP)",
+            [],
+        ).unwrap();
+    }
+
+    #[test]
+    fn test_run_is_idempotent_and_keeps_data() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        run(&mut conn).unwrap();
+        conn.execute(
+            "INSERT INTO app_usage_sessions (app_name, app_id, start_time) VALUES ('Editor', 'editor', CURRENT_TIMESTAMP)",
+            [],
+        ).unwrap();
+
+        run(&mut conn).unwrap();
+
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM app_usage_sessions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_outbox_migration_carries_pending_rows() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let tx = conn.transaction().unwrap();
+        tx.execute_batch(MIGRATIONS[0].sql).unwrap();
+        tx.pragma_update(None, "user_version", 1).unwrap();
+        tx.commit().unwrap();
+
+        conn.execute_batch(
+            "INSERT INTO event_queue (event_type, event_data, timestamp) VALUES ('app_focus', '{}', CURRENT_TIMESTAMP);
+             INSERT INTO event_queue (event_type, event_data, timestamp, processed) VALUES ('app_focus', '{}', CURRENT_TIMESTAMP, 1);
+             INSERT INTO heartbeat_queue (heartbeat_data, timestamp) VALUES ('{}', CURRENT_TIMESTAMP);",
+        ).unwrap();
+
+        run(&mut conn).unwrap();
+
+        let (rows, distinct_ids, id_len): (i64, i64, i64) = conn
+            .query_row("SELECT COUNT(*), COUNT(DISTINCT event_id), MIN(length(event_id)) FROM outbox", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap();
+        assert_eq!(rows, 2);
+        assert_eq!(distinct_ids, 2);
+        assert_eq!(id_len, 36);
+    }
+
+    #[test]
+    fn test_refuses_newer_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", latest_version() + 1).unwrap();
+
+        assert!(run(&mut conn).is_err());
+    }
+}
@@ -1,8 +1,12 @@
 pub mod consent;
 pub mod database;
+pub mod job_store;
+pub mod migrations;
 pub mod secure_store;
 pub mod work_session;
 pub mod offline_queue;
+pub mod policy_cache;
+pub mod retention;
 pub mod app_usage;
 
 use anyhow::Result;
@@ -76,9 +80,6 @@ impl AppState {
         // Initialize database
         database::init().await?;
         
-        // Initialize app usage tracking
-        app_usage::init_database().await?;
-        
         // Load recent app usage sessions
         app_usage::load_recent_sessions(24).await?; // Load last 24 hours
         
@@ -2,17 +2,35 @@
 
 use anyhow::Result;
 use chrono::{DateTime, Utc};
-use rusqlite::params;
+use rand::Rng;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
 use serde_json::Value;
 
 use super::database;
+use crate::api::error::{DeliveryError, FailureKind};
+
+// Rows returned by the read-only pending getters
+const DEFAULT_FETCH_LIMIT: usize = 10;
+
+// Retry schedule: exponential backoff from BACKOFF_BASE_SECS up to BACKOFF_MAX_SECS,
+// then the item is moved to the dead-letter table instead of being dropped
+const MAX_ATTEMPTS: i32 = 10;
+const BACKOFF_BASE_SECS: i64 = 5;
+const BACKOFF_MAX_SECS: i64 = 60 * 60;
+
+// Outbox `kind` values
+const KIND_EVENT: &str = "event";
+const KIND_HEARTBEAT: &str = "heartbeat";
 
 #[derive(Debug)]
 #[allow(dead_code)]
 pub struct QueuedEvent {
     pub id: i64,
+    pub event_id: String,
     pub event_type: String,
     pub event_data: Value,
+    /// When the event happened on this machine, not when it was queued or sent
     pub timestamp: DateTime<Utc>,
     pub retry_count: i32,
     pub max_retries: i32,
@@ -22,155 +40,929 @@ pub struct QueuedEvent {
 #[allow(dead_code)]
 pub struct QueuedHeartbeat {
     pub id: i64,
+    pub event_id: String,
     pub heartbeat_data: Value,
+    /// When the heartbeat was sampled, not when it was queued or sent
     pub timestamp: DateTime<Utc>,
     pub retry_count: i32,
     pub max_retries: i32,
 }
 
-// Heartbeat queue operations
-pub async fn queue_heartbeat(heartbeat_data: &Value) -> Result<()> {
-    let conn = database::get_connection()?;
-    
-    let now = Utc::now();
-    let data_str = serde_json::to_string(heartbeat_data)?;
-    
-    conn.execute(
-        "INSERT INTO heartbeat_queue (heartbeat_data, timestamp) 
-         VALUES (?1, ?2)",
-        params![data_str, now],
-    )?;
-    
+/// Anything that isn't an explicit server rejection (connect errors, timeouts, ...) is transient.
+/// The whole error chain is searched, so a `DeliveryError` wrapped by the API layer still counts.
+pub fn classify_error(error: &anyhow::Error) -> FailureKind {
+    error
+        .chain()
+        .find_map(|e| e.downcast_ref::<DeliveryError>())
+        .map(|e| e.kind)
+        .unwrap_or(FailureKind::Transient)
+}
+
+/// One item that could not be delivered, with the reason
+#[derive(Debug, Clone)]
+pub struct FailedItem {
+    pub id: i64,
+    pub kind: FailureKind,
+    pub error: String,
+}
+
+impl FailedItem {
+    pub fn from_error(id: i64, error: &anyhow::Error) -> Self {
+        Self { id, kind: classify_error(error), error: error.to_string() }
+    }
+}
+
+/// Delay before retry number `retry_count + 1`: exponential, capped, with "equal jitter"
+/// (half fixed, half random) so agents coming back online don't retry in lockstep
+pub fn backoff_delay(retry_count: i32) -> chrono::Duration {
+    let exponent = retry_count.clamp(0, 20) as u32;
+    let delay = BACKOFF_BASE_SECS.saturating_mul(1_i64 << exponent).min(BACKOFF_MAX_SECS);
+    let half = delay / 2;
+    let jitter = rand::thread_rng().gen_range(0..=delay - half);
+    chrono::Duration::seconds(half + jitter)
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeadLetter {
+    pub id: i64,
+    pub event_id: String,
+    pub kind: String,
+    pub event_type: String,
+    pub payload: Value,
+    pub timestamp: DateTime<Utc>,
+    pub retry_count: i32,
+    pub last_error: Option<String>,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// Generate the client-side ID for a new outbox item.
+/// The same ID must be used for every delivery attempt so the server can drop duplicates.
+pub fn new_event_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+async fn enqueue(event_id: &str, kind: &'static str, event_type: &str, payload: &Value, occurred_at: DateTime<Utc>) -> Result<()> {
+    let event_id = event_id.to_string();
+    let event_type = event_type.to_string();
+    // Nothing unfiltered is written to disk
+    let payload = serde_json::to_string(&crate::policy::filter::filter_payload(payload.clone()))?;
+
+    database::with_connection(move |conn| {
+        // OR IGNORE: re-queueing an item that is already in the outbox is a no-op
+        conn.prepare_cached(
+            "INSERT OR IGNORE INTO outbox (event_id, kind, event_type, payload, timestamp, max_retries)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?
+        .execute(params![event_id, kind, event_type, payload, occurred_at, MAX_ATTEMPTS])?;
+        Ok(())
+    }).await
+}
+
+/// Mark outbox rows as acknowledged by the server, in one transaction
+async fn mark_delivered(ids: Vec<i64>) -> Result<()> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    database::with_connection(move |conn| {
+        let now = Utc::now();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "UPDATE outbox SET delivered_at = ?1, claimed_by = NULL, lease_until = NULL WHERE id = ?2",
+            )?;
+            for id in ids {
+                stmt.execute(params![now, id])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }).await
+}
+
+fn move_to_dead_letter(conn: &Connection, id: i64, error: &str) -> rusqlite::Result<()> {
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO dead_letter (event_id, kind, event_type, payload, timestamp, retry_count, last_error, failed_at)
+         SELECT event_id, kind, event_type, payload, timestamp, retry_count, ?2, ?3
+         FROM outbox WHERE id = ?1",
+    )?
+    .execute(params![id, error, Utc::now()])?;
+    conn.prepare_cached("DELETE FROM outbox WHERE id = ?1")?.execute(params![id])?;
     Ok(())
 }
 
-pub async fn get_pending_heartbeats() -> Result<Vec<QueuedHeartbeat>> {
-    let conn = database::get_connection()?;
-    
-    let mut stmt = conn.prepare(
-        "SELECT id, heartbeat_data, timestamp, retry_count, max_retries 
-         FROM heartbeat_queue 
-         WHERE processed = 0 AND retry_count < max_retries
-         ORDER BY timestamp ASC
-         LIMIT 10"
-    )?;
-    
-    let heartbeat_iter = stmt.query_map([], |row| {
-        let heartbeat_data: String = row.get(1)?;
-        let heartbeat_data: Value = serde_json::from_str(&heartbeat_data)
-            .map_err(|_| rusqlite::Error::InvalidColumnType(1, "heartbeat_data".to_string(), rusqlite::types::Type::Text))?;
-        
-        Ok(QueuedHeartbeat {
-            id: row.get(0)?,
-            heartbeat_data,
-            timestamp: row.get(2)?,
-            retry_count: row.get(3)?,
-            max_retries: row.get(4)?,
-        })
-    })?;
-    
-    let mut heartbeats = Vec::new();
-    for heartbeat in heartbeat_iter {
-        heartbeats.push(heartbeat?);
+/// Reschedule transient failures with backoff and dead-letter permanent ones
+/// (or transient ones that have used up their attempts). Unauthorized failures
+/// are released without using up an attempt.
+async fn mark_failed(failures: Vec<FailedItem>) -> Result<()> {
+    if failures.is_empty() {
+        return Ok(());
     }
-    
-    Ok(heartbeats)
+    database::with_connection(move |conn| {
+        let now = Utc::now();
+        let tx = conn.transaction()?;
+        for failure in failures {
+            let attempts: Option<(i32, i32)> = tx
+                .prepare_cached("SELECT retry_count, max_retries FROM outbox WHERE id = ?1")?
+                .query_row(params![failure.id], |row| Ok((row.get(0)?, row.get(1)?)))
+                .optional()?;
+            let Some((retry_count, max_retries)) = attempts else {
+                continue;
+            };
+
+            if failure.kind == FailureKind::Unauthorized {
+                tx.prepare_cached(
+                    "UPDATE outbox SET last_error = ?2, claimed_by = NULL, lease_until = NULL WHERE id = ?1",
+                )?
+                .execute(params![failure.id, failure.error])?;
+            } else if failure.kind == FailureKind::Permanent {
+                log::warn!("Outbox item {} rejected permanently, moving to dead letter: {}", failure.id, failure.error);
+                move_to_dead_letter(&tx, failure.id, &failure.error)?;
+            } else if retry_count + 1 >= max_retries {
+                log::warn!("Outbox item {} exhausted {} attempts, moving to dead letter: {}", failure.id, max_retries, failure.error);
+                tx.prepare_cached("UPDATE outbox SET retry_count = retry_count + 1 WHERE id = ?1")?
+                    .execute(params![failure.id])?;
+                move_to_dead_letter(&tx, failure.id, &format!("retries exhausted: {}", failure.error))?;
+            } else {
+                let next_attempt_at = now + backoff_delay(retry_count);
+                tx.prepare_cached(
+                    "UPDATE outbox
+                     SET retry_count = retry_count + 1, next_attempt_at = ?2, last_error = ?3,
+                         claimed_by = NULL, lease_until = NULL
+                     WHERE id = ?1",
+                )?
+                .execute(params![failure.id, next_attempt_at, failure.error])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }).await
+}
+
+const EVENT_COLUMNS: &str = "id, event_id, event_type, payload, timestamp, retry_count, max_retries";
+const HEARTBEAT_COLUMNS: &str = "id, event_id, payload, timestamp, retry_count, max_retries";
+
+// Due for delivery and not leased to anyone. Params: ?1 kind, ?2 limit, ?3 now
+const PENDING_FILTER: &str = "kind = ?1 AND delivered_at IS NULL AND retry_count < max_retries
+    AND (next_attempt_at IS NULL OR next_attempt_at <= ?3)
+    AND (lease_until IS NULL OR lease_until <= ?3)";
+
+// Rows handed out by the last `claim`. Params: ?1 kind, ?2 owner, ?3 lease_until
+const CLAIMED_FILTER: &str = "kind = ?1 AND claimed_by = ?2 AND lease_until = ?3 AND delivered_at IS NULL";
+
+fn payload_from_row(row: &rusqlite::Row, idx: usize) -> rusqlite::Result<Value> {
+    let payload: String = row.get(idx)?;
+    serde_json::from_str(&payload)
+        .map_err(|_| rusqlite::Error::InvalidColumnType(idx, "payload".to_string(), rusqlite::types::Type::Text))
+}
+
+fn event_from_row(row: &rusqlite::Row) -> rusqlite::Result<QueuedEvent> {
+    Ok(QueuedEvent {
+        id: row.get(0)?,
+        event_id: row.get(1)?,
+        event_type: row.get(2)?,
+        event_data: payload_from_row(row, 3)?,
+        timestamp: row.get(4)?,
+        retry_count: row.get(5)?,
+        max_retries: row.get(6)?,
+    })
+}
+
+fn heartbeat_from_row(row: &rusqlite::Row) -> rusqlite::Result<QueuedHeartbeat> {
+    Ok(QueuedHeartbeat {
+        id: row.get(0)?,
+        event_id: row.get(1)?,
+        heartbeat_data: payload_from_row(row, 2)?,
+        timestamp: row.get(3)?,
+        retry_count: row.get(4)?,
+        max_retries: row.get(5)?,
+    })
+}
+
+/// Lease the oldest due rows of `kind` to `owner` until now + `lease`.
+/// Rows already leased to `owner` are picked up again, so a worker never locks itself out
+/// after a crash mid-batch; rows leased to anyone else are skipped until their lease expires.
+fn claim(conn: &mut Connection, kind: &str, owner: &str, limit: usize, lease: chrono::Duration) -> rusqlite::Result<DateTime<Utc>> {
+    let now = Utc::now();
+    let lease_until = now + lease;
+    // IMMEDIATE takes the write lock up front so two processes can't claim the same rows
+    let tx = rusqlite::Transaction::new(conn, rusqlite::TransactionBehavior::Immediate)?;
+    tx.prepare_cached(
+        "UPDATE outbox SET claimed_by = ?2, lease_until = ?3
+         WHERE id IN (
+             SELECT id FROM outbox
+             WHERE kind = ?1 AND delivered_at IS NULL AND retry_count < max_retries
+               AND (next_attempt_at IS NULL OR next_attempt_at <= ?4)
+               AND (lease_until IS NULL OR lease_until <= ?4 OR claimed_by = ?2)
+             ORDER BY timestamp ASC
+             LIMIT ?5
+         )",
+    )?
+    .execute(params![kind, owner, lease_until, now, limit as i64])?;
+    tx.commit()?;
+    Ok(lease_until)
+}
+
+// Heartbeat queue operations
+pub async fn queue_heartbeat(heartbeat_data: &Value) -> Result<String> {
+    let event_id = new_event_id();
+    queue_heartbeat_with_id(&event_id, heartbeat_data, Utc::now()).await?;
+    Ok(event_id)
+}
+
+/// Queue a heartbeat whose live delivery already used `event_id`, keeping the
+/// time it was sampled so a late upload doesn't shift the timeline
+pub async fn queue_heartbeat_with_id(event_id: &str, heartbeat_data: &Value, occurred_at: DateTime<Utc>) -> Result<()> {
+    enqueue(event_id, KIND_HEARTBEAT, KIND_HEARTBEAT, heartbeat_data, occurred_at).await
 }
 
+pub async fn get_pending_heartbeats() -> Result<Vec<QueuedHeartbeat>> {
+    database::with_connection(move |conn| {
+        let sql = format!("SELECT {} FROM outbox WHERE {} ORDER BY timestamp ASC LIMIT ?2", HEARTBEAT_COLUMNS, PENDING_FILTER);
+        let mut stmt = conn.prepare_cached(&sql)?;
+        let heartbeats = stmt
+            .query_map(params![KIND_HEARTBEAT, DEFAULT_FETCH_LIMIT as i64, Utc::now()], heartbeat_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(heartbeats)
+    }).await
+}
+
+/// Lease up to `limit` due heartbeats to `owner` so no other drainer sends them concurrently
+pub async fn claim_pending_heartbeats(owner: &str, limit: usize, lease: chrono::Duration) -> Result<Vec<QueuedHeartbeat>> {
+    let owner = owner.to_string();
+    database::with_connection(move |conn| {
+        let lease_until = claim(conn, KIND_HEARTBEAT, &owner, limit, lease)?;
+        let sql = format!("SELECT {} FROM outbox WHERE {} ORDER BY timestamp ASC", HEARTBEAT_COLUMNS, CLAIMED_FILTER);
+        let mut stmt = conn.prepare_cached(&sql)?;
+        let heartbeats = stmt
+            .query_map(params![KIND_HEARTBEAT, owner, lease_until], heartbeat_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(heartbeats)
+    }).await
+}
+
+/// Only call once the server has acknowledged the heartbeat
 pub async fn mark_heartbeat_processed(id: i64) -> Result<()> {
-    let conn = database::get_connection()?;
-    
-    conn.execute(
-        "UPDATE heartbeat_queue SET processed = 1 WHERE id = ?1",
-        params![id],
-    )?;
-    
-    Ok(())
+    mark_delivered(vec![id]).await
 }
 
-pub async fn mark_heartbeat_failed(id: i64) -> Result<()> {
-    let conn = database::get_connection()?;
-    
-    conn.execute(
-        "UPDATE heartbeat_queue 
-         SET retry_count = retry_count + 1 
-         WHERE id = ?1",
-        params![id],
-    )?;
-    
-    Ok(())
+pub async fn mark_heartbeat_failed(id: i64, error: &anyhow::Error) -> Result<()> {
+    mark_failed(vec![FailedItem::from_error(id, error)]).await
 }
 
 // Event queue operations
-pub async fn queue_event(event_type: &str, event_data: &Value) -> Result<()> {
-    let conn = database::get_connection()?;
-    
-    let now = Utc::now();
-    let data_str = serde_json::to_string(event_data)?;
-    
-    conn.execute(
-        "INSERT INTO event_queue (event_type, event_data, timestamp) 
-         VALUES (?1, ?2, ?3)",
-        params![event_type, data_str, now],
-    )?;
-    
-    Ok(())
+pub async fn queue_event(event_type: &str, event_data: &Value) -> Result<String> {
+    let event_id = new_event_id();
+    queue_event_with_id(&event_id, event_type, event_data, Utc::now()).await?;
+    Ok(event_id)
+}
+
+/// Queue an event whose live delivery already used `event_id`. `occurred_at` is
+/// the time of the action itself and is what the server records, however late
+/// the event is eventually uploaded.
+pub async fn queue_event_with_id(event_id: &str, event_type: &str, event_data: &Value, occurred_at: DateTime<Utc>) -> Result<()> {
+    enqueue(event_id, KIND_EVENT, event_type, event_data, occurred_at).await
 }
 
 pub async fn get_pending_events() -> Result<Vec<QueuedEvent>> {
-    let conn = database::get_connection()?;
-    
-    let mut stmt = conn.prepare(
-        "SELECT id, event_type, event_data, timestamp, retry_count, max_retries 
-         FROM event_queue 
-         WHERE processed = 0 AND retry_count < max_retries
-         ORDER BY timestamp ASC
-         LIMIT 10"
-    )?;
-    
-    let event_iter = stmt.query_map([], |row| {
-        let event_data: String = row.get(2)?;
-        let event_data: Value = serde_json::from_str(&event_data)
-            .map_err(|_| rusqlite::Error::InvalidColumnType(2, "event_data".to_string(), rusqlite::types::Type::Text))?;
-        
-        Ok(QueuedEvent {
-            id: row.get(0)?,
-            event_type: row.get(1)?,
-            event_data,
-            timestamp: row.get(3)?,
-            retry_count: row.get(4)?,
-            max_retries: row.get(5)?,
-        })
-    })?;
-    
-    let mut events = Vec::new();
-    for event in event_iter {
-        events.push(event?);
-    }
-    
-    Ok(events)
+    database::with_connection(move |conn| {
+        let sql = format!("SELECT {} FROM outbox WHERE {} ORDER BY timestamp ASC LIMIT ?2", EVENT_COLUMNS, PENDING_FILTER);
+        let mut stmt = conn.prepare_cached(&sql)?;
+        let events = stmt
+            .query_map(params![KIND_EVENT, DEFAULT_FETCH_LIMIT as i64, Utc::now()], event_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(events)
+    }).await
+}
+
+/// Lease up to `limit` of the oldest due events to `owner` for batch upload
+pub async fn claim_pending_events(owner: &str, limit: usize, lease: chrono::Duration) -> Result<Vec<QueuedEvent>> {
+    let owner = owner.to_string();
+    database::with_connection(move |conn| {
+        let lease_until = claim(conn, KIND_EVENT, &owner, limit, lease)?;
+        let sql = format!("SELECT {} FROM outbox WHERE {} ORDER BY timestamp ASC", EVENT_COLUMNS, CLAIMED_FILTER);
+        let mut stmt = conn.prepare_cached(&sql)?;
+        let events = stmt
+            .query_map(params![KIND_EVENT, owner, lease_until], event_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(events)
+    }).await
 }
 
+/// Only call once the server has acknowledged the event
 pub async fn mark_event_processed(event_id: i64) -> Result<()> {
-    let conn = database::get_connection()?;
-    
-    conn.execute(
-        "UPDATE event_queue SET processed = 1 WHERE id = ?1",
-        params![event_id],
-    )?;
-    
-    Ok(())
+    mark_delivered(vec![event_id]).await
 }
 
-pub async fn mark_event_failed(event_id: i64) -> Result<()> {
-    let conn = database::get_connection()?;
-    
-    conn.execute(
-        "UPDATE event_queue 
-         SET retry_count = retry_count + 1 
-         WHERE id = ?1",
-        params![event_id],
-    )?;
-    
-    Ok(())
-}
\ No newline at end of file
+pub async fn mark_event_failed(event_id: i64, error: &anyhow::Error) -> Result<()> {
+    mark_failed(vec![FailedItem::from_error(event_id, error)]).await
+}
+
+/// Mark every event the server acknowledged in a batch as delivered
+pub async fn mark_events_processed(ids: Vec<i64>) -> Result<()> {
+    mark_delivered(ids).await
+}
+
+pub async fn mark_events_failed(failures: Vec<FailedItem>) -> Result<()> {
+    mark_failed(failures).await
+}
+
+// Dead-letter operations
+pub async fn get_dead_letters(limit: usize) -> Result<Vec<DeadLetter>> {
+    database::with_connection(move |conn| {
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, event_id, kind, event_type, payload, timestamp, retry_count, last_error, failed_at
+             FROM dead_letter
+             ORDER BY failed_at DESC
+             LIMIT ?1"
+        )?;
+
+        let rows = stmt.query_map(params![limit as i64], |row| {
+            let payload: String = row.get(4)?;
+            let payload: Value = serde_json::from_str(&payload)
+                .map_err(|_| rusqlite::Error::InvalidColumnType(4, "payload".to_string(), rusqlite::types::Type::Text))?;
+
+            Ok(DeadLetter {
+                id: row.get(0)?,
+                event_id: row.get(1)?,
+                kind: row.get(2)?,
+                event_type: row.get(3)?,
+                payload,
+                timestamp: row.get(5)?,
+                retry_count: row.get(6)?,
+                last_error: row.get(7)?,
+                failed_at: row.get(8)?,
+            })
+        })?;
+
+        let mut dead_letters = Vec::new();
+        for row in rows {
+            dead_letters.push(row?);
+        }
+
+        Ok(dead_letters)
+    }).await
+}
+
+/// Move dead-lettered items back into the outbox with a fresh retry budget.
+/// `ids` of None replays everything. Items keep their event ID, so replaying
+/// something the server did receive is harmless.
+pub async fn replay_dead_letters(ids: Option<Vec<i64>>) -> Result<usize> {
+    database::with_connection(move |conn| {
+        let tx = conn.transaction()?;
+        let ids = match ids {
+            Some(ids) => ids,
+            None => tx
+                .prepare("SELECT id FROM dead_letter")?
+                .query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<i64>>>()?,
+        };
+
+        let mut replayed = 0;
+        for id in ids {
+            replayed += tx.prepare_cached(
+                "INSERT OR IGNORE INTO outbox (event_id, kind, event_type, payload, timestamp, max_retries)
+                 SELECT event_id, kind, event_type, payload, timestamp, ?2
+                 FROM dead_letter WHERE id = ?1",
+            )?
+            .execute(params![id, MAX_ATTEMPTS])?;
+            tx.prepare_cached("DELETE FROM dead_letter WHERE id = ?1")?.execute(params![id])?;
+        }
+
+        tx.commit()?;
+        Ok(replayed)
+    }).await
+}
+
+// Queue inspection (support tooling). Items are addressed by their event ID,
+// which is unique across the outbox and the dead-letter table.
+
+// Where an item is in its delivery lifecycle
+pub const STATE_PENDING: &str = "pending";
+pub const STATE_RETRYING: &str = "retrying";
+pub const STATE_IN_FLIGHT: &str = "in_flight";
+pub const STATE_DELIVERED: &str = "delivered";
+pub const STATE_DEAD_LETTER: &str = "dead_letter";
+
+// Every queued item with its state. Params: ?1 now
+const ITEMS_QUERY: &str = "
+    SELECT event_id, kind, event_type, payload, timestamp, retry_count, max_retries,
+           next_attempt_at, last_error, delivered_at,
+           CASE WHEN delivered_at IS NOT NULL THEN 'delivered'
+                WHEN lease_until > ?1 THEN 'in_flight'
+                WHEN retry_count > 0 THEN 'retrying'
+                ELSE 'pending' END AS state
+    FROM outbox
+    UNION ALL
+    SELECT event_id, kind, event_type, payload, timestamp, retry_count, NULL,
+           NULL, last_error, NULL, 'dead_letter'
+    FROM dead_letter";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueCount {
+    pub kind: String,
+    pub event_type: String,
+    pub state: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueSummary {
+    pub counts: Vec<QueueCount>,
+    /// Occurrence time of the oldest item not yet acknowledged by the server
+    pub oldest_pending_at: Option<DateTime<Utc>>,
+    pub oldest_pending_age_secs: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueItem {
+    pub event_id: String,
+    pub kind: String,
+    pub event_type: String,
+    pub state: String,
+    pub payload: Value,
+    pub occurred_at: DateTime<Utc>,
+    pub retry_count: i32,
+    pub max_retries: Option<i32>,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+fn item_from_row(row: &rusqlite::Row) -> rusqlite::Result<QueueItem> {
+    Ok(QueueItem {
+        event_id: row.get(0)?,
+        kind: row.get(1)?,
+        event_type: row.get(2)?,
+        payload: payload_from_row(row, 3)?,
+        occurred_at: row.get(4)?,
+        retry_count: row.get(5)?,
+        max_retries: row.get(6)?,
+        next_attempt_at: row.get(7)?,
+        last_error: row.get(8)?,
+        delivered_at: row.get(9)?,
+        state: row.get(10)?,
+    })
+}
+
+/// Counts per kind, event type and state, plus how long the oldest undelivered item has waited
+pub async fn get_queue_summary() -> Result<QueueSummary> {
+    database::with_connection(move |conn| {
+        let now = Utc::now();
+        let sql = format!(
+            "SELECT kind, event_type, state, COUNT(*) FROM ({})
+             GROUP BY kind, event_type, state
+             ORDER BY kind, event_type, state",
+            ITEMS_QUERY
+        );
+        let counts = conn
+            .prepare(&sql)?
+            .query_map(params![now], |row| {
+                Ok(QueueCount {
+                    kind: row.get(0)?,
+                    event_type: row.get(1)?,
+                    state: row.get(2)?,
+                    count: row.get::<_, i64>(3)? as usize,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let oldest_pending_at: Option<DateTime<Utc>> = conn.query_row(
+            "SELECT MIN(timestamp) FROM outbox WHERE delivered_at IS NULL",
+            [],
+            |row| row.get(0),
+        )?;
+
+        Ok(QueueSummary {
+            counts,
+            oldest_pending_at,
+            oldest_pending_age_secs: oldest_pending_at.map(|t| (now - t).num_seconds().max(0)),
+        })
+    }).await
+}
+
+/// Pending and delivered totals: (pending events, delivered events, pending heartbeats, delivered heartbeats)
+pub async fn get_queue_stats() -> Result<(usize, usize, usize, usize)> {
+    let summary = get_queue_summary().await?;
+    let total = |kind: &str, delivered: bool| {
+        summary
+            .counts
+            .iter()
+            .filter(|c| c.kind == kind && c.state != STATE_DEAD_LETTER && (c.state == STATE_DELIVERED) == delivered)
+            .map(|c| c.count)
+            .sum::<usize>()
+    };
+    Ok((
+        total(KIND_EVENT, false),
+        total(KIND_EVENT, true),
+        total(KIND_HEARTBEAT, false),
+        total(KIND_HEARTBEAT, true),
+    ))
+}
+
+/// List queued items, optionally restricted to one state, oldest first
+pub async fn get_queue_items(state: Option<String>, limit: usize) -> Result<Vec<QueueItem>> {
+    database::with_connection(move |conn| {
+        let sql = format!(
+            "SELECT * FROM ({}) WHERE ?2 IS NULL OR state = ?2 ORDER BY timestamp ASC LIMIT ?3",
+            ITEMS_QUERY
+        );
+        let items = conn
+            .prepare(&sql)?
+            .query_map(params![Utc::now(), state, limit as i64], item_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(items)
+    }).await
+}
+
+/// Full records for the given event IDs (all items when None), e.g. for a support export
+pub async fn export_queue_items(event_ids: Option<Vec<String>>) -> Result<Vec<QueueItem>> {
+    database::with_connection(move |conn| {
+        let sql = format!(
+            "SELECT * FROM ({}) WHERE ?2 IS NULL OR event_id = ?2 ORDER BY timestamp ASC",
+            ITEMS_QUERY
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let now = Utc::now();
+
+        let items = match event_ids {
+            None => stmt
+                .query_map(params![now, None::<String>], item_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?,
+            Some(ids) => {
+                let mut items = Vec::new();
+                for id in ids {
+                    for item in stmt.query_map(params![now, id], item_from_row)? {
+                        items.push(item?);
+                    }
+                }
+                items
+            }
+        };
+        Ok(items)
+    }).await
+}
+
+/// Make the given items due immediately. Undelivered outbox rows get a fresh retry
+/// budget; dead-lettered ones are moved back into the outbox. Rows currently being
+/// uploaded are left alone. Returns how many items were rescheduled.
+pub async fn retry_queue_items(event_ids: Vec<String>) -> Result<usize> {
+    database::with_connection(move |conn| {
+        let now = Utc::now();
+        let tx = conn.transaction()?;
+        let mut retried = 0;
+        for event_id in &event_ids {
+            retried += tx.prepare_cached(
+                "UPDATE outbox SET retry_count = 0, max_retries = ?2, next_attempt_at = NULL
+                 WHERE event_id = ?1 AND delivered_at IS NULL
+                   AND (lease_until IS NULL OR lease_until <= ?3)",
+            )?
+            .execute(params![event_id, MAX_ATTEMPTS, now])?;
+
+            let replayed = tx.prepare_cached(
+                "INSERT OR IGNORE INTO outbox (event_id, kind, event_type, payload, timestamp, max_retries)
+                 SELECT event_id, kind, event_type, payload, timestamp, ?2
+                 FROM dead_letter WHERE event_id = ?1",
+            )?
+            .execute(params![event_id, MAX_ATTEMPTS])?;
+            tx.prepare_cached("DELETE FROM dead_letter WHERE event_id = ?1")?.execute(params![event_id])?;
+            retried += replayed;
+        }
+        tx.commit()?;
+        Ok(retried)
+    }).await
+}
+
+/// Permanently drop the given undelivered or dead-lettered items.
+/// Rows currently being uploaded are left alone. Returns how many items were removed.
+pub async fn discard_queue_items(event_ids: Vec<String>) -> Result<usize> {
+    database::with_connection(move |conn| {
+        let now = Utc::now();
+        let tx = conn.transaction()?;
+        let mut discarded = 0;
+        for event_id in &event_ids {
+            discarded += tx.prepare_cached(
+                "DELETE FROM outbox
+                 WHERE event_id = ?1 AND delivered_at IS NULL
+                   AND (lease_until IS NULL OR lease_until <= ?2)",
+            )?
+            .execute(params![event_id, now])?;
+            discarded += tx.prepare_cached("DELETE FROM dead_letter WHERE event_id = ?1")?
+                .execute(params![event_id])?;
+        }
+        tx.commit()?;
+        Ok(discarded)
+    }).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn setup() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        super::super::migrations::run(&mut conn).unwrap();
+        conn
+    }
+
+    fn insert(conn: &Connection, event_id: &str, age_secs: i64) {
+        conn.execute(
+            "INSERT INTO outbox (event_id, kind, event_type, payload, timestamp, max_retries) VALUES (?1, ?2, ?2, '{}', ?3, ?4)",
+            params![event_id, KIND_EVENT, Utc::now() - chrono::Duration::seconds(age_secs), MAX_ATTEMPTS],
+        )
+        .unwrap();
+    }
+
+    fn claimed_by(conn: &Connection, owner: &str) -> Vec<String> {
+        let mut stmt = conn.prepare("SELECT event_id FROM outbox WHERE claimed_by = ?1 ORDER BY timestamp ASC").unwrap();
+        stmt.query_map(params![owner], |row| row.get(0)).unwrap().collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn test_claim_leases_oldest_rows_to_one_worker() {
+        let mut conn = setup();
+        let lease = chrono::Duration::seconds(60);
+        insert(&conn, "newest", 10);
+        insert(&conn, "oldest", 30);
+        insert(&conn, "middle", 20);
+
+        claim(&mut conn, KIND_EVENT, "worker-a", 2, lease).unwrap();
+        assert_eq!(claimed_by(&conn, "worker-a"), vec!["oldest", "middle"]);
+
+        // Another worker only gets what is left while the lease is live
+        claim(&mut conn, KIND_EVENT, "worker-b", 10, lease).unwrap();
+        assert_eq!(claimed_by(&conn, "worker-b"), vec!["newest"]);
+        assert_eq!(claimed_by(&conn, "worker-a"), vec!["oldest", "middle"]);
+    }
+
+    #[test]
+    fn test_owner_reclaims_its_own_live_lease() {
+        let mut conn = setup();
+        let lease = chrono::Duration::seconds(60);
+        insert(&conn, "event-1", 10);
+
+        let first = claim(&mut conn, KIND_EVENT, "worker-a", 10, lease).unwrap();
+        let second = claim(&mut conn, KIND_EVENT, "worker-a", 10, lease).unwrap();
+        assert!(second >= first);
+        assert_eq!(claimed_by(&conn, "worker-a"), vec!["event-1"]);
+    }
+
+    #[test]
+    fn test_expired_lease_is_reclaimed_by_another_worker() {
+        let mut conn = setup();
+        insert(&conn, "event-1", 10);
+
+        claim(&mut conn, KIND_EVENT, "worker-a", 10, chrono::Duration::seconds(-1)).unwrap();
+        claim(&mut conn, KIND_EVENT, "worker-b", 10, chrono::Duration::seconds(60)).unwrap();
+        assert!(claimed_by(&conn, "worker-a").is_empty());
+        assert_eq!(claimed_by(&conn, "worker-b"), vec!["event-1"]);
+    }
+
+    #[test]
+    fn test_claim_skips_delivered_and_backed_off_rows() {
+        let mut conn = setup();
+        insert(&conn, "delivered", 30);
+        insert(&conn, "backed-off", 20);
+        insert(&conn, "due", 10);
+        conn.execute("UPDATE outbox SET delivered_at = ?1 WHERE event_id = 'delivered'", params![Utc::now()]).unwrap();
+        conn.execute(
+            "UPDATE outbox SET next_attempt_at = ?1 WHERE event_id = 'backed-off'",
+            params![Utc::now() + chrono::Duration::minutes(5)],
+        )
+        .unwrap();
+
+        claim(&mut conn, KIND_EVENT, "worker-a", 10, chrono::Duration::seconds(60)).unwrap();
+        assert_eq!(claimed_by(&conn, "worker-a"), vec!["due"]);
+    }
+
+    #[test]
+    fn test_failure_classification() {
+        assert_eq!(classify_error(&anyhow::anyhow!("timeout")), FailureKind::Transient);
+
+        // Still found when the API layer adds context on top
+        let wrapped = anyhow::Error::new(DeliveryError::from_status(422, "invalid payload")).context("Failed to send events");
+        assert_eq!(classify_error(&wrapped), FailureKind::Permanent);
+    }
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        for retry in 0..30 {
+            let delay = backoff_delay(retry).num_seconds();
+            let ceiling = (BACKOFF_BASE_SECS << retry.min(20)).min(BACKOFF_MAX_SECS);
+            assert!(delay >= ceiling / 2 && delay <= ceiling, "retry {}: {}s", retry, delay);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transient_failure_is_rescheduled() {
+        let _db = database::test_database().await;
+
+        queue_event("test_event", &json!({"test": "data"})).await.unwrap();
+        let events = get_pending_events().await.unwrap();
+
+        let error = anyhow::anyhow!("Network error: connection refused");
+        mark_event_failed(events[0].id, &error).await.unwrap();
+
+        // Backed off, so not due yet - but not dead-lettered either
+        assert!(get_pending_events().await.unwrap().is_empty());
+        assert!(get_dead_letters(10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_attempts_are_dead_lettered() {
+        let _db = database::test_database().await;
+
+        let event_id = queue_event("test_event", &json!({"test": "data"})).await.unwrap();
+        let id = get_pending_events().await.unwrap()[0].id;
+
+        let error = anyhow::anyhow!("Network error: connection refused");
+        for _ in 0..MAX_ATTEMPTS {
+            mark_event_failed(id, &error).await.unwrap();
+        }
+
+        let dead_letters = get_dead_letters(10).await.unwrap();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].event_id, event_id);
+        assert_eq!(dead_letters[0].retry_count, MAX_ATTEMPTS);
+        assert!(dead_letters[0].last_error.as_deref().unwrap().starts_with("retries exhausted"));
+    }
+
+    #[tokio::test]
+    async fn test_unauthorized_failure_keeps_attempts() {
+        let _db = database::test_database().await;
+
+        queue_event("clock_in", &json!({})).await.unwrap();
+        let events = get_pending_events().await.unwrap();
+        let error = anyhow::Error::new(crate::api::error::ApiError::from_status(401, "token expired"));
+        mark_event_failed(events[0].id, &error).await.unwrap();
+
+        // Still due right away, with no attempt used up and nothing dead-lettered
+        let pending = get_pending_events().await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].retry_count, 0);
+        assert!(get_dead_letters(10).await.unwrap().is_empty());
+        let items = get_queue_items(None, 10).await.unwrap();
+        assert!(items[0].last_error.as_deref().unwrap().contains("token expired"));
+    }
+
+    #[tokio::test]
+    async fn test_permanent_failure_is_dead_lettered_and_replayable() {
+        let _db = database::test_database().await;
+
+        let event_id = queue_event("test_event", &json!({"test": "data"})).await.unwrap();
+        let events = get_pending_events().await.unwrap();
+
+        let error = anyhow::Error::new(DeliveryError::from_status(422, "invalid payload"));
+        mark_event_failed(events[0].id, &error).await.unwrap();
+
+        let dead_letters = get_dead_letters(10).await.unwrap();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].event_id, event_id);
+        assert!(get_pending_events().await.unwrap().is_empty());
+
+        assert_eq!(replay_dead_letters(None).await.unwrap(), 1);
+        let events = get_pending_events().await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_id, event_id);
+        assert_eq!(events[0].retry_count, 0);
+        assert!(get_dead_letters(10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_queue_summary_reports_states_and_age() {
+        let _db = database::test_database().await;
+
+        let occurred_at = Utc::now() - chrono::Duration::hours(2);
+        let stuck = new_event_id();
+        queue_event_with_id(&stuck, "clock_out", &json!({}), occurred_at).await.unwrap();
+        queue_event("app_focus", &json!({})).await.unwrap();
+        queue_heartbeat(&json!({"status": "active"})).await.unwrap();
+        let delivered = get_pending_heartbeats().await.unwrap();
+        mark_heartbeat_processed(delivered[0].id).await.unwrap();
+
+        let events = get_pending_events().await.unwrap();
+        let error = anyhow::anyhow!("Network error: connection refused");
+        mark_event_failed(events[0].id, &error).await.unwrap();
+
+        let summary = get_queue_summary().await.unwrap();
+        let state_of = |event_type: &str| {
+            summary.counts.iter().find(|c| c.event_type == event_type).map(|c| c.state.clone())
+        };
+        assert_eq!(state_of("clock_out").as_deref(), Some(STATE_RETRYING));
+        assert_eq!(state_of("app_focus").as_deref(), Some(STATE_PENDING));
+        assert_eq!(state_of(KIND_HEARTBEAT).as_deref(), Some(STATE_DELIVERED));
+        assert!(summary.oldest_pending_age_secs.unwrap() >= 2 * 3600);
+        assert_eq!(get_queue_stats().await.unwrap(), (2, 0, 0, 1));
+
+        let items = get_queue_items(Some(STATE_RETRYING.to_string()), 10).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].event_id, stuck);
+        assert!(items[0].last_error.as_deref().unwrap().contains("connection refused"));
+    }
+
+    #[tokio::test]
+    async fn test_retry_discard_and_export_selected_items() {
+        let _db = database::test_database().await;
+
+        let rejected = queue_event("clock_in", &json!({"session_id": 1})).await.unwrap();
+        let unwanted = queue_event("app_focus", &json!({})).await.unwrap();
+        let events = get_pending_events().await.unwrap();
+        let error = anyhow::Error::new(DeliveryError::from_status(422, "invalid payload"));
+        mark_event_failed(events[0].id, &error).await.unwrap();
+
+        let exported = export_queue_items(Some(vec![rejected.clone()])).await.unwrap();
+        assert_eq!(exported.len(), 1);
+        assert_eq!(exported[0].state, STATE_DEAD_LETTER);
+        assert_eq!(exported[0].payload, json!({"session_id": 1}));
+        assert_eq!(export_queue_items(None).await.unwrap().len(), 2);
+
+        assert_eq!(retry_queue_items(vec![rejected.clone()]).await.unwrap(), 1);
+        assert_eq!(discard_queue_items(vec![unwanted]).await.unwrap(), 1);
+
+        let pending = get_pending_events().await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].event_id, rejected);
+        assert!(get_dead_letters(10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_items_being_uploaded_are_left_alone() {
+        let _db = database::test_database().await;
+
+        let event_id = queue_event("clock_in", &json!({})).await.unwrap();
+        claim_pending_events("worker-a", 10, chrono::Duration::seconds(60)).await.unwrap();
+
+        assert_eq!(get_queue_items(None, 10).await.unwrap()[0].state, STATE_IN_FLIGHT);
+        assert_eq!(retry_queue_items(vec![event_id.clone()]).await.unwrap(), 0);
+        assert_eq!(discard_queue_items(vec![event_id]).await.unwrap(), 0);
+        assert_eq!(get_queue_items(Some(STATE_IN_FLIGHT.to_string()), 10).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_requeue_with_same_id_is_ignored() {
+        let _db = database::test_database().await;
+
+        let event_data = json!({"test": "data"});
+        let event_id = queue_event("test_event", &event_data).await.unwrap();
+        queue_event_with_id(&event_id, "test_event", &event_data, Utc::now()).await.unwrap();
+
+        let events = get_pending_events().await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_id, event_id);
+    }
+
+    #[tokio::test]
+    async fn test_queued_event_keeps_original_time() {
+        let _db = database::test_database().await;
+
+        let occurred_at = Utc::now() - chrono::Duration::hours(3);
+        let event_id = new_event_id();
+        queue_event_with_id(&event_id, "clock_out", &json!({"source": "desktop_agent"}), occurred_at).await.unwrap();
+
+        let events = get_pending_events().await.unwrap();
+        assert_eq!(events[0].timestamp, occurred_at);
+    }
+
+    #[tokio::test]
+    async fn test_queue_stats() {
+        let _db = database::test_database().await;
+
+        // Queue some test data
+        let event_data = json!({"test": "data"});
+        queue_event("test_event", &event_data).await.unwrap();
+
+        let heartbeat_data = json!({"status": "active"});
+        queue_heartbeat(&heartbeat_data).await.unwrap();
+
+        let (pending_events, _processed_events, pending_heartbeats, _processed_heartbeats) =
+            get_queue_stats().await.unwrap();
+
+        assert!(pending_events > 0);
+        assert!(pending_heartbeats > 0);
+    }
+
+    #[tokio::test]
+    async fn test_window_titles_are_redacted_before_queueing() {
+        let _db = database::test_database().await;
+        let _policy = crate::policy::toggles::test_policy(crate::policy::toggles::PolicyConfig::default()).await;
+
+        let event_data = json!({
+            "app_name": "Google Chrome",
+            "app_id": "com.google.Chrome",
+            "window_title": "Offer letter - Google Chrome",
+        });
+        queue_event("app_focus", &event_data).await.unwrap();
+
+        let heartbeat = json!({
+            "currentApp": { "name": "Notes", "app_id": "com.apple.Notes", "window_title": "Passwords" },
+        });
+        queue_heartbeat(&heartbeat).await.unwrap();
+
+        let events = get_pending_events().await.unwrap();
+        assert_eq!(events[0].event_data["window_title"], "[Redacted]");
+        assert_eq!(events[0].event_data["app_name"], "Google Chrome");
+
+        let heartbeats = get_pending_heartbeats().await.unwrap();
+        assert_eq!(heartbeats[0].heartbeat_data["currentApp"]["window_title"], "[Redacted]");
+    }
+}
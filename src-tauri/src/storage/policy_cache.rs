@@ -0,0 +1,81 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::database;
+use crate::policy::toggles::PolicyConfig;
+
+// Last policy the server gave us, with its ETag, so the agent starts with the
+// organisation's settings even when the server can't be reached.
+
+#[derive(Debug, Clone)]
+pub struct CachedPolicy {
+    pub policy: PolicyConfig,
+    pub etag: Option<String>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+pub fn load(conn: &Connection) -> Result<Option<CachedPolicy>> {
+    let row = conn
+        .query_row(
+            "SELECT policy, etag, fetched_at FROM policy_cache WHERE id = 1",
+            [],
+            |row| Ok((row.get::<_, String>(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+
+    let Some((policy, etag, fetched_at)) = row else {
+        return Ok(None);
+    };
+    Ok(Some(CachedPolicy { policy: serde_json::from_str(&policy)?, etag, fetched_at }))
+}
+
+pub fn store(conn: &Connection, policy: &PolicyConfig, etag: Option<&str>, now: DateTime<Utc>) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO policy_cache (id, policy, etag, fetched_at) VALUES (1, ?1, ?2, ?3)",
+        params![serde_json::to_string(policy)?, etag, now],
+    )?;
+    Ok(())
+}
+
+pub fn clear(conn: &Connection) -> Result<()> {
+    conn.execute("DELETE FROM policy_cache", [])?;
+    Ok(())
+}
+
+pub async fn get_cached_policy() -> Result<Option<CachedPolicy>> {
+    database::with_connection(|conn| load(conn)).await
+}
+
+pub async fn save_policy(policy: PolicyConfig, etag: Option<String>) -> Result<()> {
+    database::with_connection(move |conn| store(conn, &policy, etag.as_deref(), Utc::now())).await
+}
+
+pub async fn clear_cached_policy() -> Result<()> {
+    database::with_connection(|conn| clear(conn)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_round_trips_and_is_replaced() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        super::super::migrations::run(&mut conn).unwrap();
+        assert!(load(&conn).unwrap().is_none());
+
+        let mut policy = PolicyConfig { idle_threshold_seconds: 600, ..PolicyConfig::default() };
+        store(&conn, &policy, Some("\"v1\""), Utc::now()).unwrap();
+
+        policy.screenshot_enabled = true;
+        store(&conn, &policy, Some("\"v2\""), Utc::now()).unwrap();
+
+        let cached = load(&conn).unwrap().unwrap();
+        assert_eq!(cached.policy, policy);
+        assert_eq!(cached.etag.as_deref(), Some("\"v2\""));
+
+        clear(&conn).unwrap();
+        assert!(load(&conn).unwrap().is_none());
+    }
+}
@@ -0,0 +1,311 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+use super::database;
+
+// How often the retention pass runs
+const RETENTION_INTERVAL_SECS: u64 = 60 * 60;
+// Rows deleted per round while enforcing the size ceiling
+const EVICTION_CHUNK: usize = 50;
+
+/// Limits that keep the local queue bounded, e.g. during a week offline
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    /// Delivered outbox rows are kept this long for troubleshooting, then purged
+    pub delivered_retention_days: i64,
+    /// Ceiling for outbox payloads; oldest rows are evicted beyond it. Other tables
+    /// don't count, so their growth never costs undelivered data.
+    pub max_queue_bytes: u64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            delivered_retention_days: 7,
+            max_queue_bytes: 100 * 1024 * 1024, // 100MB
+        }
+    }
+}
+
+impl RetentionPolicy {
+    pub fn from_env() -> Self {
+        let mut policy = Self::default();
+
+        if let Ok(val) = std::env::var("TRACKEX_QUEUE_RETENTION_DAYS") {
+            policy.delivered_retention_days = val.parse().unwrap_or(policy.delivered_retention_days);
+        }
+
+        if let Ok(val) = std::env::var("TRACKEX_QUEUE_MAX_MB") {
+            if let Ok(mb) = val.parse::<u64>() {
+                policy.max_queue_bytes = mb * 1024 * 1024;
+            }
+        }
+
+        policy
+    }
+}
+
+/// What a retention pass removed
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct CompactionReport {
+    pub delivered_purged: usize,
+    pub heartbeats_compacted: usize,
+    pub events_evicted: usize,
+    pub heartbeats_evicted: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+impl CompactionReport {
+    /// Undelivered data that was thrown away (as opposed to housekeeping)
+    pub fn dropped_undelivered(&self) -> usize {
+        self.heartbeats_compacted + self.events_evicted + self.heartbeats_evicted
+    }
+}
+
+/// Bytes used by live pages (excludes pages on the freelist)
+fn used_bytes(conn: &Connection) -> Result<u64> {
+    let page_size: i64 = conn.pragma_query_value(None, "page_size", |row| row.get(0))?;
+    let page_count: i64 = conn.pragma_query_value(None, "page_count", |row| row.get(0))?;
+    let freelist: i64 = conn.pragma_query_value(None, "freelist_count", |row| row.get(0))?;
+    Ok(((page_count - freelist).max(0) * page_size) as u64)
+}
+
+/// Payload bytes held by the outbox, the part of the database the size cap applies to
+fn queue_bytes(conn: &Connection) -> Result<u64> {
+    let bytes: i64 = conn.query_row("SELECT COALESCE(SUM(length(payload)), 0) FROM outbox", [], |row| row.get(0))?;
+    Ok(bytes.max(0) as u64)
+}
+
+fn purge_delivered(conn: &Connection, policy: &RetentionPolicy, now: DateTime<Utc>) -> Result<usize> {
+    let cutoff = now - chrono::Duration::days(policy.delivered_retention_days);
+    let purged = conn.execute(
+        "DELETE FROM outbox WHERE delivered_at IS NOT NULL AND delivered_at < ?1",
+        params![cutoff],
+    )?;
+    Ok(purged)
+}
+
+/// Heartbeats only describe current state, so for each hour keep just the latest undelivered one
+fn compact_heartbeats(conn: &Connection, now: DateTime<Utc>) -> Result<usize> {
+    let compacted = conn.execute(
+        "DELETE FROM outbox
+         WHERE kind = 'heartbeat' AND delivered_at IS NULL
+           AND (lease_until IS NULL OR lease_until <= ?1)
+           AND id NOT IN (
+               SELECT MAX(id) FROM outbox
+               WHERE kind = 'heartbeat' AND delivered_at IS NULL
+               GROUP BY strftime('%Y-%m-%d %H', timestamp)
+           )",
+        params![now],
+    )?;
+    Ok(compacted)
+}
+
+/// Delete outbox rows oldest-first (delivered ones before anything undelivered)
+/// until the outbox is back under the ceiling
+fn enforce_size_cap(conn: &mut Connection, policy: &RetentionPolicy, now: DateTime<Utc>, report: &mut CompactionReport) -> Result<()> {
+    while queue_bytes(conn)? > policy.max_queue_bytes {
+        let tx = conn.transaction()?;
+        let victims: Vec<(i64, String, bool)> = tx
+            .prepare_cached(
+                "SELECT id, kind, delivered_at IS NULL FROM outbox
+                 WHERE lease_until IS NULL OR lease_until <= ?1
+                 ORDER BY delivered_at IS NULL, timestamp ASC
+                 LIMIT ?2",
+            )?
+            .query_map(params![now, EVICTION_CHUNK as i64], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        if victims.is_empty() {
+            log::warn!("Outbox is over its size ceiling but there is nothing left to evict");
+            break;
+        }
+
+        {
+            let mut delete = tx.prepare_cached("DELETE FROM outbox WHERE id = ?1")?;
+            for (id, kind, undelivered) in &victims {
+                delete.execute(params![id])?;
+                match (kind.as_str(), undelivered) {
+                    (_, false) => report.delivered_purged += 1,
+                    ("heartbeat", true) => report.heartbeats_evicted += 1,
+                    (_, true) => report.events_evicted += 1,
+                }
+            }
+        }
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// Give freed pages back to the filesystem. Databases created before incremental
+/// auto-vacuum was enabled need a one-off full VACUUM to switch modes.
+fn vacuum(conn: &Connection) -> Result<()> {
+    let auto_vacuum: i64 = conn.pragma_query_value(None, "auto_vacuum", |row| row.get(0))?;
+    if auto_vacuum != 2 {
+        log::info!("Switching database to incremental auto-vacuum");
+        conn.pragma_update(None, "auto_vacuum", "INCREMENTAL")?;
+        conn.execute_batch("VACUUM")?;
+    } else {
+        conn.execute_batch("PRAGMA incremental_vacuum")?;
+    }
+    Ok(())
+}
+
+pub fn compact(conn: &mut Connection, policy: &RetentionPolicy, now: DateTime<Utc>) -> Result<CompactionReport> {
+    let mut report = CompactionReport {
+        bytes_before: used_bytes(conn)?,
+        ..Default::default()
+    };
+
+    report.delivered_purged = purge_delivered(conn, policy, now)?;
+    report.heartbeats_compacted = compact_heartbeats(conn, now)?;
+    enforce_size_cap(conn, policy, now, &mut report)?;
+    vacuum(conn)?;
+
+    report.bytes_after = used_bytes(conn)?;
+    Ok(report)
+}
+
+pub async fn run_compaction(policy: RetentionPolicy) -> Result<CompactionReport> {
+    let report = database::with_connection(move |conn| compact(conn, &policy, Utc::now())).await?;
+
+    log::info!(
+        "Queue retention: purged {} delivered, compacted {} heartbeats, evicted {} events / {} heartbeats ({} -> {} bytes)",
+        report.delivered_purged,
+        report.heartbeats_compacted,
+        report.events_evicted,
+        report.heartbeats_evicted,
+        report.bytes_before,
+        report.bytes_after
+    );
+
+    if report.dropped_undelivered() > 0 {
+        crate::utils::logging::log_remote_non_blocking(
+            "queue_retention_dropped",
+            "warn",
+            "Undelivered queue items dropped by retention policy",
+            serde_json::to_value(&report).ok(),
+        ).await;
+    }
+
+    Ok(report)
+}
+
+/// Periodic retention pass, starting right away so a bloated queue is trimmed at launch
+pub async fn start_retention_service() {
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(RETENTION_INTERVAL_SECS));
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = run_compaction(RetentionPolicy::from_env()).await {
+            log::error!("Queue retention pass failed: {}", e);
+        }
+        if let Err(e) = super::job_store::purge_old_jobs().await {
+            log::error!("Job journal cleanup failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        super::super::migrations::run(&mut conn).unwrap();
+        conn
+    }
+
+    fn insert(conn: &Connection, event_id: &str, kind: &str, timestamp: DateTime<Utc>, delivered_at: Option<DateTime<Utc>>) {
+        conn.execute(
+            "INSERT INTO outbox (event_id, kind, event_type, payload, timestamp, delivered_at) VALUES (?1, ?2, ?2, '{}', ?3, ?4)",
+            params![event_id, kind, timestamp, delivered_at],
+        ).unwrap();
+    }
+
+    fn count(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM outbox", [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_purges_old_delivered_rows_only() {
+        let mut conn = setup();
+        let now = Utc::now();
+        insert(&conn, "old", "event", now - chrono::Duration::days(10), Some(now - chrono::Duration::days(10)));
+        insert(&conn, "recent", "event", now, Some(now));
+        insert(&conn, "pending", "event", now - chrono::Duration::days(10), None);
+
+        let report = compact(&mut conn, &RetentionPolicy::default(), now).unwrap();
+
+        assert_eq!(report.delivered_purged, 1);
+        assert_eq!(count(&conn), 2);
+    }
+
+    #[test]
+    fn test_keeps_latest_heartbeat_per_hour() {
+        let mut conn = setup();
+        let hour = Utc::now() - chrono::Duration::hours(5);
+        for i in 0..6 {
+            insert(&conn, &format!("hb-{}", i), "heartbeat", hour + chrono::Duration::seconds(i * 10), None);
+        }
+        insert(&conn, "next-hour", "heartbeat", hour + chrono::Duration::hours(1), None);
+
+        let report = compact(&mut conn, &RetentionPolicy::default(), Utc::now()).unwrap();
+
+        assert_eq!(report.heartbeats_compacted, 5);
+        let kept: Vec<String> = conn
+            .prepare("SELECT event_id FROM outbox ORDER BY id").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap();
+        assert_eq!(kept, vec!["hb-5", "next-hour"]);
+    }
+
+    #[test]
+    fn test_size_cap_evicts_oldest_first() {
+        let mut conn = setup();
+        let now = Utc::now();
+        for i in 0..200 {
+            conn.execute(
+                "INSERT INTO outbox (event_id, kind, event_type, payload, timestamp) VALUES (?1, 'event', 'app_focus', ?2, ?3)",
+                params![format!("evt-{}", i), "x".repeat(2000), now + chrono::Duration::seconds(i)],
+            ).unwrap();
+        }
+        let policy = RetentionPolicy { max_queue_bytes: 200 * 1024, ..Default::default() };
+
+        let report = compact(&mut conn, &policy, now).unwrap();
+
+        assert!(report.events_evicted > 0);
+        assert!(queue_bytes(&conn).unwrap() <= policy.max_queue_bytes);
+        let oldest: String = conn
+            .query_row("SELECT event_id FROM outbox ORDER BY timestamp LIMIT 1", [], |row| row.get(0))
+            .unwrap();
+        assert_ne!(oldest, "evt-0");
+        assert_eq!(count(&conn), 200 - report.events_evicted as i64);
+    }
+
+    #[test]
+    fn test_other_tables_never_cost_undelivered_rows() {
+        let mut conn = setup();
+        let now = Utc::now();
+        insert(&conn, "pending", "event", now - chrono::Duration::days(3), None);
+        insert(&conn, "delivered", "event", now - chrono::Duration::days(1), Some(now));
+        for i in 0..200 {
+            conn.execute(
+                "INSERT INTO dead_letter (event_id, kind, event_type, payload, timestamp, failed_at) VALUES (?1, 'event', 'app_focus', ?2, ?3, ?3)",
+                params![format!("dead-{}", i), "x".repeat(2000), now],
+            ).unwrap();
+        }
+        let policy = RetentionPolicy { max_queue_bytes: 100 * 1024, ..Default::default() };
+        assert!(used_bytes(&conn).unwrap() > policy.max_queue_bytes);
+
+        let report = compact(&mut conn, &policy, now).unwrap();
+
+        assert_eq!(report.dropped_undelivered(), 0);
+        assert_eq!(report.delivered_purged, 0);
+        assert_eq!(count(&conn), 2);
+    }
+}
@@ -16,37 +16,40 @@ pub struct WorkSession {
 
 #[allow(dead_code)]
 pub async fn start_session() -> Result<i64> {
-    let conn = database::get_connection()?;
-    
-    // End any existing active sessions first
-    conn.execute(
-        "UPDATE work_sessions SET is_active = 0, ended_at = CURRENT_TIMESTAMP 
-         WHERE is_active = 1",
-        [],
-    )?;
-    
     let now = Utc::now();
-    
-    // Start new session
-    conn.execute(
-        "INSERT INTO work_sessions (started_at, is_active) VALUES (?1, 1)",
-        params![now],
-    )?;
-    
-    let session_id = conn.last_insert_rowid();
-    
-    Ok(session_id)
+
+    database::with_connection(move |conn| {
+        let tx = conn.transaction()?;
+
+        // End any existing active sessions first
+        tx.execute(
+            "UPDATE work_sessions SET is_active = 0, ended_at = CURRENT_TIMESTAMP 
+             WHERE is_active = 1",
+            [],
+        )?;
+
+        // Start new session
+        tx.execute(
+            "INSERT INTO work_sessions (started_at, is_active) VALUES (?1, 1)",
+            params![now],
+        )?;
+
+        let session_id = tx.last_insert_rowid();
+        tx.commit()?;
+
+        Ok(session_id)
+    }).await
 }
 
 #[allow(dead_code)]
 pub async fn end_session() -> Result<()> {
-    let conn = database::get_connection()?;
-    
-    let rows_affected = conn.execute(
-        "UPDATE work_sessions SET is_active = 0, ended_at = CURRENT_TIMESTAMP 
-         WHERE is_active = 1",
-        [],
-    )?;
+    let rows_affected = database::with_connection(|conn| {
+        Ok(conn.execute(
+            "UPDATE work_sessions SET is_active = 0, ended_at = CURRENT_TIMESTAMP 
+             WHERE is_active = 1",
+            [],
+        )?)
+    }).await?;
     
     if rows_affected > 0 {
     } else {
@@ -58,28 +61,28 @@ pub async fn end_session() -> Result<()> {
 
 #[allow(dead_code)]
 pub async fn get_current_session() -> Result<Option<WorkSession>> {
-    let conn = database::get_connection()?;
-    
-    let mut stmt = conn.prepare(
-        "SELECT id, started_at, ended_at, is_active 
-         FROM work_sessions 
-         WHERE is_active = 1 
-         ORDER BY started_at DESC 
-         LIMIT 1"
-    )?;
-    
-    match stmt.query_row([], |row| {
-        Ok(WorkSession {
-            id: row.get(0)?,
-            started_at: row.get(1)?,
-            ended_at: row.get(2)?,
-            is_active: row.get(3)?,
-        })
-    }) {
-        Ok(session) => Ok(Some(session)),
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(e.into()),
-    }
+    database::with_connection(|conn| {
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, started_at, ended_at, is_active 
+             FROM work_sessions 
+             WHERE is_active = 1 
+             ORDER BY started_at DESC 
+             LIMIT 1"
+        )?;
+
+        match stmt.query_row([], |row| {
+            Ok(WorkSession {
+                id: row.get(0)?,
+                started_at: row.get(1)?,
+                ended_at: row.get(2)?,
+                is_active: row.get(3)?,
+            })
+        }) {
+            Ok(session) => Ok(Some(session)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }).await
 }
 
 #[allow(dead_code)]
@@ -90,13 +93,13 @@ pub async fn is_session_active() -> Result<bool> {
 
 #[allow(dead_code)]
 pub async fn clear_all_active_sessions() -> Result<()> {
-    let conn = database::get_connection()?;
-    
-    let rows_affected = conn.execute(
-        "UPDATE work_sessions SET is_active = 0, ended_at = CURRENT_TIMESTAMP 
-         WHERE is_active = 1",
-        [],
-    )?;
+    let rows_affected = database::with_connection(|conn| {
+        Ok(conn.execute(
+            "UPDATE work_sessions SET is_active = 0, ended_at = CURRENT_TIMESTAMP 
+             WHERE is_active = 1",
+            [],
+        )?)
+    }).await?;
     
     if rows_affected > 0 {
         log::info!("Cleared {} active sessions from database", rows_affected);
@@ -114,73 +117,67 @@ pub async fn get_current_session_id() -> Result<Option<i64>> {
 }
 
 pub async fn get_session_start_time() -> Result<DateTime<Utc>> {
-    let conn = database::get_connection()?;
-    
-    let mut stmt = conn.prepare(
-        "SELECT started_at FROM work_sessions 
-         WHERE is_active = 1 
-         ORDER BY started_at DESC 
-         LIMIT 1"
-    )?;
-    
-    match stmt.query_row([], |row| {
-        Ok(row.get::<_, DateTime<Utc>>(0)?)
-    }) {
-        Ok(start_time) => Ok(start_time),
-        Err(rusqlite::Error::QueryReturnedNoRows) => {
-            // No active session, return current time
-            Ok(Utc::now())
-        },
-        Err(e) => Err(e.into()),
-    }
+    database::with_connection(|conn| {
+        let mut stmt = conn.prepare_cached(
+            "SELECT started_at FROM work_sessions 
+             WHERE is_active = 1 
+             ORDER BY started_at DESC 
+             LIMIT 1"
+        )?;
+
+        match stmt.query_row([], |row| row.get::<_, DateTime<Utc>>(0)) {
+            Ok(start_time) => Ok(start_time),
+            Err(rusqlite::Error::QueryReturnedNoRows) => {
+                // No active session, return current time
+                Ok(Utc::now())
+            },
+            Err(e) => Err(e.into()),
+        }
+    }).await
 }
 
 pub async fn get_today_time_totals() -> Result<(i64, i64)> {
-    let conn = database::get_connection()?;
-    
-    // Phase 2 Spec: Total Work = Σ(session clock_in→clock_out) in range
-    let mut work_stmt = conn.prepare(
-        "SELECT COALESCE(SUM(
-            CASE 
-                WHEN ended_at IS NOT NULL THEN 
-                    (strftime('%s', ended_at) - strftime('%s', started_at))
-                ELSE 
-                    (strftime('%s', 'now') - strftime('%s', started_at))
-            END
-        ), 0) as total_work_time
-         FROM work_sessions 
-         WHERE DATE(started_at) = DATE('now')"
-    )?;
-    
-    let total_work_time: i64 = work_stmt.query_row([], |row| {
-        Ok(row.get::<_, i64>(0)?)
-    })?;
-    
-    // Phase 2 Spec: Idle = minutes with no input ≥ threshold while clocked in
-    let mut idle_stmt = conn.prepare(
-        "SELECT COALESCE(SUM(
-            CASE 
-                WHEN end_time IS NOT NULL THEN 
-                    (strftime('%s', end_time) - strftime('%s', start_time))
-                ELSE 
-                    (strftime('%s', 'now') - strftime('%s', start_time))
-            END
-        ), 0) as total_idle_time
-         FROM app_usage_sessions 
-         WHERE DATE(start_time) = DATE('now') AND is_idle = 1"
-    )?;
-    
-    let idle_time: i64 = idle_stmt.query_row([], |row| {
-        Ok(row.get::<_, i64>(0)?)
-    })?;
-    
-    // Phase 2 Spec: Active = Work − Idle
-    let active_time = total_work_time - idle_time;
-    
-    // Ensure active time is not negative (in case of calculation errors)
-    let active_time = active_time.max(0);
-    
-    
-    Ok((active_time, idle_time))
+    database::with_connection(|conn| {
+        // Phase 2 Spec: Total Work = Σ(session clock_in→clock_out) in range
+        let mut work_stmt = conn.prepare_cached(
+            "SELECT COALESCE(SUM(
+                CASE 
+                    WHEN ended_at IS NOT NULL THEN 
+                        (strftime('%s', ended_at) - strftime('%s', started_at))
+                    ELSE 
+                        (strftime('%s', 'now') - strftime('%s', started_at))
+                END
+            ), 0) as total_work_time
+             FROM work_sessions 
+             WHERE DATE(started_at) = DATE('now')"
+        )?;
+
+        let total_work_time: i64 = work_stmt.query_row([], |row| row.get(0))?;
+
+        // Phase 2 Spec: Idle = minutes with no input ≥ threshold while clocked in
+        let mut idle_stmt = conn.prepare_cached(
+            "SELECT COALESCE(SUM(
+                CASE 
+                    WHEN end_time IS NOT NULL THEN 
+                        (strftime('%s', end_time) - strftime('%s', start_time))
+                    ELSE 
+                        (strftime('%s', 'now') - strftime('%s', start_time))
+                END
+            ), 0) as total_idle_time
+             FROM app_usage_sessions 
+             WHERE DATE(start_time) = DATE('now') AND is_idle = 1"
+        )?;
+
+        let idle_time: i64 = idle_stmt.query_row([], |row| row.get(0))?;
+
+        // Phase 2 Spec: Active = Work − Idle
+        let active_time = total_work_time - idle_time;
+
+        // Ensure active time is not negative (in case of calculation errors)
+        let active_time = active_time.max(0);
+
+
+        Ok((active_time, idle_time))
+    }).await
 }
 
@@ -3,14 +3,20 @@ use log::LevelFilter;
 use std::io::Write;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
 use std::sync::LazyLock;
 
+use crate::api::client::{ApiClient, Auth, Timeout};
+
 // Global configuration for remote logging
 static REMOTE_LOGGING_ENABLED: AtomicBool = AtomicBool::new(false);
 static DEBUG_MODE: AtomicBool = AtomicBool::new(false);
 static ALLOWED_LEVELS: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
 
+// Last lines written to the log, for diagnostics bundles (logs only go to stdout)
+const RECENT_LOG_LINES: usize = 500;
+static RECENT_LOGS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
 pub fn init() {
     let mut builder = Builder::from_default_env();
     
@@ -18,13 +24,14 @@ pub fn init() {
         .target(Target::Stdout)
         .filter_level(LevelFilter::Error) // Only show errors by default
         .format(|buf, record| {
-            writeln!(
-                buf,
+            let line = format!(
                 "[{}] [{}] {}",
                 chrono::Utc::now().format("%Y-%m-%d %H:%M:%S%.3f"),
                 record.level(),
                 record.args()
-            )
+            );
+            remember_line(&line);
+            writeln!(buf, "{}", line)
         })
         .init();
 
@@ -32,6 +39,23 @@ pub fn init() {
     init_remote_logging_config();
 }
 
+fn remember_line(line: &str) {
+    if let Ok(mut recent) = RECENT_LOGS.lock() {
+        if recent.len() >= RECENT_LOG_LINES {
+            recent.pop_front();
+        }
+        recent.push_back(line.to_string());
+    }
+}
+
+/// The most recent log lines, oldest first
+pub fn recent_logs(limit: usize) -> Vec<String> {
+    let Ok(recent) = RECENT_LOGS.lock() else {
+        return Vec::new();
+    };
+    recent.iter().skip(recent.len().saturating_sub(limit)).cloned().collect()
+}
+
 /// Initialize remote logging configuration
 fn init_remote_logging_config() {
     // Check if debug mode is enabled via environment variable
@@ -103,36 +127,22 @@ pub async fn log_remote_non_blocking(event: &str, level: &str, message: &str, co
 
     // Spawn and detach the network call with very short timeout
     tokio::spawn(async move {
-        // Very short timeout client to prevent hanging
-        let client = match reqwest::Client::builder()
-            .timeout(std::time::Duration::from_millis(500))
-            .build() {
-                Ok(c) => c,
-                Err(_) => {
-                    // Silently fail - don't log client creation errors
-                    return;
-                }
-            };
-
         // Resolve server URL from storage (falls back internally to default)
-        let base_url = match crate::storage::get_server_url().await {
-            Ok(u) => u,
+        let client = match ApiClient::new().await {
+            Ok(c) => c,
             Err(_) => {
                 // Silently fail - don't log server URL errors
                 return;
             }
         };
-        let base = base_url.trim_end_matches('/');
-        let url = format!("{}/api/logs", base);
+        let request = client
+            .post("/api/logs", &payload)
+            .auth(Auth::None)
+            .timeout(Timeout::Telemetry);
         
         // Use a timeout wrapper to ensure we don't hang
         match tokio::time::timeout(std::time::Duration::from_millis(300), async {
-            client
-                .post(url)
-                .header("Content-Type", "application/json")
-                .json(&payload)
-                .send()
-                .await
+            request.send().await
         }).await {
             Ok(Ok(resp)) => {
                 if !resp.status().is_success() {
@@ -181,30 +191,22 @@ pub fn get_remote_logging_config() -> (bool, bool, Vec<String>) {
 /// Fetch logging configuration from backend API
 pub async fn fetch_logging_config_from_backend() -> Result<(), String> {
     // Get server URL
-    let server_url = match crate::storage::get_server_url().await {
-        Ok(url) => url,
+    let client = match ApiClient::new().await {
+        Ok(client) => client,
         Err(e) => {
             log::warn!("Failed to get server URL for logging config: {}", e);
             return Ok(());
         }
     };
 
-    // Create HTTP client
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(10))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-
-    let config_url = format!("{}/api/system/logging-config", server_url.trim_end_matches('/'));
+    let request = client
+        .get("/api/system/logging-config")
+        .auth(Auth::None)
+        .timeout(Timeout::Interactive);
     
-    log::info!("🔍 Fetching global logging configuration from: {}", config_url);
+    log::info!("🔍 Fetching global logging configuration from: {}", request.url());
 
-    match client
-        .get(&config_url)
-        .header("Content-Type", "application/json")
-        .send()
-        .await
-    {
+    match request.send().await {
         Ok(response) => {
             if response.status().is_success() {
                 match response.json::<serde_json::Value>().await {
@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod policy_tests {
-    use trackex_agent_lib::policy::toggles::{PolicyConfig};
+    use trackex_agent_lib::policy::toggles::{IdleOverride, PolicyConfig};
 
     #[test]
     fn test_default_policy_config() {
@@ -11,7 +11,7 @@ mod policy_tests {
         assert!(config.domain_only_mode);
         assert!(config.title_redaction_enabled);
         assert_eq!(config.idle_threshold_seconds, 300);
-        assert!(!config.allowlist_patterns.is_empty());
+        assert!(config.allowlist_patterns.is_empty());
     }
 
     #[test]
@@ -128,4 +128,18 @@ mod policy_tests {
         std::env::remove_var("TRACKEX_SCREENSHOT_INTERVAL");
         std::env::remove_var("TRACKEX_IDLE_THRESHOLD");
     }
+
+    #[test]
+    fn test_idle_threshold_overrides() {
+        let config = PolicyConfig {
+            idle_threshold_seconds: 300,
+            idle_overrides: vec![IdleOverride { app: "zoom".to_string(), idle_threshold_seconds: 1800 }],
+            ..PolicyConfig::default()
+        };
+
+        // Matched on app ID or name, ignoring case
+        assert_eq!(config.idle_threshold_for("us.zoom.xos", "zoom.us"), 1800);
+        assert_eq!(config.idle_threshold_for("", "Zoom Meeting"), 1800);
+        assert_eq!(config.idle_threshold_for("com.apple.Safari", "Safari"), 300);
+    }
 }
@@ -1,17 +1,24 @@
 #[cfg(test)]
 mod queue_tests {
     use trackex_agent_lib::storage::{database, offline_queue};
+    use trackex_agent_lib::storage::database::DatabaseConfig;
     use serde_json::json;
-    use tempfile::tempdir;
-    
-    async fn setup_test_db() -> Result<(), Box<dyn std::error::Error>> {
+    use tokio::sync::{Mutex, MutexGuard};
+
+    // The database handle is process-wide, so tests take turns and each one
+    // gets a fresh in-memory database instead of the real user database
+    static TEST_DB_LOCK: Mutex<()> = Mutex::const_new(());
+
+    async fn setup_test_db() -> Result<MutexGuard<'static, ()>, Box<dyn std::error::Error>> {
+        let guard = TEST_DB_LOCK.lock().await;
+        database::configure(DatabaseConfig::in_memory());
         database::init().await?;
-        Ok(())
+        Ok(guard)
     }
 
     #[tokio::test]
     async fn test_queue_event() {
-        setup_test_db().await.unwrap();
+        let _db = setup_test_db().await.unwrap();
         
         let event_data = json!({
             "app_name": "Test App",
@@ -24,7 +31,7 @@ mod queue_tests {
 
     #[tokio::test]
     async fn test_get_pending_events() {
-        setup_test_db().await.unwrap();
+        let _db = setup_test_db().await.unwrap();
         
         // Queue some test events
         let event_data = json!({"test": "data"});
@@ -37,7 +44,7 @@ mod queue_tests {
 
     #[tokio::test]
     async fn test_mark_event_processed() {
-        setup_test_db().await.unwrap();
+        let _db = setup_test_db().await.unwrap();
         
         // Queue an event
         let event_data = json!({"test": "data"});
@@ -59,7 +66,7 @@ mod queue_tests {
 
     #[tokio::test]
     async fn test_queue_heartbeat() {
-        setup_test_db().await.unwrap();
+        let _db = setup_test_db().await.unwrap();
         
         let heartbeat_data = json!({
             "status": "active",
@@ -72,22 +79,4 @@ mod queue_tests {
         let heartbeats = offline_queue::get_pending_heartbeats().await.unwrap();
         assert!(!heartbeats.is_empty());
     }
-
-    #[tokio::test]
-    async fn test_queue_stats() {
-        setup_test_db().await.unwrap();
-        
-        // Queue some test data
-        let event_data = json!({"test": "data"});
-        offline_queue::queue_event("test_event", &event_data).await.unwrap();
-        
-        let heartbeat_data = json!({"status": "active"});
-        offline_queue::queue_heartbeat(&heartbeat_data).await.unwrap();
-        
-        let (pending_events, _processed_events, pending_heartbeats, _processed_heartbeats) = 
-            offline_queue::get_queue_stats().await.unwrap();
-        
-        assert!(pending_events > 0);
-        assert!(pending_heartbeats > 0);
-    }
 }
@@ -1,11 +1,14 @@
 #[cfg(test)]
 mod redaction_tests {
-    use trackex_agent_lib::policy::privacy::{
-        extract_domain_from_title, 
-        redact_window_title, 
-        should_use_domain_only,
-        get_default_allowlist_patterns
-    };
+    use trackex_agent_lib::policy::filter::filter_title;
+    use trackex_agent_lib::policy::privacy::{extract_domain_from_title, should_use_domain_only};
+    use trackex_agent_lib::policy::toggles::PolicyConfig;
+
+    // Titles of an app that isn't a browser, filtered with `patterns` as the allowlist
+    fn redact_window_title(title: &str, patterns: &[String]) -> String {
+        let policy = PolicyConfig { allowlist_patterns: patterns.to_vec(), ..PolicyConfig::default() };
+        filter_title(&policy, "com.example.Editor", title)
+    }
 
     #[test]
     fn test_browser_detection() {
@@ -91,41 +94,18 @@ mod redaction_tests {
 
     #[test]
     fn test_title_redaction_domain_fallback() {
-        let empty_patterns: Vec<String> = vec![];
-        
-        // Should extract domain when no patterns provided
-        assert_eq!(
-            redact_window_title("Google - Chrome", &empty_patterns),
-            "Google"
-        );
-        
+        let policy = PolicyConfig::default();
+
+        // Browsers keep the site's host name, and nothing else
         assert_eq!(
-            redact_window_title("github.com | Code Repository", &empty_patterns),
+            filter_title(&policy, "com.google.Chrome", "github.com | Code Repository"),
             "github.com"
         );
-        
-        // Should redact when no domain extractable
-        assert_eq!(
-            redact_window_title("Random Document", &empty_patterns),
-            "[Redacted]"
-        );
-    }
+        assert_eq!(filter_title(&policy, "com.google.Chrome", "Google - Chrome"), "[Redacted]");
 
-    #[test]
-    fn test_default_allowlist_patterns() {
-        let patterns = get_default_allowlist_patterns();
-        assert!(!patterns.is_empty());
-        
-        // Test that default patterns work
-        assert_eq!(
-            redact_window_title("Dashboard", &patterns),
-            "Dashboard"
-        );
-        
-        assert_eq!(
-            redact_window_title("google.com", &patterns),
-            "google.com"
-        );
+        // Other apps have no sites to fall back to
+        assert_eq!(redact_window_title("github.com | Code Repository", &[]), "[Redacted]");
+        assert_eq!(redact_window_title("Random Document", &[]), "[Redacted]");
     }
 
     #[test]